@@ -1,14 +1,37 @@
 use proc_macro::TokenStream;
 use proc_macro_error::abort;
 use syn::parse_macro_input;
+use syn::AttributeArgs;
 use syn::Item;
 use syn::ItemEnum;
+use syn::Lit;
+use syn::Meta;
+use syn::NestedMeta;
 
+const DEFAULT_SCHEDULED_TASKS_CRON: &str = "0 */5 * * * * *"; // Every 5 minutes
+
+/// Accepts an optional `scheduled_tasks_cron = "..."` argument to override how often
+/// the scheduled tasks (e.g. resending events) are run
 // #[proc_macro_error]
 #[proc_macro_attribute]
-pub fn private_event(_attrs: TokenStream, input: TokenStream) -> TokenStream {
+pub fn private_event(attrs: TokenStream, input: TokenStream) -> TokenStream {
+    let attr_args = parse_macro_input!(attrs as AttributeArgs);
     let input = parse_macro_input!(input as Item);
-    // let attr_args: proc_macro2::TokenStream = attrs.into();
+
+    let mut scheduled_tasks_cron = DEFAULT_SCHEDULED_TASKS_CRON.to_string();
+    for arg in attr_args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(name_value))
+                if name_value.path.is_ident("scheduled_tasks_cron") =>
+            {
+                let Lit::Str(cron) = name_value.lit else {
+                    abort!(name_value.lit, "scheduled_tasks_cron must be a string")
+                };
+                scheduled_tasks_cron = cron.value();
+            }
+            _ => abort!(arg, "Unknown argument for private_event"),
+        }
+    }
 
     let ident = match &input {
         Item::Enum(ItemEnum { ident, .. }) => ident,
@@ -33,17 +56,32 @@ pub fn private_event(_attrs: TokenStream, input: TokenStream) -> TokenStream {
         }
 
         #[hdk_extern]
-        pub fn receive_message(input: private_event_sourcing::ReceiveMessageInput) -> ExternResult<()> {
+        pub fn receive_message(input: private_event_sourcing::ReceiveMessageInput) -> ExternResult<private_event_sourcing::ReceiveMessageReport> {
             private_event_sourcing::receive_message::<#ident>(input.provenance, input.message)
         }
 
+        #[hdk_extern]
+        pub fn query_undecodable_private_events() -> ExternResult<Vec<private_event_sourcing::UndecodablePrivateEvent>> {
+            private_event_sourcing::query_undecodable_private_events::<#ident>()
+        }
+
+        #[hdk_extern]
+        pub fn get_delivery_status(event_hash: EntryHash) -> ExternResult<std::collections::BTreeMap<AgentPubKeyB64, private_event_sourcing::DeliveryStatus>> {
+            private_event_sourcing::get_delivery_status::<#ident>(event_hash)
+        }
+
+        #[hdk_extern]
+        pub fn mark_events_as_read(events_hashes: BTreeSet<EntryHash>) -> ExternResult<()> {
+            private_event_sourcing::mark_events_as_read::<#ident>(events_hashes)
+        }
+
         #[hdk_extern(infallible)]
         fn scheduled_tasks(_: Option<Schedule>) -> Option<Schedule> {
             if let Err(err) = private_event_sourcing::scheduled_tasks::<#ident>() {
                 error!("Failed to perform scheduled tasks: {err:?}");
             }
 
-            Some(Schedule::Persisted("0 */5 * * * * *".into())) // Every 5 minutes
+            Some(Schedule::Persisted(#scheduled_tasks_cron.into()))
         }
     };
     output.into()
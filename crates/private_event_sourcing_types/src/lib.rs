@@ -6,6 +6,11 @@ pub struct Message {
     pub private_events: Vec<PrivateEventEntry>,
     pub events_sent_to_recipients: Vec<EventSentToRecipients>,
     pub acknowledgements: Vec<Acknowledgement>,
+    /// Defaults to empty so that messages from agents running older versions can still be received
+    #[serde(default)]
+    pub read_receipts: Vec<ReadReceipt>,
+    #[serde(default)]
+    pub rejections: Vec<Rejection>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -18,12 +23,74 @@ pub struct ReceiveMessageInput {
 pub struct PrivateEventContent<T> {
     pub event_type: String,
     pub event: T,
+    /// The events that causally precede this event (its parents in the event DAG)
+    /// Skipped when empty so that events created before this field existed keep their hash and signature
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_events: Vec<EntryHash>,
+    /// The version of the schema of the event at the time it was created
+    /// Skipped when 0 so that events created before this field existed keep their hash and signature
+    #[serde(default, skip_serializing_if = "is_initial_schema_version")]
+    pub schema_version: u32,
+}
+
+fn is_initial_schema_version(schema_version: &u32) -> bool {
+    *schema_version == 0
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct SignedContent<T> {
     pub timestamp: Timestamp,
     pub content: T,
+    /// Skipped when missing so that entries created before this field existed keep their signature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hlc: Option<HybridLogicalClock>,
+}
+
+impl<T> SignedContent<T> {
+    /// The hybrid logical clock for this content, falling back to its timestamp for contents created without one
+    pub fn clock(&self) -> HybridLogicalClock {
+        self.hlc.clone().unwrap_or(HybridLogicalClock {
+            physical: self.timestamp,
+            logical: 0,
+        })
+    }
+}
+
+/// Hybrid logical clock: a physical timestamp plus a logical counter that orders
+/// the events that happened within the same physical timestamp
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HybridLogicalClock {
+    pub physical: Timestamp,
+    pub logical: u32,
+}
+
+/// Maximum value of the logical counter of a hybrid logical clock
+///
+/// Received clocks with a greater logical counter are invalid, and our own clock moves on to
+/// the next physical tick when its logical counter reaches it
+pub const MAX_LOGICAL_CLOCK: u32 = 1 << 20;
+
+impl HybridLogicalClock {
+    /// The clock for a new local event, given the latest clock that we have observed
+    pub fn tick(latest: Option<HybridLogicalClock>, now: Timestamp) -> HybridLogicalClock {
+        match latest {
+            Some(latest) if latest.physical >= now => match latest.logical.checked_add(1) {
+                Some(logical) if logical <= MAX_LOGICAL_CLOCK => HybridLogicalClock {
+                    physical: latest.physical,
+                    logical,
+                },
+                // The logical counter is exhausted: move on to the next physical tick
+                _ => HybridLogicalClock {
+                    physical: Timestamp::from_micros(latest.physical.as_micros().saturating_add(1)),
+                    logical: 0,
+                },
+            },
+            _ => HybridLogicalClock {
+                physical: now,
+                logical: 0,
+            },
+        }
+    }
 }
 
 impl<T: std::fmt::Debug + Serialize + DeserializeOwned> std::convert::TryFrom<&SignedContent<T>>
@@ -69,10 +136,24 @@ where
     T: Clone + std::fmt::Debug + Serialize + DeserializeOwned,
 {
     pub fn build(content: T) -> ExternResult<Self> {
-        let timestamp = sys_time()?;
+        Self::build_with_clock(content, None)
+    }
+
+    /// Builds the signed entry stamping it with the next tick of the given latest observed clock
+    ///
+    /// The timestamp of the entry is the physical part of its clock, which can be slightly ahead of
+    /// our own time if we have observed clocks from the future
+    pub fn build_with_clock(
+        content: T,
+        latest_clock: Option<HybridLogicalClock>,
+    ) -> ExternResult<Self> {
+        let clock = HybridLogicalClock::tick(latest_clock, sys_time()?);
+        let timestamp = clock.physical;
+        let hlc = Some(clock);
         let payload: SignedContent<T> = SignedContent {
             timestamp,
             content: content.clone(),
+            hlc: hlc.clone(),
         };
         let bytes = SerializedBytes::try_from(payload)
             .clone()
@@ -83,7 +164,11 @@ where
         Ok(SignedEntry {
             author: my_pub_key,
             signature,
-            payload: SignedContent { timestamp, content },
+            payload: SignedContent {
+                timestamp,
+                content,
+                hlc,
+            },
         })
     }
 
@@ -117,8 +202,137 @@ pub struct EventSentToRecipients(pub SignedEntry<EventSentToRecipientsContent>);
 #[derive(Serialize, Deserialize, SerializedBytes, Debug, Clone, PartialEq, Eq)]
 pub struct AcknowledgementContent {
     pub private_event_hash: EntryHash,
+    /// Other events acknowledged together with `private_event_hash`, for cumulative acknowledgements
+    /// Skipped when empty so that acknowledgements created before this field existed keep their signature
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub other_private_event_hashes: BTreeSet<EntryHash>,
+}
+
+impl AcknowledgementContent {
+    /// Builds a cumulative acknowledgement for all the given events, or None if there are no events
+    pub fn for_events(mut private_event_hashes: BTreeSet<EntryHash>) -> Option<Self> {
+        let private_event_hash = private_event_hashes.pop_first()?;
+        Some(AcknowledgementContent {
+            private_event_hash,
+            other_private_event_hashes: private_event_hashes,
+        })
+    }
+
+    /// All the events acknowledged by this acknowledgement
+    pub fn private_event_hashes(&self) -> BTreeSet<EntryHash> {
+        let mut private_event_hashes = self.other_private_event_hashes.clone();
+        private_event_hashes.insert(self.private_event_hash.clone());
+        private_event_hashes
+    }
+
+    pub fn acknowledges(&self, private_event_hash: &EntryHash) -> bool {
+        self.private_event_hash.eq(private_event_hash)
+            || self.other_private_event_hashes.contains(private_event_hash)
+    }
 }
 
 #[hdk_entry_helper]
 #[derive(Clone, PartialEq, Eq)]
 pub struct Acknowledgement(pub SignedEntry<AcknowledgementContent>);
+
+#[derive(Serialize, Deserialize, SerializedBytes, Debug, Clone, PartialEq, Eq)]
+pub struct ReadReceiptContent {
+    pub private_event_hash: EntryHash,
+}
+
+/// Explicit confirmation that an agent has seen or read an event,
+/// as opposed to an acknowledgement that only means that the event was committed to their source chain
+#[hdk_entry_helper]
+#[derive(Clone, PartialEq, Eq)]
+pub struct ReadReceipt(pub SignedEntry<ReadReceiptContent>);
+
+#[derive(Serialize, Deserialize, SerializedBytes, Debug, Clone, PartialEq, Eq)]
+pub struct RejectionContent {
+    pub event_hash: EntryHash,
+    pub reason: String,
+}
+
+/// Negative acknowledgement: the event was received but was invalid, so it was discarded
+#[hdk_entry_helper]
+#[derive(Clone, PartialEq, Eq)]
+pub struct Rejection(pub SignedEntry<RejectionContent>);
+
+/// The position of an event in the total order of events, identical for every agent that has the same events
+///
+/// Events are ordered by their lamport clock first, which is always greater than the lamport clocks of their
+/// previous events, so this order respects the causal order between events
+///
+/// Concurrent events with the same lamport clock are ordered by their hybrid logical clock, so that events
+/// without previous events, like the ones created before they existed, keep their chronological order,
+/// and finally by author and hash to break the remaining ties
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventOrderKey {
+    pub lamport_clock: u32,
+    pub clock: HybridLogicalClock,
+    pub author: AgentPubKey,
+    pub event_hash: EntryHashB64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(physical: i64, logical: u32) -> HybridLogicalClock {
+        HybridLogicalClock {
+            physical: Timestamp::from_micros(physical),
+            logical,
+        }
+    }
+
+    #[test]
+    fn tick_without_latest_clock_uses_now() {
+        let now = Timestamp::from_micros(10);
+        assert_eq!(HybridLogicalClock::tick(None, now), clock(10, 0));
+    }
+
+    #[test]
+    fn tick_with_latest_clock_in_the_past_uses_now() {
+        let now = Timestamp::from_micros(10);
+        assert_eq!(
+            HybridLogicalClock::tick(Some(clock(5, 3)), now),
+            clock(10, 0)
+        );
+    }
+
+    #[test]
+    fn tick_with_latest_clock_ahead_increments_logical() {
+        let now = Timestamp::from_micros(10);
+        assert_eq!(
+            HybridLogicalClock::tick(Some(clock(10, 3)), now),
+            clock(10, 4)
+        );
+        assert_eq!(
+            HybridLogicalClock::tick(Some(clock(20, 0)), now),
+            clock(20, 1)
+        );
+    }
+
+    #[test]
+    fn tick_with_exhausted_logical_moves_to_next_physical_tick() {
+        let now = Timestamp::from_micros(10);
+        assert_eq!(
+            HybridLogicalClock::tick(Some(clock(10, MAX_LOGICAL_CLOCK)), now),
+            clock(11, 0)
+        );
+        assert_eq!(
+            HybridLogicalClock::tick(Some(clock(10, u32::MAX)), now),
+            clock(11, 0)
+        );
+    }
+
+    #[test]
+    fn ticks_are_strictly_increasing() {
+        let now = Timestamp::from_micros(10);
+        let mut latest = clock(10, MAX_LOGICAL_CLOCK - 2);
+        for _ in 0..5 {
+            let next = HybridLogicalClock::tick(Some(latest.clone()), now);
+            assert!(next > latest);
+            latest = next;
+        }
+    }
+}
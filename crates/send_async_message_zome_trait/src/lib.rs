@@ -11,5 +11,6 @@ pub struct SendAsyncMessageInput {
 
 #[zome_trait]
 pub trait SendAsyncMessage {
-    fn send_async_message(input: SendAsyncMessageInput) -> ExternResult<()>;
+    // Returns the recipients to which the message was not sent because they reached the rate limit
+    fn send_async_message(input: SendAsyncMessageInput) -> ExternResult<BTreeSet<AgentPubKey>>;
 }
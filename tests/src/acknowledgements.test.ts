@@ -0,0 +1,72 @@
+import { toPromise } from '@darksoil-studio/holochain-signals';
+import { encodeHashToBase64 } from '@holochain/client';
+import { runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import { Acknowledgement } from '../../ui/src/types.js';
+import { receiveMessage, setup, waitUntil } from './setup.js';
+
+function acknowledgedEvents(acknowledgement: Acknowledgement): Array<string> {
+	return [
+		acknowledgement.payload.content.private_event_hash,
+		...(acknowledgement.payload.content.other_private_event_hashes || []),
+	].map(encodeHashToBase64);
+}
+
+test('events received together are acknowledged with a single acknowledgement', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await bob.player.conductor.shutDown();
+
+		for (let i = 0; i < 3; i++) {
+			await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'create_private_shared_entry',
+				payload: {
+					type: 'SharedEntry',
+					recipient: bob.player.agentPubKey,
+					content: `${i}`,
+				},
+			});
+		}
+		const privateEventEntries =
+			await alice.store.client.queryPrivateEventEntries();
+		const eventHashes = Object.keys(privateEventEntries).sort();
+
+		await bob.startUp();
+		await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: Object.values(privateEventEntries),
+		});
+
+		const bobAcknowledgements = async () =>
+			(await bob.store.client.queryAcknowledgementEntries()).filter(
+				a =>
+					encodeHashToBase64(a.author) ===
+					encodeHashToBase64(bob.player.agentPubKey),
+			);
+		await waitUntil(
+			async () => (await bobAcknowledgements()).length > 0,
+			20_000,
+		);
+
+		const acknowledgements = await bobAcknowledgements();
+		assert.equal(acknowledgements.length, 1);
+		assert.deepEqual(
+			acknowledgedEvents(acknowledgements[0]).sort(),
+			eventHashes,
+		);
+
+		// The cumulative acknowledgement acknowledges all the events for alice
+		await waitUntil(async () => {
+			const acknowledgements = await toPromise(alice.store.acknowledgements);
+			return eventHashes.every(
+				eventHash =>
+					!!acknowledgements[eventHash]?.[
+						encodeHashToBase64(bob.player.agentPubKey)
+					],
+			);
+		}, 20_000);
+	});
+});
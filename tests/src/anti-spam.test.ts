@@ -0,0 +1,183 @@
+import {
+	decodeHashFromBase64,
+	encodeHashToBase64,
+	fakeAgentPubKey,
+} from '@holochain/client';
+import { pause, runScenario } from '@holochain/tryorama';
+import { assert, expect, test } from 'vitest';
+
+import {
+	queryDeadLetters,
+	sendAsyncMessage,
+	setup,
+	waitUntil,
+} from './setup.js';
+
+test('async messages from blocked agents are dropped', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob, carol] = await setup(scenario, 3);
+
+		await bob.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'encrypted_links',
+			fn_name: 'block_agent',
+			payload: alice.player.agentPubKey,
+		});
+		const blockedAgents: Array<Uint8Array> =
+			await bob.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'encrypted_links',
+				fn_name: 'query_blocked_agents',
+				payload: undefined,
+			});
+		assert.deepEqual(blockedAgents.map(encodeHashToBase64), [
+			encodeHashToBase64(alice.player.agentPubKey),
+		]);
+
+		// Messages for a zome that is not allowed would end up in the dead-letter queue
+		for (const sender of [alice, carol]) {
+			await sendAsyncMessage(
+				sender.store,
+				[bob.player.agentPubKey],
+				'unknown_zome',
+				'message',
+				{},
+			);
+		}
+
+		await waitUntil(
+			async () => (await queryDeadLetters(bob.store)).length > 0,
+			120_000,
+		);
+		await pause(35_000);
+
+		const deadLetters = await queryDeadLetters(bob.store);
+		assert.deepEqual(
+			deadLetters.map(d => encodeHashToBase64(d.dead_letter.provenance)),
+			[encodeHashToBase64(carol.player.agentPubKey)],
+		);
+	});
+});
+
+test('async messages that are too big are rejected', async () => {
+	await runScenario(async scenario => {
+		const [alice] = await setup(scenario, 1);
+
+		const content = Array.from(Array(1_100_000)).fill('a').join('');
+
+		await expect(
+			sendAsyncMessage(
+				alice.store,
+				[await fakeAgentPubKey()],
+				'example',
+				'message',
+				{
+					rejections: [
+						{
+							author: alice.player.agentPubKey,
+							signature: new Uint8Array(64),
+							payload: {
+								timestamp: Date.now() * 1000,
+								content: {
+									event_hash: new Uint8Array(39),
+									reason: content,
+								},
+							},
+						},
+					],
+				},
+			),
+		).rejects.toThrow();
+	});
+});
+
+test('private events too big to be sent are not created', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await expect(
+			alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'create_private_shared_entry',
+				payload: {
+					type: 'SharedEntry',
+					recipient: bob.player.agentPubKey,
+					content: Array.from(Array(600_000)).fill('a').join(''),
+				},
+			}),
+		).rejects.toThrow();
+
+		const privateEvents = await alice.store.client.queryPrivateEventEntries();
+		assert.equal(Object.keys(privateEvents).length, 0);
+	});
+});
+
+test('async messages above the rate limit are deferred instead of failing', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		const MAX_MESSAGES_PER_MINUTE = 200;
+
+		for (let i = 0; i < MAX_MESSAGES_PER_MINUTE; i++) {
+			await sendAsyncMessage(
+				alice.store,
+				[bob.player.agentPubKey],
+				'example',
+				`message-${i}`,
+				{},
+			);
+		}
+
+		// Sending one more message doesn't fail, it's just not sent yet
+		const rateLimitedRecipients = await sendAsyncMessage(
+			alice.store,
+			[bob.player.agentPubKey],
+			'example',
+			'one-too-many',
+			{},
+		);
+		assert.deepEqual(
+			rateLimitedRecipients.map(encodeHashToBase64),
+			[encodeHashToBase64(bob.player.agentPubKey)],
+		);
+
+		// Creating and resending events to a recipient above the limit doesn't fail either,
+		// and they are not recorded as sent to it
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'deferred',
+			},
+		});
+		const privateEvents = await alice.store.client.queryPrivateEventEntries();
+		assert.equal(Object.keys(privateEvents).length, 1);
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'send_new_events',
+			payload: Object.keys(privateEvents).map(decodeHashFromBase64),
+		});
+		// Bob may still have received the event by remote signal and acknowledged it
+		const deliveryStatus = await alice.store.client.getDeliveryStatus(
+			decodeHashFromBase64(Object.keys(privateEvents)[0]),
+		);
+		assert.notEqual(
+			deliveryStatus[encodeHashToBase64(bob.player.agentPubKey)].type,
+			'Sent',
+		);
+
+		// The limit is per recipient
+		await sendAsyncMessage(
+			alice.store,
+			[await fakeAgentPubKey()],
+			'example',
+			'other-recipient',
+			{},
+		);
+	});
+});
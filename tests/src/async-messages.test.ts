@@ -0,0 +1,226 @@
+import { encodeHashToBase64 } from '@holochain/client';
+import { pause, runScenario } from '@holochain/tryorama';
+import { assert, expect, test } from 'vitest';
+
+import {
+	buildPrivateEventEntry,
+	queryDeadLetters,
+	sendAsyncMessage,
+	setup,
+	waitUntil,
+} from './setup.js';
+
+test('async messages with the same message id are only delivered once', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		// Messages for a zome that is not allowed end up in the dead-letter queue,
+		// which makes every delivery visible
+		for (const messageId of ['duplicated', 'duplicated', 'other']) {
+			await sendAsyncMessage(
+				alice.store,
+				[bob.player.agentPubKey],
+				'unknown_zome',
+				messageId,
+				{},
+			);
+		}
+
+		await waitUntil(
+			async () => (await queryDeadLetters(bob.store)).length >= 2,
+			120_000,
+		);
+		// Give the inbox another run to process any duplicated message
+		await pause(35_000);
+
+		const deadLetters = await queryDeadLetters(bob.store);
+		assert.equal(deadLetters.length, 2);
+	});
+});
+
+test('async messages in the inbox are handed off to their target zome', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		const privateEventEntry = await buildPrivateEventEntry(alice.store, {
+			event_type: 'SharedEntry',
+			event: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'hello',
+			},
+		});
+
+		await sendAsyncMessage(
+			alice.store,
+			[bob.player.agentPubKey],
+			'example',
+			'message',
+			{
+				private_events: [privateEventEntry],
+			},
+		);
+
+		await waitUntil(async () => {
+			const privateEvents = await bob.store.client.queryPrivateEventEntries();
+			return Object.keys(privateEvents).length === 1;
+		}, 120_000);
+
+		// The delivered message was removed from the inbox instead of failing again
+		await pause(35_000);
+		assert.equal((await queryDeadLetters(bob.store)).length, 0);
+	});
+});
+
+test('async messages with private events are handed off again even if their message id was already processed', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		const buildEvent = (content: string) =>
+			buildPrivateEventEntry(alice.store, {
+				event_type: 'SharedEntry',
+				event: {
+					type: 'SharedEntry',
+					recipient: bob.player.agentPubKey,
+					content,
+				},
+			});
+		const firstEvent = await buildEvent('hello');
+
+		await sendAsyncMessage(
+			alice.store,
+			[bob.player.agentPubKey],
+			'example',
+			'resent',
+			{
+				private_events: [firstEvent],
+			},
+		);
+		await waitUntil(async () => {
+			const privateEvents = await bob.store.client.queryPrivateEventEntries();
+			return Object.keys(privateEvents).length === 1;
+		}, 120_000);
+
+		// As if the acknowledgement was lost: the event is resent with the same message id,
+		// and the resend must reach the target zome again
+		const secondEvent = await buildEvent('bye');
+		// Let the removal of the first message from bob's inbox propagate,
+		// otherwise the sender skips the message as still pending
+		await pause(35_000);
+		await sendAsyncMessage(
+			alice.store,
+			[bob.player.agentPubKey],
+			'example',
+			'resent',
+			{
+				private_events: [firstEvent, secondEvent],
+			},
+		);
+		await waitUntil(async () => {
+			const privateEvents = await bob.store.client.queryPrivateEventEntries();
+			return Object.keys(privateEvents).length === 2;
+		}, 120_000);
+
+		assert.equal((await queryDeadLetters(bob.store)).length, 0);
+	});
+});
+
+test('undeliverable async messages are kept in the dead-letter queue until they are purged', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await sendAsyncMessage(
+			alice.store,
+			[bob.player.agentPubKey],
+			'unknown_zome',
+			'message',
+			{},
+		);
+
+		await waitUntil(
+			async () => (await queryDeadLetters(bob.store)).length === 1,
+			120_000,
+		);
+		const [deadLetter] = await queryDeadLetters(bob.store);
+		assert.equal(
+			encodeHashToBase64(deadLetter.dead_letter.provenance),
+			encodeHashToBase64(alice.player.agentPubKey),
+		);
+		assert.equal(deadLetter.dead_letter.zome_name, 'unknown_zome');
+
+		// Still undeliverable: it stays in the queue
+		const report: any = await bob.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'encrypted_links',
+			fn_name: 'retry_dead_letters',
+			payload: [deadLetter.action_hash],
+		});
+		assert.equal(report.delivered.length, 0);
+		assert.equal(report.failed.length, 1);
+		assert.equal((await queryDeadLetters(bob.store)).length, 1);
+
+		await bob.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'encrypted_links',
+			fn_name: 'purge_dead_letters',
+			payload: [deadLetter.action_hash],
+		});
+		assert.equal((await queryDeadLetters(bob.store)).length, 0);
+	});
+});
+
+test('async messages are only handed off to the allowed target zomes', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		const queryAllowedTargetZomes = async (): Promise<Array<string>> =>
+			bob.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'encrypted_links',
+				fn_name: 'query_allowed_target_zomes',
+				payload: undefined,
+			});
+		const callEncryptedLinks = (fn_name: string, payload: string) =>
+			bob.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'encrypted_links',
+				fn_name,
+				payload,
+			});
+
+		// Configured at build time for the test DNA
+		assert.deepEqual(await queryAllowedTargetZomes(), ['example']);
+
+		await callEncryptedLinks('register_target_zome', 'other_zome');
+		assert.deepEqual((await queryAllowedTargetZomes()).sort(), [
+			'example',
+			'other_zome',
+		]);
+
+		await callEncryptedLinks('unregister_target_zome', 'other_zome');
+		assert.deepEqual(await queryAllowedTargetZomes(), ['example']);
+
+		await expect(
+			callEncryptedLinks('register_target_zome', 'encrypted_links'),
+		).rejects.toThrow();
+
+		await sendAsyncMessage(
+			alice.store,
+			[bob.player.agentPubKey],
+			'other_zome',
+			'message',
+			{},
+		);
+
+		await waitUntil(
+			async () => (await queryDeadLetters(bob.store)).length === 1,
+			120_000,
+		);
+		const [deadLetter] = await queryDeadLetters(bob.store);
+		assert.ok(
+			deadLetter.dead_letter.error.includes(
+				'is not an allowed target for async messages',
+			),
+		);
+	});
+});
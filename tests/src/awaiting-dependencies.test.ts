@@ -0,0 +1,144 @@
+import {
+	decodeHashFromBase64,
+	encodeHashToBase64,
+	fakeEntryHash,
+} from '@holochain/client';
+import { runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import {
+	buildPrivateEventEntry,
+	receiveMessage,
+	setup,
+	waitUntil,
+} from './setup.js';
+
+test('events with missing previous events are listed as stuck until they expire', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		const missingEvent = await fakeEntryHash();
+		const privateEventEntry = await buildPrivateEventEntry(alice.store, {
+			event_type: 'SharedEntry',
+			event: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'hello',
+			},
+			previous_events: [missingEvent],
+		});
+
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [privateEventEntry],
+		});
+		assert.equal(report.private_events.deferred.length, 1);
+
+		const stuck = await bob.store.client.queryStuckAwaitingDependencies();
+		assert.equal(stuck.length, 1);
+		assert.ok('Event' in stuck[0].record.awaiting_dependencies);
+		const unresolved = stuck[0].unresolved_dependencies;
+		assert.ok('Hashes' in unresolved);
+		assert.deepEqual(
+			(unresolved as { Hashes: Array<Uint8Array> }).Hashes.map(
+				encodeHashToBase64,
+			),
+			[encodeHashToBase64(missingEvent)],
+		);
+		assert.ok(stuck[0].expires_at > stuck[0].record.timestamp);
+
+		const privateEvents = await bob.store.client.queryPrivateEventEntries();
+		assert.equal(Object.keys(privateEvents).length, 0);
+	});
+});
+
+test('missing previous events are requested from the sender', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await bob.player.conductor.shutDown();
+
+		for (const content of ['first', 'second']) {
+			await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'create_private_shared_entry',
+				payload: {
+					type: 'SharedEntry',
+					recipient: bob.player.agentPubKey,
+					content,
+				},
+			});
+		}
+		const ordered = await alice.store.client.queryOrderedPrivateEventEntries();
+		const [[firstHash], [secondHash, second]] = ordered;
+
+		await bob.startUp();
+
+		// Bob only gets the second event, which depends on the first one
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [second],
+		});
+		assert.deepEqual(report.private_events.deferred, [secondHash]);
+
+		await waitUntil(async () => {
+			const privateEvents = await bob.store.client.queryPrivateEventEntries();
+			return !!privateEvents[firstHash];
+		}, 60_000);
+
+		// The second event is committed from the awaiting dependencies queue
+		await bob.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'scheduled_tasks',
+			payload: undefined,
+		});
+		const privateEvents = await bob.store.client.queryPrivateEventEntries();
+		assert.ok(privateEvents[secondHash]);
+	});
+});
+
+test('events stop waiting for the previous events that their author does not send us', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob, carol] = await setup(scenario, 3);
+
+		// NewFriend events don't have any recipients, so bob is not entitled to receive it
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'add_friend',
+			payload: carol.player.agentPubKey,
+		});
+		const alicePrivateEvents =
+			await alice.store.client.queryPrivateEventEntries();
+		const [newFriendHash] = Object.entries(alicePrivateEvents).find(
+			([_, entry]) => entry.payload.content.event_type === 'NewFriend',
+		)!;
+
+		const privateEventEntry = await buildPrivateEventEntry(alice.store, {
+			event_type: 'SharedEntry',
+			event: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'hello',
+			},
+			previous_events: [decodeHashFromBase64(newFriendHash)],
+		});
+
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [privateEventEntry],
+		});
+		assert.equal(report.private_events.deferred.length, 1);
+		const [eventHash] = report.private_events.deferred;
+
+		// Alice answers that bob is not entitled to the NewFriend event
+		await waitUntil(async () => {
+			const privateEvents = await bob.store.client.queryPrivateEventEntries();
+			return !!privateEvents[eventHash];
+		}, 60_000);
+
+		const privateEvents = await bob.store.client.queryPrivateEventEntries();
+		assert.notOk(privateEvents[newFriendHash]);
+		const stuck = await bob.store.client.queryStuckAwaitingDependencies();
+		assert.equal(stuck.length, 0);
+	});
+});
@@ -0,0 +1,142 @@
+import { EntryHashB64, encodeHashToBase64 } from '@holochain/client';
+import { runScenario } from '@holochain/tryorama';
+import { decode } from '@msgpack/msgpack';
+import { assert, test } from 'vitest';
+
+import { PrivateEventEntry } from '../../ui/src/types.js';
+import { setup, waitUntil } from './setup.js';
+
+function findEventWithContent(
+	privateEventEntries: Record<EntryHashB64, PrivateEventEntry>,
+	content: string,
+): [EntryHashB64, PrivateEventEntry] {
+	const found = Object.entries(privateEventEntries).find(
+		([_, entry]) =>
+			(decode(entry.payload.content.event) as any).content === content,
+	);
+	if (!found) throw new Error(`No event with content ${content}.`);
+	return found;
+}
+
+test('new events point to the previous events as their causal dependencies', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		for (const content of ['first', 'second']) {
+			await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'create_private_shared_entry',
+				payload: {
+					type: 'SharedEntry',
+					recipient: bob.player.agentPubKey,
+					content,
+				},
+			});
+		}
+
+		const privateEventEntries =
+			await alice.store.client.queryPrivateEventEntries();
+		const [firstHash, first] = findEventWithContent(
+			privateEventEntries,
+			'first',
+		);
+		const [_, second] = findEventWithContent(privateEventEntries, 'second');
+
+		assert.deepEqual(first.payload.content.previous_events || [], []);
+		assert.deepEqual(
+			(second.payload.content.previous_events || []).map(encodeHashToBase64),
+			[firstHash],
+		);
+	});
+});
+
+test('all agents order the same events in the same total order', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		// Concurrent events from both agents
+		await Promise.all(
+			[
+				[alice, bob],
+				[bob, alice],
+			].map(async ([author, recipient]) => {
+				for (let i = 0; i < 2; i++) {
+					await author.store.client.client.callZome({
+						role_name: 'private_event_sourcing_test',
+						zome_name: 'example',
+						fn_name: 'create_private_shared_entry',
+						payload: {
+							type: 'SharedEntry',
+							recipient: recipient.player.agentPubKey,
+							content: `${i}`,
+						},
+					});
+				}
+			}),
+		);
+
+		await waitUntil(async () => {
+			const alicePrivateEvents =
+				await alice.store.client.queryPrivateEventEntries();
+			const bobPrivateEvents =
+				await bob.store.client.queryPrivateEventEntries();
+			return (
+				Object.keys(alicePrivateEvents).length === 4 &&
+				Object.keys(bobPrivateEvents).length === 4
+			);
+		}, 20_000);
+
+		const aliceOrder = (
+			await alice.store.client.queryOrderedPrivateEventEntries()
+		).map(([hash]) => hash);
+		const bobOrder = (
+			await bob.store.client.queryOrderedPrivateEventEntries()
+		).map(([hash]) => hash);
+
+		assert.equal(aliceOrder.length, 4);
+		assert.deepEqual(aliceOrder, bobOrder);
+	});
+});
+
+test('new events only point to the previous events that their recipients can receive', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob, carol] = await setup(scenario, 3);
+
+		// NewFriend events don't have any recipients
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'add_friend',
+			payload: carol.player.agentPubKey,
+		});
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'hello',
+			},
+		});
+
+		const alicePrivateEvents =
+			await alice.store.client.queryPrivateEventEntries();
+		const [sharedEntryHash, sharedEntry] = findEventWithContent(
+			alicePrivateEvents,
+			'hello',
+		);
+		assert.deepEqual(sharedEntry.payload.content.previous_events || [], []);
+
+		await waitUntil(async () => {
+			const bobPrivateEvents =
+				await bob.store.client.queryPrivateEventEntries();
+			return !!bobPrivateEvents[sharedEntryHash];
+		}, 20_000);
+
+		const stuck = await bob.store.client.queryStuckAwaitingDependencies();
+		assert.equal(stuck.length, 0);
+	});
+});
@@ -0,0 +1,60 @@
+import { runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import { HybridLogicalClock, PrivateEventEntry } from '../../ui/src/types.js';
+import { setup, waitUntil } from './setup.js';
+
+function isAfter(clock: HybridLogicalClock, other: HybridLogicalClock) {
+	return (
+		clock.physical > other.physical ||
+		(clock.physical === other.physical && clock.logical > other.logical)
+	);
+}
+
+test('the clocks of new events are after the clocks of all the events seen before', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		for (let i = 0; i < 2; i++) {
+			await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'create_private_shared_entry',
+				payload: {
+					type: 'SharedEntry',
+					recipient: bob.player.agentPubKey,
+					content: `${i}`,
+				},
+			});
+		}
+
+		await waitUntil(async () => {
+			const privateEvents = await bob.store.client.queryPrivateEventEntries();
+			return Object.keys(privateEvents).length === 2;
+		}, 20_000);
+
+		await bob.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: alice.player.agentPubKey,
+				content: 'reply',
+			},
+		});
+
+		const ordered = await bob.store.client.queryOrderedPrivateEventEntries();
+		const clocks = ordered.map(
+			([_, entry]: [string, PrivateEventEntry]) => entry.payload.hlc!,
+		);
+
+		assert.equal(clocks.length, 3);
+		assert.ok(isAfter(clocks[1], clocks[0]));
+		// Bob's event was created after observing alice's events
+		assert.ok(isAfter(clocks[2], clocks[1]));
+		for (const [_, entry] of ordered) {
+			assert.equal(entry.payload.timestamp, entry.payload.hlc!.physical);
+		}
+	});
+});
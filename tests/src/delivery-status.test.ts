@@ -0,0 +1,97 @@
+import { decodeHashFromBase64, encodeHashToBase64 } from '@holochain/client';
+import { pause, runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import { setup, waitUntil } from './setup.js';
+
+test('events are not resent before the delay of the resend policy', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await bob.player.conductor.shutDown();
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'hello',
+			},
+		});
+		const [eventHash] = Object.keys(
+			await alice.store.client.queryPrivateEventEntries(),
+		);
+		const bobKey = encodeHashToBase64(bob.player.agentPubKey);
+
+		let deliveryStatus = await alice.store.client.getDeliveryStatus(
+			decodeHashFromBase64(eventHash),
+		);
+		assert.equal(deliveryStatus[bobKey].type, 'Sent');
+		assert.equal((deliveryStatus[bobKey] as any).attempts, 1);
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'scheduled_tasks',
+			payload: undefined,
+		});
+		await pause(1000);
+
+		deliveryStatus = await alice.store.client.getDeliveryStatus(
+			decodeHashFromBase64(eventHash),
+		);
+		assert.equal(deliveryStatus[bobKey].type, 'Sent');
+		assert.equal((deliveryStatus[bobKey] as any).attempts, 1);
+	});
+});
+
+test('the delivery status is tracked for each recipient', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob, carol] = await setup(scenario, 3);
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'add_friend',
+			payload: carol.player.agentPubKey,
+		});
+
+		await carol.player.conductor.shutDown();
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'hello',
+			},
+		});
+		const [[eventHash]] = Object.entries(
+			await alice.store.client.queryPrivateEventEntries(),
+		).filter(
+			([_, entry]) => entry.payload.content.event_type === 'SharedEntry',
+		);
+		const bobKey = encodeHashToBase64(bob.player.agentPubKey);
+		const carolKey = encodeHashToBase64(carol.player.agentPubKey);
+
+		await waitUntil(async () => {
+			const deliveryStatus = await alice.store.client.getDeliveryStatus(
+				decodeHashFromBase64(eventHash),
+			);
+			return deliveryStatus[bobKey].type === 'Acknowledged';
+		}, 20_000);
+
+		const deliveryStatus = await alice.store.client.getDeliveryStatus(
+			decodeHashFromBase64(eventHash),
+		);
+		assert.deepEqual(
+			Object.keys(deliveryStatus).sort(),
+			[bobKey, carolKey].sort(),
+		);
+		assert.equal(deliveryStatus[carolKey].type, 'Sent');
+	});
+});
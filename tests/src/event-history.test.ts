@@ -0,0 +1,167 @@
+import { AppWebsocket, CellId } from '@holochain/client';
+import { Player, enableAndGetAgentApp, runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import { setup, testHappUrl, waitUntil } from './setup.js';
+
+/**
+ * Installs a new cell for the same agent, returning a websocket for it and the id of the old cell
+ */
+async function installNewCell(
+	player: Player,
+	oldAppWs: AppWebsocket,
+): Promise<{ appWs: AppWebsocket; oldCellId: CellId }> {
+	const appInfo = await player.conductor.installApp({
+		appBundleSource: {
+			type: 'path',
+			value: testHappUrl,
+		},
+		options: {
+			agentPubKey: player.cells[0].cell_id[1],
+			networkSeed: 'new cell',
+		},
+	});
+	const port = await player.conductor.attachAppInterface();
+	const issued = await player.conductor
+		.adminWs()
+		.issueAppAuthenticationToken({
+			installed_app_id: appInfo.installed_app_id,
+		});
+	const appWs = await player.conductor.connectAppWs(issued.token, port);
+	await enableAndGetAgentApp(player.conductor.adminWs(), appWs, appInfo);
+
+	const oldAppInfo = await oldAppWs.appInfo();
+	const cellInfo = oldAppInfo.cell_info['private_event_sourcing_test'][0];
+	if (cellInfo.type !== 'provisioned') throw new Error('Unexpected cell type.');
+
+	return { appWs, oldCellId: cellInfo.value.cell_id };
+}
+
+test('imported event histories are validated and reported', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'hello',
+			},
+		});
+
+		await waitUntil(async () => {
+			const acknowledgements =
+				await alice.store.client.queryAcknowledgementEntries();
+			return acknowledgements.length > 0;
+		}, 20_000);
+
+		const { appWs, oldCellId } = await installNewCell(
+			alice.player,
+			alice.store.client.client as AppWebsocket,
+		);
+
+		const report: any = await appWs.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'migrate_from_old_cell',
+			payload: oldCellId,
+		});
+
+		assert.equal(report.events.accepted.length, 1);
+		assert.ok(report.events_sent_to_recipients.accepted.length > 0);
+		assert.ok(report.acknowledgements.accepted.length > 0);
+		for (const imported of Object.values(report) as any[]) {
+			assert.equal(imported.deferred.length, 0);
+			assert.deepEqual(imported.rejected, {});
+		}
+	});
+});
+
+test('event histories are exported in pages and migrated in chunks', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		const EVENTS_NUMBER = 10;
+		for (let i = 0; i < EVENTS_NUMBER; i++) {
+			await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'create_private_shared_entry',
+				payload: {
+					type: 'SharedEntry',
+					recipient: bob.player.agentPubKey,
+					content: `${i}`,
+				},
+			});
+		}
+
+		// Small pages so that the history doesn't fit in a single one
+		const exportedEvents: Array<string> = [];
+		let pages = 0;
+		let cursor = undefined;
+		do {
+			const page: any = await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'export_event_history_page',
+				payload: {
+					cursor,
+					max_page_size_bytes: 1024,
+				},
+			});
+			exportedEvents.push(...Object.keys(page.history.events));
+			cursor = page.next_cursor;
+			pages += 1;
+
+			if (pages === 1) {
+				// Events created during the export don't change the pages that are still to be exported
+				await alice.store.client.client.callZome({
+					role_name: 'private_event_sourcing_test',
+					zome_name: 'example',
+					fn_name: 'create_private_shared_entry',
+					payload: {
+						type: 'SharedEntry',
+						recipient: bob.player.agentPubKey,
+						content: `${EVENTS_NUMBER}`,
+					},
+				});
+			}
+		} while (cursor);
+
+		const privateEvents = await alice.store.client.queryPrivateEventEntries();
+		assert.ok(pages > 1);
+		// Every event is exported exactly once
+		assert.deepEqual(exportedEvents.sort(), Object.keys(privateEvents).sort());
+
+		const { appWs, oldCellId } = await installNewCell(
+			alice.player,
+			alice.store.client.client as AppWebsocket,
+		);
+
+		let finished = false;
+		const importedEvents: Array<string> = [];
+		while (!finished) {
+			const output: any = await appWs.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'migrate_next_chunk_from_old_cell',
+				payload: oldCellId,
+			});
+			importedEvents.push(...output.report.events.accepted);
+			finished = output.finished;
+		}
+
+		assert.deepEqual(importedEvents.sort(), Object.keys(privateEvents).sort());
+
+		const newPrivateEvents: Record<string, unknown> = await appWs.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'query_private_event_entries',
+			payload: undefined,
+		});
+		assert.equal(Object.keys(newPrivateEvents).length, EVENTS_NUMBER + 1);
+	});
+});
@@ -0,0 +1,161 @@
+import { encodeHashToBase64, fakeAgentPubKey } from '@holochain/client';
+import { runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import {
+	receiveMessage,
+	setup,
+	waitUntil,
+	withInvalidSignature,
+} from './setup.js';
+
+test('events with an invalid signature are not committed', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: await fakeAgentPubKey(),
+				content: 'hello',
+			},
+		});
+		const [privateEventEntry] = Object.values(
+			await alice.store.client.queryPrivateEventEntries(),
+		);
+
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [withInvalidSignature(privateEventEntry)],
+		});
+
+		assert.equal(report.private_events.accepted.length, 0);
+		const reasons = Object.values(report.private_events.rejected);
+		assert.equal(reasons.length, 1);
+		assert.ok(reasons[0].includes('invalid signature'));
+
+		const privateEvents = await bob.store.client.queryPrivateEventEntries();
+		assert.equal(Object.keys(privateEvents).length, 0);
+	});
+});
+
+test('invalid events are rejected with a signed rejection sent back to their sender', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: await fakeAgentPubKey(),
+				content: 'hello',
+			},
+		});
+		const [privateEventEntry] = Object.values(
+			await alice.store.client.queryPrivateEventEntries(),
+		);
+
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [withInvalidSignature(privateEventEntry)],
+		});
+		const [[rejectedHash, reason]] = Object.entries(
+			report.private_events.rejected,
+		);
+
+		const rejections = await bob.store.client.queryRejectionEntries();
+		assert.equal(rejections.length, 1);
+		assert.equal(
+			encodeHashToBase64(rejections[0].author),
+			encodeHashToBase64(bob.player.agentPubKey),
+		);
+		assert.equal(
+			encodeHashToBase64(rejections[0].payload.content.event_hash),
+			rejectedHash,
+		);
+		assert.equal(rejections[0].payload.content.reason, reason);
+
+		// Alice never had the rejected event, so she keeps the rejection awaiting it
+		await waitUntil(async () => {
+			const stuck = await alice.store.client.queryStuckAwaitingDependencies();
+			return stuck.some(s => 'Rejection' in s.record.awaiting_dependencies);
+		}, 20_000);
+	});
+});
+
+test('an invalid event does not prevent receiving the rest of the message', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		for (const content of ['valid', 'tampered']) {
+			await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'create_private_shared_entry',
+				payload: {
+					type: 'SharedEntry',
+					recipient: await fakeAgentPubKey(),
+					content,
+				},
+			});
+		}
+		const ordered = await alice.store.client.queryOrderedPrivateEventEntries();
+		const [[validHash, valid], [_, tampered]] = ordered;
+
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [valid, withInvalidSignature(tampered)],
+		});
+
+		assert.deepEqual(report.private_events.accepted, [validHash]);
+		assert.equal(Object.keys(report.private_events.rejected).length, 1);
+
+		const privateEvents = await bob.store.client.queryPrivateEventEntries();
+		assert.deepEqual(Object.keys(privateEvents), [validHash]);
+	});
+});
+
+test('invalid events are quarantined until they are purged', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: await fakeAgentPubKey(),
+				content: 'hello',
+			},
+		});
+		const [privateEventEntry] = Object.values(
+			await alice.store.client.queryPrivateEventEntries(),
+		);
+
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [withInvalidSignature(privateEventEntry)],
+		});
+		const [rejectedHash] = Object.keys(report.private_events.rejected);
+
+		// Receiving it again doesn't quarantine it twice
+		await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [withInvalidSignature(privateEventEntry)],
+		});
+
+		let quarantined = await bob.store.client.queryQuarantinedEvents();
+		assert.equal(quarantined.length, 1);
+		assert.equal(quarantined[0].event_hash, rejectedHash);
+		assert.equal(
+			encodeHashToBase64(quarantined[0].quarantined_event.provenance),
+			encodeHashToBase64(alice.player.agentPubKey),
+		);
+
+		await bob.store.client.purgeQuarantinedEvents([quarantined[0].action_hash]);
+
+		quarantined = await bob.store.client.queryQuarantinedEvents();
+		assert.equal(quarantined.length, 0);
+	});
+});
@@ -63,12 +63,14 @@ test('migration of entries works', async () => {
 
 		const previousCellId: CellId = cellInfo.value.cell_id;
 
-		await appWs.callZome({
+		const report: any = await appWs.callZome({
 			role_name: 'private_event_sourcing_test',
 			zome_name: 'example',
 			payload: previousCellId,
 			fn_name: 'migrate_from_old_cell',
 		});
+		assert.equal(report.events.accepted.length, 1);
+		assert.equal(Object.keys(report.events.rejected).length, 0);
 
 		await pause(200);
 		const aliceStore2 = new PrivateEventSourcingStore(
@@ -0,0 +1,83 @@
+import { encodeHashToBase64, fakeAgentPubKey } from '@holochain/client';
+import { runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import { setup } from './setup.js';
+
+test('projections fold all the private events', async () => {
+	await runScenario(async scenario => {
+		const [alice] = await setup(scenario, 1);
+
+		const friends = [await fakeAgentPubKey(), await fakeAgentPubKey()];
+
+		for (const friend of friends) {
+			await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'add_friend',
+				payload: friend,
+			});
+		}
+
+		const projectedFriends: Array<Uint8Array> =
+			await alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'query_friends',
+				payload: undefined,
+			});
+
+		assert.deepEqual(
+			projectedFriends.map(encodeHashToBase64).sort(),
+			friends.map(encodeHashToBase64).sort(),
+		);
+	});
+});
+
+test('projections include the events committed after their latest snapshot', async () => {
+	await runScenario(async scenario => {
+		const [alice] = await setup(scenario, 1);
+
+		const addFriends = async (count: number) => {
+			for (let i = 0; i < count; i++) {
+				await alice.store.client.client.callZome({
+					role_name: 'private_event_sourcing_test',
+					zome_name: 'example',
+					fn_name: 'add_friend',
+					payload: await fakeAgentPubKey(),
+				});
+			}
+		};
+		const queryFriends = async (): Promise<Array<Uint8Array>> =>
+			alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'query_friends',
+				payload: undefined,
+			});
+
+		const runScheduledTasks = () =>
+			alice.store.client.client.callZome({
+				role_name: 'private_event_sourcing_test',
+				zome_name: 'example',
+				fn_name: 'scheduled_tasks',
+				payload: undefined,
+			});
+
+		// Enough events for a snapshot to be persisted by the scheduled tasks
+		await addFriends(101);
+		assert.equal((await queryFriends()).length, 101);
+		await runScheduledTasks();
+		assert.equal((await queryFriends()).length, 101);
+
+		await addFriends(2);
+		assert.equal((await queryFriends()).length, 103);
+
+		// Enough new events for the snapshot to be superseded by a new one
+		await addFriends(100);
+		await runScheduledTasks();
+		assert.equal((await queryFriends()).length, 203);
+		await addFriends(1);
+		assert.equal((await queryFriends()).length, 204);
+	});
+});
@@ -0,0 +1,53 @@
+import { decodeHashFromBase64, encodeHashToBase64 } from '@holochain/client';
+import { runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import { setup, waitUntil } from './setup.js';
+
+test('events are only read once the recipient marks them as read', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		await alice.store.client.client.callZome({
+			role_name: 'private_event_sourcing_test',
+			zome_name: 'example',
+			fn_name: 'create_private_shared_entry',
+			payload: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'hello',
+			},
+		});
+		const [eventHash] = Object.keys(
+			await alice.store.client.queryPrivateEventEntries(),
+		);
+		const bobKey = encodeHashToBase64(bob.player.agentPubKey);
+
+		await waitUntil(async () => {
+			const deliveryStatus = await alice.store.client.getDeliveryStatus(
+				decodeHashFromBase64(eventHash),
+			);
+			return deliveryStatus[bobKey].type === 'Acknowledged';
+		}, 20_000);
+
+		// Delivered but not read yet
+		let readReceipts = await alice.store.client.queryReadReceiptsForEvent(
+			decodeHashFromBase64(eventHash),
+		);
+		assert.deepEqual(readReceipts, {});
+
+		await bob.store.client.markEventsAsRead([decodeHashFromBase64(eventHash)]);
+
+		await waitUntil(async () => {
+			const readReceipts = await alice.store.client.queryReadReceiptsForEvent(
+				decodeHashFromBase64(eventHash),
+			);
+			return !!readReceipts[bobKey];
+		}, 20_000);
+
+		const eventsReadByBob = await alice.store.client.queryEventsReadByAgent(
+			bob.player.agentPubKey,
+		);
+		assert.deepEqual(Object.keys(eventsReadByBob), [eventHash]);
+	});
+});
@@ -0,0 +1,34 @@
+import { runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import { buildPrivateEventEntry, receiveMessage, setup } from './setup.js';
+
+test('events with a newer schema version are kept until they can be decoded', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		const privateEventEntry = await buildPrivateEventEntry(alice.store, {
+			event_type: 'SharedEntry',
+			event: {
+				type: 'SharedEntry',
+				recipient: bob.player.agentPubKey,
+				content: 'from a newer version',
+			},
+			schema_version: 1,
+		});
+
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [privateEventEntry],
+		});
+		assert.equal(report.private_events.accepted.length, 0);
+		assert.equal(report.private_events.deferred.length, 1);
+
+		const privateEvents = await bob.store.client.queryPrivateEventEntries();
+		assert.equal(Object.keys(privateEvents).length, 0);
+
+		const undecodable = await bob.store.client.queryUndecodablePrivateEvents();
+		assert.equal(undecodable.length, 1);
+		assert.equal(undecodable[0].schema_version, 1);
+		assert.ok(undecodable[0].awaiting_dependencies);
+	});
+});
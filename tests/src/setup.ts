@@ -2,13 +2,27 @@ import {
 	LinkedDevicesClient,
 	LinkedDevicesStore,
 } from '@darksoil-studio/linked-devices-zome';
-import { AppWebsocket } from '@holochain/client';
+import {
+	ActionHash,
+	AgentPubKey,
+	AppWebsocket,
+	EntryHash,
+} from '@holochain/client';
 import { Scenario, dhtSync, pause } from '@holochain/tryorama';
+import { encode } from '@msgpack/msgpack';
 import { dirname } from 'path';
 import { fileURLToPath } from 'url';
 
 import { PrivateEventSourcingClient } from '../../ui/src/private-event-sourcing-client.js';
 import { PrivateEventSourcingStore } from '../../ui/src/private-event-sourcing-store.js';
+import {
+	Acknowledgement,
+	EventSentToRecipients,
+	PrivateEventEntry,
+	ReadReceipt,
+	ReceiveMessageReport,
+	Rejection,
+} from '../../ui/src/types.js';
 
 export const testHappUrl =
 	dirname(fileURLToPath(import.meta.url)) +
@@ -139,3 +153,131 @@ export async function linkDevices(
 		store1Passcode,
 	);
 }
+
+export interface Message {
+	private_events: Array<PrivateEventEntry>;
+	events_sent_to_recipients: Array<EventSentToRecipients>;
+	acknowledgements: Array<Acknowledgement>;
+	read_receipts: Array<ReadReceipt>;
+	rejections: Array<Rejection>;
+}
+
+/**
+ * Hands off the given message to the player's zome as if it had been sent by the provenance
+ */
+export async function receiveMessage(
+	store: PrivateEventSourcingStore<any>,
+	provenance: AgentPubKey,
+	message: Partial<Message>,
+): Promise<ReceiveMessageReport> {
+	return store.client.client.callZome({
+		role_name: 'private_event_sourcing_test',
+		zome_name: 'example',
+		fn_name: 'receive_message',
+		payload: {
+			provenance,
+			message: {
+				private_events: [],
+				events_sent_to_recipients: [],
+				acknowledgements: [],
+				read_receipts: [],
+				rejections: [],
+				...message,
+			},
+		},
+	});
+}
+
+/**
+ * Copy of the given private event entry with its signature corrupted
+ */
+export function withInvalidSignature(
+	privateEventEntry: PrivateEventEntry,
+): PrivateEventEntry {
+	const signature = new Uint8Array(privateEventEntry.signature);
+	signature[0] = signature[0] ^ 0xff;
+	return {
+		...privateEventEntry,
+		signature,
+	};
+}
+
+/**
+ * Signs a private event with the given content as the player, without committing it
+ */
+export async function buildPrivateEventEntry(
+	store: PrivateEventSourcingStore<any>,
+	content: {
+		event_type: string;
+		event: unknown;
+		previous_events?: Array<EntryHash>;
+		schema_version?: number;
+	},
+): Promise<PrivateEventEntry> {
+	return store.client.client.callZome({
+		role_name: 'private_event_sourcing_test',
+		zome_name: 'example',
+		fn_name: 'build_private_event_entry',
+		payload: {
+			event_type: content.event_type,
+			event: encode(content.event),
+			previous_events: content.previous_events || [],
+			schema_version: content.schema_version || 0,
+		},
+	});
+}
+
+/**
+ * Sends the given message to the recipients through the async messages of the encrypted_links zome
+ *
+ * Returns the recipients to which the message was not sent because they reached the rate limit
+ */
+export async function sendAsyncMessage(
+	store: PrivateEventSourcingStore<any>,
+	recipients: Array<AgentPubKey>,
+	zomeName: string,
+	messageId: string,
+	message: Partial<Message>,
+): Promise<Array<AgentPubKey>> {
+	const bytes = encode({
+		private_events: [],
+		events_sent_to_recipients: [],
+		acknowledgements: [],
+		read_receipts: [],
+		rejections: [],
+		...message,
+	});
+	return store.client.client.callZome({
+		role_name: 'private_event_sourcing_test',
+		zome_name: 'encrypted_links',
+		fn_name: 'send_async_message',
+		payload: {
+			recipients,
+			zome_name: zomeName,
+			message_id: messageId,
+			message: Array.from(bytes),
+		},
+	});
+}
+
+export interface DeadLetterRecord {
+	action_hash: ActionHash;
+	dead_letter: {
+		provenance: AgentPubKey;
+		zome_name: string | undefined;
+		message: Array<number>;
+		error: string;
+		timestamp: number;
+	};
+}
+
+export async function queryDeadLetters(
+	store: PrivateEventSourcingStore<any>,
+): Promise<Array<DeadLetterRecord>> {
+	return store.client.client.callZome({
+		role_name: 'private_event_sourcing_test',
+		zome_name: 'encrypted_links',
+		fn_name: 'query_dead_letters',
+		payload: undefined,
+	});
+}
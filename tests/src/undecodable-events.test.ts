@@ -0,0 +1,41 @@
+import { pause, runScenario } from '@holochain/tryorama';
+import { assert, test } from 'vitest';
+
+import { UndecodablePrivateEvent } from '../../ui/src/types.js';
+import { buildPrivateEventEntry, receiveMessage, setup } from './setup.js';
+
+test('undecodable events are reported instead of dropped', async () => {
+	await runScenario(async scenario => {
+		const [alice, bob] = await setup(scenario);
+
+		const signals: Array<UndecodablePrivateEvent> = [];
+		bob.store.client.onSignal(signal => {
+			if ('type' in signal && signal.type === 'UndecodablePrivateEvent') {
+				signals.push(signal.undecodable_private_event);
+			}
+		});
+
+		const privateEventEntry = await buildPrivateEventEntry(alice.store, {
+			event_type: 'UnknownEvent',
+			event: {
+				type: 'UnknownEvent',
+			},
+		});
+
+		const report = await receiveMessage(bob.store, alice.player.agentPubKey, {
+			private_events: [privateEventEntry],
+		});
+		assert.equal(report.private_events.deferred.length, 1);
+		const [eventHash] = report.private_events.deferred;
+
+		const undecodable = await bob.store.client.queryUndecodablePrivateEvents();
+		assert.equal(undecodable.length, 1);
+		assert.equal(undecodable[0].event_hash, eventHash);
+		assert.equal(undecodable[0].event_type, 'UnknownEvent');
+		assert.ok(undecodable[0].awaiting_dependencies);
+
+		await pause(500);
+		assert.equal(signals.length, 1);
+		assert.equal(signals[0].event_hash, eventHash);
+	});
+});
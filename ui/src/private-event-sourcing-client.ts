@@ -1,11 +1,25 @@
 import { ZomeClient } from '@darksoil-studio/holochain-utils';
-import { AgentPubKey, AppClient, EntryHashB64 } from '@holochain/client';
+import {
+	ActionHash,
+	AgentPubKey,
+	AgentPubKeyB64,
+	AppClient,
+	EntryHash,
+	EntryHashB64,
+	Timestamp,
+} from '@holochain/client';
 
 import {
 	Acknowledgement,
+	DeliveryStatus,
 	EventSentToRecipients,
 	PrivateEventEntry,
 	PrivateEventSourcingSignal,
+	QuarantinedEventRecord,
+	StuckAwaitingDependencies,
+	ReadReceipt,
+	Rejection,
+	UndecodablePrivateEvent,
 } from './types.js';
 
 export class PrivateEventSourcingClient<ADDITIONAL_SIGNALS> extends ZomeClient<
@@ -23,6 +37,16 @@ export class PrivateEventSourcingClient<ADDITIONAL_SIGNALS> extends ZomeClient<
 		return this.callZome('query_private_event_entries', undefined);
 	}
 
+	queryOrderedPrivateEventEntries(): Promise<
+		Array<[EntryHashB64, PrivateEventEntry]>
+	> {
+		return this.callZome('query_ordered_private_event_entries', undefined);
+	}
+
+	queryUndecodablePrivateEvents(): Promise<Array<UndecodablePrivateEvent>> {
+		return this.callZome('query_undecodable_private_events', undefined);
+	}
+
 	queryEventsSentToRecipientsEntries(): Promise<Array<EventSentToRecipients>> {
 		return this.callZome('query_events_sent_to_recipients_entries', undefined);
 	}
@@ -31,6 +55,48 @@ export class PrivateEventSourcingClient<ADDITIONAL_SIGNALS> extends ZomeClient<
 		return this.callZome('query_acknowledgement_entries', undefined);
 	}
 
+	getDeliveryStatus(
+		eventHash: EntryHash,
+	): Promise<Record<AgentPubKeyB64, DeliveryStatus>> {
+		return this.callZome('get_delivery_status', eventHash);
+	}
+
+	queryReadReceiptEntries(): Promise<Array<ReadReceipt>> {
+		return this.callZome('query_read_receipt_entries', undefined);
+	}
+
+	queryReadReceiptsForEvent(
+		eventHash: EntryHash,
+	): Promise<Record<AgentPubKeyB64, Timestamp>> {
+		return this.callZome('query_read_receipts_for_event', eventHash);
+	}
+
+	queryEventsReadByAgent(
+		agent: AgentPubKey,
+	): Promise<Record<EntryHashB64, Timestamp>> {
+		return this.callZome('query_events_read_by_agent', agent);
+	}
+
+	markEventsAsRead(eventsHashes: Array<EntryHash>) {
+		return this.callZome('mark_events_as_read', eventsHashes);
+	}
+
+	queryRejectionEntries(): Promise<Array<Rejection>> {
+		return this.callZome('query_rejection_entries', undefined);
+	}
+
+	queryQuarantinedEvents(): Promise<Array<QuarantinedEventRecord>> {
+		return this.callZome('query_quarantined_events', undefined);
+	}
+
+	purgeQuarantinedEvents(actionHashes: Array<ActionHash>): Promise<void> {
+		return this.callZome('purge_quarantined_events', actionHashes);
+	}
+
+	queryStuckAwaitingDependencies(): Promise<Array<StuckAwaitingDependencies>> {
+		return this.callZome('query_stuck_awaiting_dependencies', undefined);
+	}
+
 	synchronizeWithLinkedDevice(linkedDevice: AgentPubKey) {
 		return this.callZome('synchronize_with_linked_device', linkedDevice);
 	}
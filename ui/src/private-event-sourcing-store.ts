@@ -77,7 +77,10 @@ export class PrivateEventSourcingStore<E> {
 					content: {
 						event_type: privateEventEntry.payload.content.event_type,
 						event: decode(privateEventEntry.payload.content.event) as E,
+						previous_events: privateEventEntry.payload.content.previous_events,
+						schema_version: privateEventEntry.payload.content.schema_version,
 					},
+					hlc: privateEventEntry.payload.hlc,
 				},
 			};
 		}
@@ -164,19 +167,20 @@ export class PrivateEventSourcingStore<E> {
 		);
 
 		for (const entry of sorted) {
-			if (
-				!acknowledgements[
-					encodeHashToBase64(entry.payload.content.private_event_hash)
-				]
-			) {
-				acknowledgements[
-					encodeHashToBase64(entry.payload.content.private_event_hash)
-				] = {};
-			}
+			const privateEventHashes = [
+				entry.payload.content.private_event_hash,
+				...(entry.payload.content.other_private_event_hashes || []),
+			];
+			for (const privateEventHash of privateEventHashes) {
+				const privateEventHashB64 = encodeHashToBase64(privateEventHash);
+				if (!acknowledgements[privateEventHashB64]) {
+					acknowledgements[privateEventHashB64] = {};
+				}
 
-			acknowledgements[
-				encodeHashToBase64(entry.payload.content.private_event_hash)
-			][encodeHashToBase64(entry.author)] = entry.payload.timestamp / 1000;
+				acknowledgements[privateEventHashB64][
+					encodeHashToBase64(entry.author)
+				] = entry.payload.timestamp / 1000;
+			}
 		}
 
 		return acknowledgements;
@@ -1,7 +1,9 @@
 import { ActionCommittedSignal } from '@darksoil-studio/holochain-utils';
 import {
+	ActionHash,
 	AgentPubKey,
 	EntryHash,
+	EntryHashB64,
 	Signature,
 	Timestamp,
 } from '@holochain/client';
@@ -12,23 +14,65 @@ export type PrivateEventSourcingSignal =
 			type: 'NewPrivateEvent';
 			event_hash: EntryHash;
 			private_event_entry: PrivateEventEntry;
+	  }
+	| {
+			type: 'UndecodablePrivateEvent';
+			undecodable_private_event: UndecodablePrivateEvent;
+	  }
+	| {
+			type: 'MessageReceived';
+			provenance: AgentPubKey;
+			report: ReceiveMessageReport;
 	  };
 
+export interface UndecodablePrivateEvent {
+	event_hash: EntryHashB64;
+	event_type: string;
+	schema_version: number;
+	error: string;
+	awaiting_dependencies: boolean;
+}
+
+export interface ImportedEntries {
+	accepted: Array<EntryHashB64>;
+	deferred: Array<EntryHashB64>;
+	rejected: Record<EntryHashB64, string>;
+}
+
+export interface ReceiveMessageReport {
+	private_events: ImportedEntries;
+	events_sent_to_recipients: ImportedEntries;
+	acknowledgements: ImportedEntries;
+	read_receipts: ImportedEntries;
+	rejections: ImportedEntries;
+}
+
 export type EntryTypes =
 	| ({ type: 'PrivateEvent' } & PrivateEventEntry)
 	| ({ type: 'EventSentToRecipients' } & EventSentToRecipients)
-	| ({ type: 'Acknowledgement' } & Acknowledgement);
+	| ({ type: 'Acknowledgement' } & Acknowledgement)
+	| ({ type: 'ReadReceipt' } & ReadReceipt)
+	| ({ type: 'Rejection' } & Rejection)
+	| ({ type: 'QuarantinedEvent' } & QuarantinedEvent);
 
 export type LinkTypes = string;
 
 export interface SignedEventContent<T> {
 	event_type: string;
 	event: T;
+	previous_events?: Array<EntryHash>;
+	schema_version?: number;
+}
+
+export interface HybridLogicalClock {
+	physical: Timestamp;
+	logical: number;
 }
 
 export interface SignedContent<T> {
 	timestamp: Timestamp;
 	content: T;
+	hlc?: HybridLogicalClock;
 }
 
 export interface SignedEntry<T> {
@@ -47,4 +91,68 @@ export type EventSentToRecipients = SignedEntry<{
 
 export type Acknowledgement = SignedEntry<{
 	private_event_hash: EntryHash;
+	other_private_event_hashes?: Array<EntryHash>;
+}>;
+
+export type ReadReceipt = SignedEntry<{
+	private_event_hash: EntryHash;
+}>;
+
+export type Rejection = SignedEntry<{
+	event_hash: EntryHash;
+	reason: string;
 }>;
+
+export interface QuarantinedEvent {
+	event: PrivateEventEntry;
+	provenance: AgentPubKey;
+	reason: string;
+	timestamp: Timestamp;
+	overflow: number;
+}
+
+export interface QuarantinedEventRecord {
+	action_hash: ActionHash;
+	event_hash: EntryHashB64;
+	quarantined_event: QuarantinedEvent;
+}
+
+export type AwaitingDependencies =
+	| {
+			Event: {
+				event: PrivateEventEntry;
+				unresolved_dependencies: UnresolvedDependencies;
+				provenance: AgentPubKey | undefined;
+			};
+	  }
+	| { Acknowledgement: { acknowledgement: Acknowledgement } }
+	| {
+			EventsSentToRecipients: {
+				event_sent_to_recipients: EventSentToRecipients;
+			};
+	  }
+	| { ReadReceipt: { read_receipt: ReadReceipt } }
+	| { Rejection: { rejection: Rejection } };
+
+export type UnresolvedDependencies =
+	| { Hashes: Array<Uint8Array> }
+	| { AgentActivity: [AgentPubKey, unknown] };
+
+export interface AwaitingDependenciesRecord {
+	action_hash: ActionHash | undefined;
+	timestamp: Timestamp;
+	awaiting_dependencies: AwaitingDependencies;
+}
+
+export interface StuckAwaitingDependencies {
+	record: AwaitingDependenciesRecord;
+	unresolved_dependencies: UnresolvedDependencies;
+	expires_at: Timestamp;
+}
+
+export type DeliveryStatus =
+	| { type: 'Pending' }
+	| { type: 'Sent'; last_sent: Timestamp; attempts: number }
+	| { type: 'Acknowledged'; timestamp: Timestamp }
+	| { type: 'Rejected'; timestamp: Timestamp; reason: string }
+	| { type: 'Failed'; last_sent: Timestamp; attempts: number };
@@ -10,11 +10,25 @@
             inputs'.linked-devices-zome.packages.linked_devices_integrity;
           linked_devices = inputs'.linked-devices-zome.packages.linked_devices;
 
-          example = self'.packages.example;
+          # Only the test DNA enables the externs that the tests use to craft the events they receive
+          example = inputs.holochain-utils.outputs.builders.${system}.rustZome {
+            workspacePath = inputs.self.outPath;
+            crateCargoToml = ../zomes/coordinator/example/Cargo.toml;
+            zomeEnvironmentVars = {
+              ASYNC_MESSAGE_ZOME = "encrypted_links";
+              ENABLE_TEST_EXTERNS = "true";
+            };
+          };
           example_integrity = self'.packages.example_integrity;
 
           encrypted_links_integrity = self'.packages.encrypted_links_integrity;
-          encrypted_links = self'.packages.encrypted_links;
+          # Only the test DNA allows the example zome to receive async messages
+          encrypted_links =
+            inputs.holochain-utils.outputs.builders.${system}.rustZome {
+              workspacePath = inputs.self.outPath;
+              crateCargoToml = ../zomes/coordinator/encrypted_links/Cargo.toml;
+              zomeEnvironmentVars = { ASYNC_MESSAGE_TARGET_ZOMES = "example"; };
+            };
         };
       };
   };
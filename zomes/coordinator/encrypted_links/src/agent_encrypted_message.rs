@@ -7,7 +7,11 @@ use crate::{
     MessageWithZomeName,
 };
 
-pub fn create_encrypted_message(recipient: AgentPubKey, message: Vec<u8>) -> ExternResult<()> {
+/// Creates the encrypted message for the recipient, returning the hash of the link to it in the recipient's inbox
+pub fn create_encrypted_message(
+    recipient: AgentPubKey,
+    message: Vec<u8>,
+) -> ExternResult<ActionHash> {
     let chunks: Vec<XSalsa20Poly1305Data> =
         message.chunks(2_000).map(|c| c.to_vec().into()).collect();
     let encrypted_entries = chunks
@@ -32,23 +36,79 @@ pub fn create_encrypted_message(recipient: AgentPubKey, message: Vec<u8>) -> Ext
             entry_hash,
             LinkTypes::AgentEncryptedMessage,
             (),
-        )?;
+        )
     } else {
         create_link_relaxed(
             recipient.clone(),
             recipient.clone(),
             LinkTypes::AgentEncryptedMessage,
             bytes.bytes().clone(),
-        )?;
+        )
     }
+}
 
-    Ok(())
+/// Number of actions at the top of our source chain queried at first while counting our recent links,
+/// doubled until the oldest of them is older than a minute
+const RECENT_LINKS_WINDOW: u32 = 100;
+
+/// The number of AgentEncryptedMessage links that we created for each recipient in the last minute,
+/// which the integrity zome limits to `max_encrypted_messages_per_minute`
+pub fn query_encrypted_messages_in_last_minute() -> ExternResult<BTreeMap<AgentPubKey, u32>> {
+    let chain_head_seq = agent_info()?.chain_head.1;
+    let window_start = sys_time()?.as_micros() - 60 * 1_000_000;
+    let ScopedLinkType {
+        zome_index,
+        zome_type: link_type,
+    } = LinkTypes::AgentEncryptedMessage.try_into()?;
+
+    let mut window = RECENT_LINKS_WINDOW;
+    loop {
+        let first_action_seq = chain_head_seq.saturating_sub(window);
+        let records = query(ChainQueryFilter::new().sequence_range(
+            ChainQueryFilterRange::ActionSeqRange(first_action_seq, u32::MAX),
+        ))?;
+
+        let reached_window_start = records
+            .first()
+            .is_none_or(|r| r.action().timestamp().as_micros() < window_start);
+        if !reached_window_start && first_action_seq > 0 {
+            window = window.saturating_mul(2);
+            continue;
+        }
+
+        let mut messages_by_recipient: BTreeMap<AgentPubKey, u32> = BTreeMap::new();
+        for record in records {
+            let Action::CreateLink(create_link) = record.action() else {
+                continue;
+            };
+            if create_link.timestamp.as_micros() < window_start
+                || create_link.zome_index.ne(&zome_index)
+                || create_link.link_type.ne(&link_type)
+            {
+                continue;
+            }
+            if let Some(recipient) = create_link.base_address.clone().into_agent_pub_key() {
+                *messages_by_recipient.entry(recipient).or_default() += 1;
+            }
+        }
+        return Ok(messages_by_recipient);
+    }
 }
 
 pub fn get_agent_encrypted_messages(agent: AgentPubKey) -> ExternResult<Vec<Link>> {
     get_links(GetLinksInputBuilder::try_new(agent, LinkTypes::AgentEncryptedMessage)?.build())
 }
 
+/// Whether the message linked by the given AgentEncryptedMessage link is still in the recipient's inbox
+pub fn is_pending_message(
+    recipient: &AgentPubKey,
+    create_link_hash: &ActionHash,
+) -> ExternResult<bool> {
+    Ok(get_agent_encrypted_messages(recipient.clone())?
+        .into_iter()
+        .any(|link| link.create_link_hash.eq(create_link_hash)))
+}
+
 pub fn get_message(agent_encrypted_message_link: &Link) -> ExternResult<Option<EncryptedMessage>> {
     if agent_encrypted_message_link
         .base
@@ -79,55 +139,66 @@ pub fn get_message(agent_encrypted_message_link: &Link) -> ExternResult<Option<E
     }
 }
 
-pub fn get_my_pending_encrypted_messages() -> ExternResult<Vec<(AgentPubKey, Message, ZomeName)>> {
+pub struct PendingMessage {
+    pub provenance: AgentPubKey,
+    pub message: Message,
+    pub zome_name: ZomeName,
+    pub message_id: Option<String>,
+}
+
+/// Decrypts the message linked by the given AgentEncryptedMessage link
+///
+/// Returns None if the EncryptedMessage entry can't be fetched yet
+pub fn decrypt_pending_message(link: &Link) -> ExternResult<Option<Vec<u8>>> {
     let my_pub_key = agent_info()?.agent_initial_pubkey;
-    let links = get_agent_encrypted_messages(my_pub_key.clone())?;
 
-    let mut messages: Vec<(AgentPubKey, Message, ZomeName)> = vec![];
+    let Some(message) = get_message(link)? else {
+        return Ok(None);
+    };
+    debug!("[decrypt_pending_message] Found an EncryptedMessage.");
 
-    for link in links {
-        debug!("[commit_my_pending_encrypted_messages] Found an EncryptedMessage link.");
-        let Some(message) = get_message(&link)? else {
-            continue;
-        };
-        debug!("[commit_my_pending_encrypted_messages] Found an EncryptedMessage.");
-
-        let decrypted_data = message
-            .0
-            .into_iter()
-            .map(|chunk| {
-                ed_25519_x_salsa20_poly1305_decrypt(my_pub_key.clone(), link.author.clone(), chunk)
-            })
-            .collect::<ExternResult<Vec<XSalsa20Poly1305Data>>>()?;
-
-        let decrypted_bytes: Vec<u8> = decrypted_data
-            .into_iter()
-            .map(|chunk| chunk.as_ref().to_vec())
-            .flatten()
-            .collect();
-        let decrypted_serialized_bytes = SerializedBytes::from(UnsafeBytes::from(decrypted_bytes));
-
-        get(link.create_link_hash.clone(), GetOptions::default())?;
-        delete_link_relaxed(link.create_link_hash)?;
-
-        let result = MessageWithZomeName::try_from(decrypted_serialized_bytes);
-        let Ok(message_with_zome_name) = result else {
-            error!(
-                "Failed to deserialize message with zome name: {:?}.",
-                result
-            );
-            continue;
-        };
+    let decrypted_data = message
+        .0
+        .into_iter()
+        .map(|chunk| {
+            ed_25519_x_salsa20_poly1305_decrypt(my_pub_key.clone(), link.author.clone(), chunk)
+        })
+        .collect::<ExternResult<Vec<XSalsa20Poly1305Data>>>()?;
 
-        let result = Message::try_from(SerializedBytes::from(UnsafeBytes::from(
-            message_with_zome_name.message,
-        )));
-        let Ok(message) = result else {
-            error!("Failed to deserialize message: {:?}.", result);
-            continue;
-        };
-        messages.push((link.author, message, message_with_zome_name.zome_name));
-    }
+    let decrypted_bytes: Vec<u8> = decrypted_data
+        .into_iter()
+        .map(|chunk| chunk.as_ref().to_vec())
+        .flatten()
+        .collect();
+
+    Ok(Some(decrypted_bytes))
+}
+
+/// Deserializes the decrypted bytes of a message sent by the given provenance
+pub fn deserialize_pending_message(
+    provenance: AgentPubKey,
+    decrypted_bytes: Vec<u8>,
+) -> ExternResult<PendingMessage> {
+    let decrypted_serialized_bytes = SerializedBytes::from(UnsafeBytes::from(decrypted_bytes));
+
+    let message_with_zome_name = MessageWithZomeName::try_from(decrypted_serialized_bytes)
+        .map_err(|err| wasm_error!("Failed to deserialize message with zome name: {:?}.", err))?;
+
+    let message = Message::try_from(SerializedBytes::from(UnsafeBytes::from(
+        message_with_zome_name.message,
+    )))
+    .map_err(|err| wasm_error!("Failed to deserialize message: {:?}.", err))?;
+
+    Ok(PendingMessage {
+        provenance,
+        message,
+        zome_name: message_with_zome_name.zome_name,
+        message_id: message_with_zome_name.message_id,
+    })
+}
 
-    Ok(messages)
+/// Removes the message from our inbox, only to be called after it has been handed off or discarded
+pub fn delete_pending_message(create_link_hash: ActionHash) -> ExternResult<()> {
+    get(create_link_hash.clone(), GetOptions::default())?;
+    delete_link_relaxed(create_link_hash)
 }
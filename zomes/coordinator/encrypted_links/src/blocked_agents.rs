@@ -0,0 +1,64 @@
+use encrypted_links_integrity::{BlockedAgent, EntryTypes, UnitEntryTypes};
+use hdk::prelude::*;
+
+use crate::utils::create_relaxed;
+
+/// The actions that blocked each agent, excluding the ones that were deleted when unblocking them
+fn query_blocked_agents_actions() -> ExternResult<BTreeMap<AgentPubKey, Vec<ActionHash>>> {
+    let filter = ChainQueryFilter::new().action_type(ActionType::Delete);
+    let deleted_actions: BTreeSet<ActionHash> = query(filter)?
+        .into_iter()
+        .filter_map(|r| match r.action() {
+            Action::Delete(delete) => Some(delete.deletes_address.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::BlockedAgent.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query(filter)?;
+
+    let mut blocked_agents: BTreeMap<AgentPubKey, Vec<ActionHash>> = BTreeMap::new();
+    for record in records {
+        if deleted_actions.contains(record.action_address()) {
+            continue;
+        }
+        let Some(entry) = record.entry().as_option().clone() else {
+            return Err(wasm_error!("BlockedAgent record contained no entry."));
+        };
+        let blocked_agent = BlockedAgent::try_from(entry)?;
+        blocked_agents
+            .entry(blocked_agent.agent)
+            .or_default()
+            .push(record.action_address().clone());
+    }
+    Ok(blocked_agents)
+}
+
+/// Agents whose async messages are deleted from our inbox without being decrypted
+#[hdk_extern]
+pub fn query_blocked_agents() -> ExternResult<BTreeSet<AgentPubKey>> {
+    Ok(query_blocked_agents_actions()?.into_keys().collect())
+}
+
+#[hdk_extern]
+pub fn block_agent(agent: AgentPubKey) -> ExternResult<()> {
+    if query_blocked_agents_actions()?.contains_key(&agent) {
+        return Ok(());
+    }
+    create_relaxed(EntryTypes::BlockedAgent(BlockedAgent { agent }))?;
+    Ok(())
+}
+
+#[hdk_extern]
+pub fn unblock_agent(agent: AgentPubKey) -> ExternResult<()> {
+    let Some(action_hashes) = query_blocked_agents_actions()?.remove(&agent) else {
+        return Ok(());
+    };
+    for action_hash in action_hashes {
+        delete_entry(DeleteInput::new(action_hash, ChainTopOrdering::Relaxed))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use encrypted_links_integrity::{DeadLetter, EntryTypes, UnitEntryTypes};
+use hdk::prelude::*;
+
+use crate::{
+    agent_encrypted_message::deserialize_pending_message, hand_off_message,
+    message_ids::query_processed_message_ids, target_zomes::query_allowed_target_zomes,
+    utils::create_relaxed, MessageWithZomeName,
+};
+
+const DEFAULT_MAX_DEAD_LETTERS_PER_AGENT: usize = 20;
+
+/// Maximum number of dead letters kept for each sender, after which their undeliverable messages are dropped
+///
+/// Configurable at build time with the MAX_DEAD_LETTERS_PER_AGENT environment variable
+pub fn max_dead_letters_per_agent() -> usize {
+    std::option_env!("MAX_DEAD_LETTERS_PER_AGENT")
+        .and_then(|max| max.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_DEAD_LETTERS_PER_AGENT)
+}
+
+/// The number of dead letters of each sender, queried once when the first message is dead-lettered
+#[derive(Default)]
+pub struct DeadLetterQueue {
+    dead_letters_by_agent: Option<BTreeMap<AgentPubKey, usize>>,
+}
+
+impl DeadLetterQueue {
+    /// Stores the decrypted message in the dead-letter queue, so that it can be replayed later
+    ///
+    /// If the sender already has the maximum number of dead letters, the message is dropped instead
+    pub fn create_dead_letter(
+        &mut self,
+        provenance: AgentPubKey,
+        decrypted_bytes: Vec<u8>,
+        error: String,
+    ) -> ExternResult<()> {
+        if self.dead_letters_by_agent.is_none() {
+            let mut dead_letters_by_agent: BTreeMap<AgentPubKey, usize> = BTreeMap::new();
+            for dead_letter in query_dead_letters(())? {
+                *dead_letters_by_agent
+                    .entry(dead_letter.dead_letter.provenance)
+                    .or_default() += 1;
+            }
+            self.dead_letters_by_agent = Some(dead_letters_by_agent);
+        }
+        let dead_letters = self
+            .dead_letters_by_agent
+            .get_or_insert_with(BTreeMap::new)
+            .entry(provenance.clone())
+            .or_default();
+
+        if *dead_letters >= max_dead_letters_per_agent() {
+            warn!("Dropping message sent by agent {provenance}, which already has {dead_letters} dead letters: {error}");
+            return Ok(());
+        }
+
+        let zome_name = MessageWithZomeName::try_from(SerializedBytes::from(UnsafeBytes::from(
+            decrypted_bytes.clone(),
+        )))
+        .ok()
+        .map(|m| m.zome_name);
+
+        warn!("Moving message sent by agent {provenance} to the dead-letter queue: {error}");
+        create_relaxed(EntryTypes::DeadLetter(DeadLetter {
+            provenance,
+            zome_name,
+            message: decrypted_bytes,
+            error,
+            timestamp: sys_time()?,
+        }))?;
+        *dead_letters += 1;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeadLetterRecord {
+    /// The hash of the action that created the dead letter, used to retry or purge it
+    pub action_hash: ActionHash,
+    pub dead_letter: DeadLetter,
+}
+
+/// All the dead letters that haven't been delivered or purged yet
+#[hdk_extern]
+pub fn query_dead_letters() -> ExternResult<Vec<DeadLetterRecord>> {
+    let filter = ChainQueryFilter::new().action_type(ActionType::Delete);
+    let deleted_actions: BTreeSet<ActionHash> = query(filter)?
+        .into_iter()
+        .filter_map(|r| match r.action() {
+            Action::Delete(delete) => Some(delete.deletes_address.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::DeadLetter.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query(filter)?;
+
+    records
+        .into_iter()
+        .filter(|r| !deleted_actions.contains(r.action_address()))
+        .map(|r| {
+            let Some(entry) = r.entry().as_option().clone() else {
+                return Err(wasm_error!("DeadLetter record contained no entry."));
+            };
+            Ok(DeadLetterRecord {
+                action_hash: r.action_address().clone(),
+                dead_letter: DeadLetter::try_from(entry)?,
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RetryDeadLettersReport {
+    pub delivered: Vec<ActionHash>,
+    /// The dead letters that still couldn't be delivered, with the error, which stay in the queue
+    pub failed: Vec<(ActionHash, String)>,
+}
+
+/// Tries to hand off the given dead letters to their target zome again, deleting the ones that were delivered
+#[hdk_extern]
+pub fn retry_dead_letters(action_hashes: Vec<ActionHash>) -> ExternResult<RetryDeadLettersReport> {
+    let dead_letters: BTreeMap<ActionHash, DeadLetter> = query_dead_letters(())?
+        .into_iter()
+        .map(|d| (d.action_hash, d.dead_letter))
+        .collect();
+    let mut processed_message_ids = query_processed_message_ids()?;
+    let allowed_target_zomes = query_allowed_target_zomes(())?;
+    let mut report = RetryDeadLettersReport::default();
+
+    for action_hash in action_hashes {
+        let Some(dead_letter) = dead_letters.get(&action_hash) else {
+            return Err(wasm_error!("Action {} is not a dead letter.", action_hash));
+        };
+        let result = deserialize_pending_message(
+            dead_letter.provenance.clone(),
+            dead_letter.message.clone(),
+        )
+        .and_then(|pending_message| {
+            hand_off_message(
+                &allowed_target_zomes,
+                pending_message,
+                &mut processed_message_ids,
+            )
+        });
+
+        match result {
+            Ok(()) => {
+                delete_entry(DeleteInput::new(
+                    action_hash.clone(),
+                    ChainTopOrdering::Relaxed,
+                ))?;
+                report.delivered.push(action_hash);
+            }
+            Err(err) => report.failed.push((action_hash, format!("{err:?}"))),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Deletes the given dead letters without delivering them
+#[hdk_extern]
+pub fn purge_dead_letters(action_hashes: Vec<ActionHash>) -> ExternResult<()> {
+    let dead_letter_actions: BTreeSet<ActionHash> = query_dead_letters(())?
+        .into_iter()
+        .map(|d| d.action_hash)
+        .collect();
+
+    for action_hash in action_hashes {
+        if !dead_letter_actions.contains(&action_hash) {
+            return Err(wasm_error!("Action {} is not a dead letter.", action_hash));
+        }
+        delete_entry(DeleteInput::new(action_hash, ChainTopOrdering::Relaxed))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use encrypted_links_integrity::{EntryTypes, FailedDeliveryAttempt, UnitEntryTypes};
+use hdk::prelude::*;
+
+use crate::utils::{create_relaxed, delete_relaxed, query_deleted_actions_since};
+
+const DEFAULT_MAX_DELIVERY_ATTEMPTS: usize = 5;
+
+/// Maximum number of times we try to hand off a message in our inbox to its target zome
+///
+/// Configurable at build time with the MAX_INBOX_DELIVERY_ATTEMPTS environment variable
+pub fn max_delivery_attempts() -> usize {
+    std::option_env!("MAX_INBOX_DELIVERY_ATTEMPTS")
+        .and_then(|attempts| attempts.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_DELIVERY_ATTEMPTS)
+}
+
+/// The actions that recorded the failed delivery attempts for each AgentEncryptedMessage link,
+/// excluding the attempts that were deleted when their message left our inbox
+pub fn query_failed_delivery_attempts() -> ExternResult<BTreeMap<ActionHash, Vec<ActionHash>>> {
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::FailedDeliveryAttempt.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query(filter)?;
+
+    let deleted_actions: BTreeSet<ActionHash> = match records.first() {
+        Some(first_record) => query_deleted_actions_since(first_record.action().action_seq())?,
+        None => BTreeSet::new(),
+    };
+
+    let mut attempts: BTreeMap<ActionHash, Vec<ActionHash>> = BTreeMap::new();
+    for record in records {
+        if deleted_actions.contains(record.action_address()) {
+            continue;
+        }
+        let Some(entry) = record.entry().as_option().clone() else {
+            return Err(wasm_error!(
+                "FailedDeliveryAttempt record contained no entry."
+            ));
+        };
+        let failed_delivery_attempt = FailedDeliveryAttempt::try_from(entry)?;
+        attempts
+            .entry(failed_delivery_attempt.create_link_hash)
+            .or_default()
+            .push(record.action_address().clone());
+    }
+    Ok(attempts)
+}
+
+pub fn record_failed_delivery_attempt(
+    create_link_hash: ActionHash,
+    error: String,
+) -> ExternResult<()> {
+    create_relaxed(EntryTypes::FailedDeliveryAttempt(FailedDeliveryAttempt {
+        create_link_hash,
+        error,
+    }))?;
+    Ok(())
+}
+
+/// Deletes the given failed delivery attempts, once their message has been handed off or discarded
+pub fn delete_failed_delivery_attempts(attempts: &[ActionHash]) -> ExternResult<()> {
+    for attempt in attempts {
+        delete_relaxed(attempt.clone())?;
+    }
+    Ok(())
+}
@@ -1,4 +1,8 @@
-use agent_encrypted_message::{create_encrypted_message, get_my_pending_encrypted_messages};
+use agent_encrypted_message::{
+    create_encrypted_message, decrypt_pending_message, delete_pending_message,
+    deserialize_pending_message, get_agent_encrypted_messages, is_pending_message,
+    query_encrypted_messages_in_last_minute, PendingMessage,
+};
 pub use encrypted_links_integrity::*;
 use hc_zome_traits::*;
 use hdk::prelude::*;
@@ -6,8 +10,25 @@ use private_event_sourcing_types::ReceiveMessageInput;
 use send_async_message_zome_trait::SendAsyncMessage;
 
 mod agent_encrypted_message;
+mod blocked_agents;
+mod dead_letters;
+mod delivery_attempts;
+mod message_ids;
+mod target_zomes;
 mod utils;
 
+use blocked_agents::query_blocked_agents;
+use dead_letters::DeadLetterQueue;
+use delivery_attempts::{
+    delete_failed_delivery_attempts, max_delivery_attempts, query_failed_delivery_attempts,
+    record_failed_delivery_attempt,
+};
+use message_ids::{
+    delete_expired_message_id_records, query_processed_message_ids, query_sent_message_ids,
+    record_message_id,
+};
+use target_zomes::{check_target_zome, query_allowed_target_zomes};
+
 #[implemented_zome_traits]
 pub enum ZomeTraits {
     SendAsyncMessage(EncryptedMessagesInLinks),
@@ -19,26 +40,77 @@ pub struct EncryptedMessagesInLinks;
 pub struct MessageWithZomeName {
     pub zome_name: ZomeName,
     pub message: Vec<u8>,
+    /// Missing in messages sent by agents running older versions
+    #[serde(default)]
+    pub message_id: Option<String>,
 }
 
 #[implement_zome_trait_as_externs]
 impl SendAsyncMessage for EncryptedMessagesInLinks {
     fn send_async_message(
         input: send_async_message_zome_trait::SendAsyncMessageInput,
-    ) -> ExternResult<()> {
+    ) -> ExternResult<BTreeSet<AgentPubKey>> {
         let message = MessageWithZomeName {
             zome_name: input.zome_name,
             message: input.message,
+            message_id: Some(input.message_id.clone()),
         };
         let message_bytes = SerializedBytes::try_from(message)
             .map_err(|err| wasm_error!(err))?
             .bytes()
             .to_vec();
+        let sent_message_ids = query_sent_message_ids()?;
+        let now = sys_time()?;
+        let max_per_minute = max_encrypted_messages_per_minute();
+        let mut messages_in_last_minute = query_encrypted_messages_in_last_minute()?;
+        let mut rate_limited_recipients: BTreeSet<AgentPubKey> = BTreeSet::new();
         for recipient in input.recipients {
-            create_encrypted_message(recipient, message_bytes.clone())?;
+            // Only skip the message if it's still undelivered in the recipient's inbox
+            let sent_message = sent_message_ids.get(&(input.message_id.clone(), recipient.clone()));
+            if let Some(sent_message) = sent_message {
+                if let Some(create_link_hash) = &sent_message.create_link_hash {
+                    if is_pending_message(&recipient, create_link_hash)? {
+                        debug!(
+                            "[send_async_message] Message {} is still pending for {}: skipping.",
+                            input.message_id, recipient
+                        );
+                        // Renew the record so that it doesn't expire while the message is still pending
+                        if sent_message.needs_renewal(now) {
+                            record_message_id(MessageIdRecord::Sent {
+                                message_id: input.message_id.clone(),
+                                recipient,
+                                create_link_hash: Some(create_link_hash.clone()),
+                            })?;
+                        }
+                        continue;
+                    }
+                }
+            }
+            // Exceeding the rate limit would make the whole call fail: the recipient is returned instead,
+            // so that the caller doesn't consider the message sent to it
+            let recent_messages = messages_in_last_minute
+                .entry(recipient.clone())
+                .or_default();
+            if max_per_minute > 0 && *recent_messages >= max_per_minute {
+                warn!(
+                    "[send_async_message] Reached the limit of {max_per_minute} messages per minute to {}: not sending message {}.",
+                    recipient, input.message_id
+                );
+                rate_limited_recipients.insert(recipient);
+                continue;
+            }
+            *recent_messages += 1;
+
+            let create_link_hash =
+                create_encrypted_message(recipient.clone(), message_bytes.clone())?;
+            record_message_id(MessageIdRecord::Sent {
+                message_id: input.message_id.clone(),
+                recipient,
+                create_link_hash: Some(create_link_hash),
+            })?;
         }
 
-        Ok(())
+        Ok(rate_limited_recipients)
     }
 }
 
@@ -52,20 +124,218 @@ fn commit_pending_entries(_: Option<Schedule>) -> Option<Schedule> {
 }
 
 pub fn internal_commit_pending_entries() -> ExternResult<()> {
-    let messages = get_my_pending_encrypted_messages()?;
-
-    for (provenance, message, zome_name) in messages {
-        call_remote(
-            agent_info()?.agent_initial_pubkey,
-            zome_name,
-            FunctionName::from("receive_message"),
-            None,
-            ReceiveMessageInput {
-                provenance,
-                message,
-            },
-        )?;
-        debug!("[commit_pending_entries] received message successfully.");
+    // Messages stay in our inbox until some zome is allowed to receive them
+    let allowed_target_zomes = query_allowed_target_zomes(())?;
+    if allowed_target_zomes.is_empty() {
+        error!("[commit_pending_entries] no zome is allowed to receive async messages: set the ASYNC_MESSAGE_TARGET_ZOMES environment variable when building this zome, or call register_target_zome.");
+        return Ok(());
+    }
+
+    if let Err(err) = delete_expired_message_id_records() {
+        error!("[commit_pending_entries] failed to delete expired message ids: {err:?}.");
+    }
+
+    let links = get_agent_encrypted_messages(agent_info()?.agent_initial_pubkey)?;
+    let mut processed_message_ids = query_processed_message_ids()?;
+    let failed_delivery_attempts = query_failed_delivery_attempts()?;
+    let blocked_agents = query_blocked_agents(())?;
+    let mut dead_letter_queue = DeadLetterQueue::default();
+
+    for link in links {
+        let previous_attempts: &[ActionHash] = failed_delivery_attempts
+            .get(&link.create_link_hash)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        if blocked_agents.contains(&link.author) {
+            debug!(
+                "[commit_pending_entries] message sent by blocked agent {}: deleting it.",
+                link.author
+            );
+            if let Err(err) = remove_pending_message(&link, previous_attempts) {
+                error!("[commit_pending_entries] failed to delete message: {err:?}.");
+            }
+            continue;
+        }
+
+        let decrypted_bytes = match decrypt_pending_message(&link) {
+            Ok(Some(decrypted_bytes)) => decrypted_bytes,
+            Ok(None) => {
+                debug!("[commit_pending_entries] message is not available yet: skipping it.");
+                continue;
+            }
+            Err(err) => {
+                handle_failed_delivery(&mut dead_letter_queue, &link, None, err, previous_attempts);
+                continue;
+            }
+        };
+
+        let pending_message =
+            match deserialize_pending_message(link.author.clone(), decrypted_bytes.clone()) {
+                Ok(pending_message) => pending_message,
+                Err(err) => {
+                    // Retrying won't help: keep it until an upgrade can make sense of it
+                    if let Err(err) = dead_letter_pending_message(
+                        &mut dead_letter_queue,
+                        &link,
+                        previous_attempts,
+                        decrypted_bytes,
+                        err,
+                    ) {
+                        error!("[commit_pending_entries] failed to dead-letter message: {err:?}.");
+                    }
+                    continue;
+                }
+            };
+
+        if let Err(err) = check_target_zome(&allowed_target_zomes, &pending_message.zome_name) {
+            if let Err(err) = dead_letter_pending_message(
+                &mut dead_letter_queue,
+                &link,
+                previous_attempts,
+                decrypted_bytes,
+                err,
+            ) {
+                error!("[commit_pending_entries] failed to dead-letter message: {err:?}.");
+            }
+            continue;
+        }
+
+        let result = hand_off_message(
+            &allowed_target_zomes,
+            pending_message,
+            &mut processed_message_ids,
+        )
+        .and_then(|_| remove_pending_message(&link, previous_attempts));
+        if let Err(err) = result {
+            handle_failed_delivery(
+                &mut dead_letter_queue,
+                &link,
+                Some(decrypted_bytes),
+                err,
+                previous_attempts,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Records the failed attempt, or moves the message to the dead-letter queue if there are no attempts left
+///
+/// Messages that couldn't even be decrypted are dropped instead
+fn handle_failed_delivery(
+    dead_letter_queue: &mut DeadLetterQueue,
+    link: &Link,
+    decrypted_bytes: Option<Vec<u8>>,
+    err: WasmError,
+    previous_attempts: &[ActionHash],
+) {
+    let attempts = previous_attempts.len() + 1;
+    if attempts < max_delivery_attempts() {
+        warn!(
+            "[commit_pending_entries] failed to deliver message {} (attempt {attempts}): {err:?}.",
+            link.create_link_hash
+        );
+        if let Err(err) =
+            record_failed_delivery_attempt(link.create_link_hash.clone(), format!("{err:?}"))
+        {
+            error!("[commit_pending_entries] failed to record failed delivery attempt: {err:?}.");
+        }
+        return;
+    }
+
+    let result = match decrypted_bytes {
+        Some(decrypted_bytes) => dead_letter_pending_message(
+            dead_letter_queue,
+            link,
+            previous_attempts,
+            decrypted_bytes,
+            err,
+        ),
+        None => {
+            error!(
+                "[commit_pending_entries] giving up on message {} after {attempts} failed attempts: {err:?}.",
+                link.create_link_hash
+            );
+            remove_pending_message(link, previous_attempts)
+        }
+    };
+    if let Err(err) = result {
+        error!("[commit_pending_entries] failed to give up on message: {err:?}.");
+    }
+}
+
+/// Moves the message from our inbox to the dead-letter queue
+fn dead_letter_pending_message(
+    dead_letter_queue: &mut DeadLetterQueue,
+    link: &Link,
+    previous_attempts: &[ActionHash],
+    decrypted_bytes: Vec<u8>,
+    err: WasmError,
+) -> ExternResult<()> {
+    dead_letter_queue.create_dead_letter(
+        link.author.clone(),
+        decrypted_bytes,
+        format!("{err:?}"),
+    )?;
+    remove_pending_message(link, previous_attempts)
+}
+
+/// Removes the message from our inbox together with its failed delivery attempts,
+/// only to be called after it has been handed off or discarded
+fn remove_pending_message(link: &Link, previous_attempts: &[ActionHash]) -> ExternResult<()> {
+    delete_pending_message(link.create_link_hash.clone())?;
+    delete_failed_delivery_attempts(previous_attempts)
+}
+
+/// Hands off the message to its target zome if it's allowed
+///
+/// A message with an id that was already processed is dropped, unless it carries private events:
+/// receiving them again is idempotent, and the target zome acknowledges them again in case the first acknowledgement was lost
+fn hand_off_message(
+    allowed_target_zomes: &BTreeSet<ZomeName>,
+    pending_message: PendingMessage,
+    processed_message_ids: &mut BTreeSet<(String, AgentPubKey)>,
+) -> ExternResult<()> {
+    let PendingMessage {
+        provenance,
+        message,
+        zome_name,
+        message_id,
+    } = pending_message;
+    check_target_zome(allowed_target_zomes, &zome_name)?;
+
+    let already_processed = message_id.as_ref().is_some_and(|message_id| {
+        processed_message_ids.contains(&(message_id.clone(), provenance.clone()))
+    });
+    if already_processed && message.private_events.is_empty() {
+        debug!(
+            "[commit_pending_entries] message {message_id:?} was already processed: dropping it."
+        );
+        return Ok(());
+    }
+    let response = call_remote(
+        agent_info()?.agent_initial_pubkey,
+        zome_name,
+        FunctionName::from("receive_message"),
+        None,
+        ReceiveMessageInput {
+            provenance: provenance.clone(),
+            message,
+        },
+    )?;
+    let ZomeCallResponse::Ok(_) = response else {
+        return Err(wasm_error!("Failed to receive message: {response:?}."));
+    };
+    debug!("[commit_pending_entries] received message successfully.");
+
+    if let Some(message_id) = message_id.filter(|_| !already_processed) {
+        record_message_id(MessageIdRecord::Processed {
+            message_id: message_id.clone(),
+            provenance: provenance.clone(),
+        })?;
+        processed_message_ids.insert((message_id, provenance));
     }
 
     Ok(())
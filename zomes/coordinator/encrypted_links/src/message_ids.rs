@@ -0,0 +1,140 @@
+use encrypted_links_integrity::{EntryTypes, MessageIdRecord, UnitEntryTypes};
+use hdk::prelude::*;
+
+use crate::utils::{create_relaxed, delete_relaxed, query_deleted_actions_since};
+
+const DEFAULT_MESSAGE_ID_RECORDS_TTL_MS: i64 = 7 * 24 * 60 * 60 * 1000; // 7 days
+
+/// Time in microseconds after which the record of a sent or processed message id is deleted,
+/// and messages with the same id are not deduplicated anymore
+///
+/// Configurable at build time with the MESSAGE_ID_RECORDS_TTL_MS environment variable, ignoring non positive values
+pub fn message_id_records_ttl() -> i64 {
+    std::option_env!("MESSAGE_ID_RECORDS_TTL_MS")
+        .and_then(|ttl| ttl.parse::<i64>().ok())
+        .filter(|ttl| *ttl > 0)
+        .unwrap_or(DEFAULT_MESSAGE_ID_RECORDS_TTL_MS)
+        .saturating_mul(1000)
+}
+
+/// A message id record, together with the action that created it
+pub struct StoredMessageIdRecord {
+    pub action_hash: ActionHash,
+    pub timestamp: Timestamp,
+    pub message_id_record: MessageIdRecord,
+}
+
+impl StoredMessageIdRecord {
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.timestamp
+            .as_micros()
+            .saturating_add(message_id_records_ttl())
+            <= now.as_micros()
+    }
+}
+
+/// The message id records that haven't been deleted
+///
+/// Records that outlived their TTL, for example because the scheduled task didn't run while we were offline,
+/// are returned too so that they are deleted as expired
+pub fn query_message_id_records() -> ExternResult<Vec<StoredMessageIdRecord>> {
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::MessageIdRecord.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records: Vec<Record> = query(filter)?;
+
+    let deleted_actions: BTreeSet<ActionHash> = match records.first() {
+        Some(first_record) => query_deleted_actions_since(first_record.action().action_seq())?,
+        None => BTreeSet::new(),
+    };
+
+    records
+        .into_iter()
+        .filter(|r| !deleted_actions.contains(r.action_address()))
+        .map(|r| {
+            let Some(entry) = r.entry().as_option().clone() else {
+                return Err(wasm_error!("MessageIdRecord record contained no entry."));
+            };
+            let message_id_record = MessageIdRecord::try_from(entry)?;
+            Ok(StoredMessageIdRecord {
+                action_hash: r.action_address().clone(),
+                timestamp: r.action().timestamp(),
+                message_id_record,
+            })
+        })
+        .collect()
+}
+
+/// A message that we have sent, with the link to it in the recipient's inbox
+pub struct SentMessage {
+    /// Missing in records created by older versions
+    pub create_link_hash: Option<ActionHash>,
+    /// When the latest record for the message was created
+    pub recorded_at: Timestamp,
+}
+
+impl SentMessage {
+    /// Whether the record for this message expires in less than half the TTL
+    pub fn needs_renewal(&self, now: Timestamp) -> bool {
+        self.recorded_at
+            .as_micros()
+            .saturating_add(message_id_records_ttl() / 2)
+            <= now.as_micros()
+    }
+}
+
+/// The ids of the messages that we have sent to each recipient
+pub fn query_sent_message_ids() -> ExternResult<BTreeMap<(String, AgentPubKey), SentMessage>> {
+    let now = sys_time()?;
+    Ok(query_message_id_records()?
+        .into_iter()
+        .filter(|record| !record.is_expired(now))
+        .filter_map(|record| match record.message_id_record {
+            MessageIdRecord::Sent {
+                message_id,
+                recipient,
+                create_link_hash,
+            } => Some((
+                (message_id, recipient),
+                SentMessage {
+                    create_link_hash,
+                    recorded_at: record.timestamp,
+                },
+            )),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The ids of the messages that we have already processed from each provenance
+pub fn query_processed_message_ids() -> ExternResult<BTreeSet<(String, AgentPubKey)>> {
+    let now = sys_time()?;
+    Ok(query_message_id_records()?
+        .into_iter()
+        .filter(|record| !record.is_expired(now))
+        .filter_map(|record| match record.message_id_record {
+            MessageIdRecord::Processed {
+                message_id,
+                provenance,
+            } => Some((message_id, provenance)),
+            _ => None,
+        })
+        .collect())
+}
+
+pub fn record_message_id(message_id_record: MessageIdRecord) -> ExternResult<()> {
+    create_relaxed(EntryTypes::MessageIdRecord(message_id_record))?;
+    Ok(())
+}
+
+/// Deletes the message id records that have expired
+pub fn delete_expired_message_id_records() -> ExternResult<()> {
+    let now = sys_time()?;
+    for record in query_message_id_records()? {
+        if record.is_expired(now) {
+            delete_relaxed(record.action_hash)?;
+        }
+    }
+    Ok(())
+}
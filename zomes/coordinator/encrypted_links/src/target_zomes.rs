@@ -0,0 +1,125 @@
+use encrypted_links_integrity::{EntryTypes, TargetZome, UnitEntryTypes};
+use hdk::prelude::*;
+
+use crate::utils::create_relaxed;
+
+/// Zomes in our cell that incoming async messages are allowed to be handed off to, configured at build time
+///
+/// Configurable at build time with the ASYNC_MESSAGE_TARGET_ZOMES environment variable, as a comma-separated list of zome names
+pub fn configured_target_zomes() -> BTreeSet<ZomeName> {
+    std::option_env!("ASYNC_MESSAGE_TARGET_ZOMES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|zome| zome.trim())
+        .filter(|zome| !zome.is_empty())
+        .map(|zome| ZomeName::from(zome.to_string()))
+        .collect()
+}
+
+/// The actions that registered each target zome, excluding the ones that were deleted when unregistering them
+fn query_registered_target_zomes_actions() -> ExternResult<BTreeMap<ZomeName, Vec<ActionHash>>> {
+    let filter = ChainQueryFilter::new().action_type(ActionType::Delete);
+    let deleted_actions: BTreeSet<ActionHash> = query(filter)?
+        .into_iter()
+        .filter_map(|r| match r.action() {
+            Action::Delete(delete) => Some(delete.deletes_address.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::TargetZome.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query(filter)?;
+
+    let mut target_zomes: BTreeMap<ZomeName, Vec<ActionHash>> = BTreeMap::new();
+    for record in records {
+        if deleted_actions.contains(record.action_address()) {
+            continue;
+        }
+        let Some(entry) = record.entry().as_option().clone() else {
+            return Err(wasm_error!("TargetZome record contained no entry."));
+        };
+        let target_zome = TargetZome::try_from(entry)?;
+        target_zomes
+            .entry(target_zome.zome_name)
+            .or_default()
+            .push(record.action_address().clone());
+    }
+    Ok(target_zomes)
+}
+
+/// Zomes in our cell that incoming async messages are allowed to be handed off to:
+/// the ones configured at build time and the ones registered at runtime
+#[hdk_extern]
+pub fn query_allowed_target_zomes() -> ExternResult<BTreeSet<ZomeName>> {
+    let mut target_zomes = configured_target_zomes();
+    target_zomes.extend(query_registered_target_zomes_actions()?.into_keys());
+    Ok(target_zomes)
+}
+
+/// Allows incoming async messages to be handed off to the given zome
+///
+/// Only callable by ourselves, since remote agents must not be able to choose which zomes receive their messages
+#[hdk_extern]
+pub fn register_target_zome(zome_name: ZomeName) -> ExternResult<()> {
+    check_local_call()?;
+    if zome_info()?.name.eq(&zome_name) {
+        return Err(wasm_error!(
+            "Async messages can't be handed off to the zome that receives them."
+        ));
+    }
+    if query_allowed_target_zomes(())?.contains(&zome_name) {
+        return Ok(());
+    }
+    create_relaxed(EntryTypes::TargetZome(TargetZome { zome_name }))?;
+    Ok(())
+}
+
+/// Stops handing off incoming async messages to the given zome, unless it was configured at build time
+#[hdk_extern]
+pub fn unregister_target_zome(zome_name: ZomeName) -> ExternResult<()> {
+    check_local_call()?;
+    let Some(action_hashes) = query_registered_target_zomes_actions()?.remove(&zome_name) else {
+        return Ok(());
+    };
+    for action_hash in action_hashes {
+        delete_entry(DeleteInput::new(action_hash, ChainTopOrdering::Relaxed))?;
+    }
+    Ok(())
+}
+
+fn check_local_call() -> ExternResult<()> {
+    if call_info()?
+        .provenance
+        .ne(&agent_info()?.agent_initial_pubkey)
+    {
+        return Err(wasm_error!(
+            "Only the agent of this cell can call this function."
+        ));
+    }
+    Ok(())
+}
+
+/// Fails if the sender of a message asked us to hand it off to a zome that is not allowed
+///
+/// Messages for zomes that are not allowed are moved to the dead-letter queue,
+/// so that they can be retried once their zome is registered
+pub fn check_target_zome(
+    allowed_target_zomes: &BTreeSet<ZomeName>,
+    zome_name: &ZomeName,
+) -> ExternResult<()> {
+    if allowed_target_zomes.is_empty() {
+        return Err(wasm_error!(
+            "No zome is allowed to receive async messages: set the ASYNC_MESSAGE_TARGET_ZOMES environment variable when building this zome, or call register_target_zome."
+        ));
+    }
+    if !allowed_target_zomes.contains(zome_name) {
+        return Err(wasm_error!(
+            "Zome {} is not an allowed target for async messages.",
+            zome_name
+        ));
+    }
+    Ok(())
+}
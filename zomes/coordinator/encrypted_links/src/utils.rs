@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use encrypted_links_integrity::*;
 use hdk::prelude::*;
 
@@ -24,7 +26,7 @@ pub fn create_link_relaxed<T, E>(
     target_address: impl Into<AnyLinkableHash>,
     link_type: T,
     tag: impl Into<LinkTag>,
-) -> ExternResult<()>
+) -> ExternResult<ActionHash>
 where
     ScopedLinkType: TryFrom<T, Error = E>,
     WasmError: From<E>,
@@ -42,9 +44,7 @@ where
             tag.into(),
             ChainTopOrdering::Relaxed,
         ))
-    })?;
-
-    Ok(())
+    })
 }
 
 pub fn delete_link_relaxed(address: ActionHash) -> ExternResult<()> {
@@ -64,3 +64,20 @@ pub fn delete_relaxed(address: ActionHash) -> ExternResult<()> {
 
     Ok(())
 }
+
+/// The actions deleted by the delete actions in our source chain from the given action sequence on
+pub fn query_deleted_actions_since(first_action_seq: u32) -> ExternResult<BTreeSet<ActionHash>> {
+    let filter = ChainQueryFilter::new()
+        .action_type(ActionType::Delete)
+        .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+            first_action_seq,
+            u32::MAX,
+        ));
+    Ok(query(filter)?
+        .into_iter()
+        .filter_map(|r| match r.action() {
+            Action::Delete(delete) => Some(delete.deletes_address.clone()),
+            _ => None,
+        })
+        .collect())
+}
@@ -35,7 +35,7 @@ impl PrivateEvent for Event {
 
                 Ok(recipients)
             }
-            _ => Ok(BTreeSet::new())
+            _ => Ok(BTreeSet::new()),
         }
     }
 
@@ -50,6 +50,11 @@ impl PrivateEvent for Event {
             _ => Ok(false),
         }
     }
+
+    fn persist_projection_snapshots() -> ExternResult<()> {
+        prune_projection_snapshots(&[<Friends as Projection<Event>>::PROJECTION_NAME])?;
+        persist_projection_snapshot::<Event, Friends>()
+    }
 }
 
 #[hdk_extern]
@@ -64,19 +69,54 @@ pub fn add_friend(friend: AgentPubKey) -> ExternResult<()> {
     Ok(())
 }
 
-pub fn query_friends() -> ExternResult<BTreeSet<AgentPubKey>> {
-    let private_events = query_private_events::<Event>()?;
+/// Whether the externs that only tests need are enabled
+///
+/// Enabled at build time by setting the ENABLE_TEST_EXTERNS environment variable, which only the test DNA does
+fn test_externs_enabled() -> bool {
+    std::option_env!("ENABLE_TEST_EXTERNS").is_some()
+}
+
+/// Signs the given content as one of our private events without committing it, so that tests can craft the events they receive
+#[hdk_extern]
+pub fn build_private_event_entry(
+    content: PrivateEventContent<SerializedBytes>,
+) -> ExternResult<PrivateEventEntry> {
+    if !test_externs_enabled() {
+        return Err(wasm_error!(
+            "build_private_event_entry is only enabled in tests."
+        ));
+    }
+    Ok(PrivateEventEntry(SignedEntry::build(content)?))
+}
 
-    let mut friends: BTreeSet<AgentPubKey> = BTreeSet::new();
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Friends(BTreeSet<AgentPubKey>);
 
-    for (_hash, private_event) in private_events {
-        let Event::NewFriend { friend } = private_event.payload.content.event else {
-            continue;
-        };
-        friends.insert(friend);
+impl Projection<Event> for Friends {
+    const PROJECTION_NAME: &'static str = "friends";
+
+    fn initial_state() -> Self {
+        Friends(BTreeSet::new())
+    }
+
+    fn apply(
+        state: Self,
+        _event_hash: EntryHash,
+        signed_event: SignedEvent<Event>,
+    ) -> ExternResult<Self> {
+        let mut friends = state.0;
+        if let Event::NewFriend { friend } = signed_event.payload.content.event {
+            friends.insert(friend);
+        }
+        Ok(Friends(friends))
     }
+}
 
-    Ok(friends)
+#[hdk_extern]
+pub fn query_friends() -> ExternResult<BTreeSet<AgentPubKey>> {
+    let friends = query_projection::<Event, Friends>()?;
+
+    Ok(friends.0)
 }
 
 #[hdk_extern]
@@ -90,25 +130,39 @@ pub fn recv_remote_signal(signal_bytes: SerializedBytes) -> ExternResult<()> {
     }
 }
 
+/// Imports the whole event history of the old cell, one chunk per zome call so that each chunk is committed on its own
 #[hdk_extern]
-pub fn migrate_from_old_cell(old_cell: CellId) -> ExternResult<()> {
-    let response = call(
-        CallTargetCell::OtherCell(old_cell),
-        zome_info()?.name,
-        "export_event_history".into(),
-        None,
-        (),
-    )?;
-
-    let ZomeCallResponse::Ok(result) = response else {
-        return Err(wasm_error!(
-            "Error quering the old private event entries: {:?}.",
-            response
-        ));
-    };
-    let event_history: EventHistory = result.decode().map_err(|err| wasm_error!(err))?;
+pub fn migrate_from_old_cell(old_cell: CellId) -> ExternResult<ImportEventHistoryReport> {
+    let mut report = ImportEventHistoryReport::default();
+
+    loop {
+        let response = call(
+            CallTargetCell::Local,
+            zome_info()?.name,
+            "migrate_next_chunk_from_old_cell".into(),
+            None,
+            old_cell.clone(),
+        )?;
+
+        let ZomeCallResponse::Ok(result) = response else {
+            return Err(wasm_error!(
+                "Error migrating the event history from the old cell: {:?}.",
+                response
+            ));
+        };
+        let output: ImportEventHistoryChunkOutput =
+            result.decode().map_err(|err| wasm_error!(err))?;
+        report.append(output.report);
 
-    import_event_history(event_history)?;
+        if output.finished {
+            return Ok(report);
+        }
+    }
+}
 
-    Ok(())
+#[hdk_extern]
+pub fn migrate_next_chunk_from_old_cell(
+    old_cell: CellId,
+) -> ExternResult<ImportEventHistoryChunkOutput> {
+    import_next_event_history_chunk::<Event>(old_cell, DEFAULT_EVENT_HISTORY_PAGE_SIZE_BYTES)
 }
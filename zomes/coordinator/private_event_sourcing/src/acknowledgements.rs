@@ -4,115 +4,132 @@ use hdk::prelude::*;
 use private_event_sourcing_integrity::*;
 
 use crate::{
-    query_event_histories, query_private_event_entries, query_private_event_entry,
-    send_async_message, utils::create_relaxed, PrivateEvent, PrivateEventSourcingRemoteSignal,
+    decode_private_event, outgoing_messages::OutgoingMessages, query_event_histories,
+    query_private_event_entry, utils::create_relaxed, validate_clock, ClockTicker, ImportedEntries,
+    PrivateEvent, ReceivedEntryOutcome,
 };
 
 pub fn create_pending_acknowledgements<T: PrivateEvent>(
     private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
     acknowledgement_entries: &Vec<Acknowledgement>,
+    clock_ticker: &mut ClockTicker,
 ) -> ExternResult<()> {
-    for (event_hash, private_event_entry) in private_event_entries {
-        create_acknowledgements_for_event::<T>(
-            event_hash.clone().into(),
-            private_event_entry.clone(),
-            &acknowledgement_entries,
-        )?;
-    }
-
-    Ok(())
+    create_acknowledgements_for_events::<T>(
+        private_event_entries,
+        acknowledgement_entries,
+        clock_ticker,
+    )
 }
 
 pub fn create_acknowledgements_for<T: PrivateEvent>(
     events_hashes: BTreeSet<EntryHash>,
+    clock_ticker: &mut ClockTicker,
 ) -> ExternResult<()> {
     let acknowledgement_entries = query_acknowledgement_entries(())?;
 
+    let mut private_event_entries: BTreeMap<EntryHashB64, PrivateEventEntry> = BTreeMap::new();
     for event_hash in events_hashes {
         let private_event_entry = query_private_event_entry(event_hash.clone())?
             .ok_or(wasm_error!("Event not found."))?;
-        create_acknowledgements_for_event::<T>(
-            event_hash,
-            private_event_entry,
-            &acknowledgement_entries,
-        )?;
+        private_event_entries.insert(event_hash.into(), private_event_entry);
     }
 
-    Ok(())
+    create_acknowledgements_for_events::<T>(
+        &private_event_entries,
+        &acknowledgement_entries,
+        clock_ticker,
+    )
 }
 
-fn create_acknowledgements_for_event<T: PrivateEvent>(
-    event_hash: EntryHash,
-    private_event_entry: PrivateEventEntry,
+/// Acknowledges all the given events that we haven't acknowledged yet, and sends the acknowledgements to their
+/// authors and recipients batched together
+///
+/// All the events that have the same set of recipients are acknowledged with a single cumulative acknowledgement,
+/// so that recipients never receive acknowledgements for events that they don't have
+///
+/// Cumulative acknowledgements list every acknowledged event instead of acknowledging all the events up to a head
+/// of the event DAG, since the ancestors of a head can have different recipients than the head itself
+///
+/// Events that can't be decoded or whose recipients can't be computed are not acknowledged,
+/// so that they don't prevent acknowledging the others
+fn create_acknowledgements_for_events<T: PrivateEvent>(
+    private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
     acknowledgement_entries: &Vec<Acknowledgement>,
+    clock_ticker: &mut ClockTicker,
 ) -> ExternResult<()> {
-    if private_event_entry
-        .0
-        .author
-        .eq(&agent_info()?.agent_initial_pubkey)
-    {
-        return Ok(()); // We are the author, no need to create acknowledgement
-    }
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
 
-    if acknowledgement_entries.iter().any(|a| {
-        a.0.payload
-            .content
-            .private_event_hash
-            .eq(&EntryHash::from(event_hash.clone()))
-    }) {
-        // We have already created an acknowledgement for this entry
-        return Ok(());
-    }
-    let acknowledgement_content = AcknowledgementContent {
-        private_event_hash: event_hash.clone().into(),
-    };
-    let signed_entry = SignedEntry::build(acknowledgement_content)?;
-    let acknowledgement = Acknowledgement(signed_entry);
+    let my_acknowledged_events: BTreeSet<EntryHash> = acknowledgement_entries
+        .iter()
+        .filter(|a| a.0.author.eq(&my_pub_key))
+        .flat_map(|a| a.0.payload.content.private_event_hashes())
+        .collect();
 
-    info!("Creating acknowledgement for entry {}.", event_hash);
-    create_relaxed(EntryTypes::Acknowledgement(acknowledgement.clone()))?;
+    let mut events_by_recipients: BTreeMap<BTreeSet<AgentPubKey>, BTreeSet<EntryHash>> =
+        BTreeMap::new();
 
-    let private_event = T::try_from(private_event_entry.0.payload.content.event.clone())
-        .map_err(|_err| wasm_error!("Failed to deserialize the private event."))?;
+    for (event_hash, private_event_entry) in private_event_entries {
+        let event_hash = EntryHash::from(event_hash.clone());
+        if private_event_entry.0.author.eq(&my_pub_key) {
+            continue; // We are the author, no need to create acknowledgement
+        }
+        if my_acknowledged_events.contains(&event_hash) {
+            // We have already created an acknowledgement for this entry
+            continue;
+        }
 
-    let mut recipients = private_event.recipients(
-        event_hash.clone().into(),
-        private_event_entry.0.author.clone(),
-        private_event_entry.0.payload.timestamp,
-    )?;
+        let private_event = match decode_private_event::<T>(&private_event_entry.0.payload.content)
+        {
+            Ok(private_event) => private_event,
+            Err(err) => {
+                warn!("Not acknowledging event {event_hash}: failed to decode it: {err:?}.");
+                continue;
+            }
+        };
 
-    recipients.insert(private_event_entry.0.author);
+        let mut recipients = match private_event.recipients(
+            event_hash.clone(),
+            private_event_entry.0.author.clone(),
+            private_event_entry.0.payload.timestamp,
+        ) {
+            Ok(recipients) => recipients,
+            Err(err) => {
+                warn!("Not acknowledging event {event_hash}: failed to compute its recipients: {err:?}.");
+                continue;
+            }
+        };
+        recipients.insert(private_event_entry.0.author.clone());
+        recipients.remove(&my_pub_key);
 
-    let my_pub_key = agent_info()?.agent_initial_pubkey;
+        events_by_recipients
+            .entry(recipients)
+            .or_default()
+            .insert(event_hash);
+    }
 
-    let recipients: BTreeSet<AgentPubKey> = recipients
-        .into_iter()
-        .filter(|agent| agent.ne(&my_pub_key))
-        .collect();
+    let mut outgoing_messages = OutgoingMessages::default();
 
-    let message = Message {
-        private_events: vec![],
-        acknowledgements: vec![acknowledgement],
-        events_sent_to_recipients: vec![],
-    };
+    for (recipients, events_hashes) in events_by_recipients {
+        let Some(acknowledgement_content) = AcknowledgementContent::for_events(events_hashes)
+        else {
+            continue;
+        };
+        let signed_entry = clock_ticker.build_signed_entry(acknowledgement_content)?;
+        let acknowledgement = Acknowledgement(signed_entry);
 
-    if recipients.len() > 0 {
         info!(
-            "Sending acknowledgement for entry {} to {:?}.",
-            event_hash, recipients
+            "Creating acknowledgement for entries {:?}.",
+            acknowledgement.0.payload.content.private_event_hashes()
         );
+        create_relaxed(EntryTypes::Acknowledgement(acknowledgement.clone()))?;
 
-        send_remote_signal(
-            SerializedBytes::try_from(PrivateEventSourcingRemoteSignal::SendMessage(
-                message.clone(),
-            ))
-            .map_err(|err| wasm_error!(err))?,
-            recipients.clone().into_iter().collect(),
-        )?;
-
-        send_async_message(recipients, format!("{event_hash}/acknowledgement"), message)?;
+        for recipient in &recipients {
+            outgoing_messages.add_acknowledgement(recipient, &acknowledgement);
+        }
     }
 
+    outgoing_messages.send()?;
+
     Ok(())
 }
 
@@ -128,41 +145,23 @@ pub fn query_my_acknowledgement_for(
                 && a.0
                     .payload
                     .content
-                    .private_event_hash
-                    .eq(&EntryHash::from(event_hash.clone()))
+                    .acknowledges(&EntryHash::from(event_hash.clone()))
         })
         .cloned())
 }
 
-pub fn send_acknowledgement_for_event_to_recipient<T: PrivateEvent>(
+/// Adds our acknowledgement for the given event to the messages to be sent to the given recipient
+pub fn add_acknowledgement_for_event_to_recipient(
+    outgoing_messages: &mut OutgoingMessages,
     event_hash: &EntryHashB64,
     recipient: &AgentPubKey,
 ) -> ExternResult<()> {
     if let Some(acknowledgement) = query_my_acknowledgement_for(event_hash)? {
-        let message = Message {
-            private_events: vec![],
-            events_sent_to_recipients: vec![],
-            acknowledgements: vec![acknowledgement.clone()],
-        };
-
         info!(
             "Sending acknowledgement for event {} to agent {}.",
             event_hash, recipient
         );
-
-        send_remote_signal(
-            SerializedBytes::try_from(PrivateEventSourcingRemoteSignal::SendMessage(
-                message.clone(),
-            ))
-            .map_err(|err| wasm_error!(err))?,
-            vec![recipient.clone()],
-        )?;
-
-        send_async_message(
-            vec![recipient.clone()].into_iter().collect(),
-            format!("{event_hash}/acknowledgement"),
-            message,
-        )?;
+        outgoing_messages.add_acknowledgement(recipient, &acknowledgement);
     } else {
         warn!("Received an event I already have but have not created an acknowledgement for.");
     }
@@ -174,53 +173,90 @@ pub fn receive_acknowledgements<T: PrivateEvent>(
     current_events: &BTreeMap<EntryHashB64, PrivateEventEntry>,
     provenance: AgentPubKey,
     acknowledgements: Vec<Acknowledgement>,
+    report: &mut ImportedEntries,
 ) -> ExternResult<()> {
     let current_acknowledgements = query_acknowledgement_entries(())?;
 
     for acknowledgement in acknowledgements {
-        if current_acknowledgements
-            .iter()
-            .find(|a| a.eq(&&acknowledgement))
-            .is_some()
-        {
-            // We already have this acknowledgement committed
-            continue;
-        }
+        let entry_hash = EntryHashB64::from(hash_entry(&acknowledgement)?);
+        let outcome = receive_acknowledgement(
+            current_events,
+            &current_acknowledgements,
+            &provenance,
+            acknowledgement,
+        );
+        report.record(entry_hash, outcome);
+    }
 
-        let valid = acknowledgement.0.verify()?;
+    Ok(())
+}
 
-        if !valid {
-            return Err(wasm_error!("Invalid acknowledgement: invalid signature."));
-        }
+fn receive_acknowledgement(
+    current_events: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    current_acknowledgements: &Vec<Acknowledgement>,
+    provenance: &AgentPubKey,
+    acknowledgement: Acknowledgement,
+) -> ExternResult<ReceivedEntryOutcome> {
+    if current_acknowledgements.contains(&acknowledgement) {
+        // We already have this acknowledgement committed
+        return Ok(ReceivedEntryOutcome::AlreadyKnown);
+    }
 
-        let event_hash = acknowledgement.0.payload.content.private_event_hash.clone();
-
-        if current_events.contains_key(&EntryHashB64::from(event_hash.clone())) {
-            info!(
-                "Received acknowledgement for entry {} from agent {}.",
-                event_hash, provenance,
-            );
-            create_relaxed(EntryTypes::Acknowledgement(acknowledgement))?;
-        } else {
-            create_relaxed(EntryTypes::AwaitingDependencies(
-                AwaitingDependencies::Acknowledgement { acknowledgement },
-            ))?;
-        }
+    if !acknowledgement.0.verify()? {
+        return Ok(ReceivedEntryOutcome::Invalid(String::from(
+            "Invalid acknowledgement: invalid signature.",
+        )));
     }
 
-    Ok(())
+    if let Err(reason) = validate_clock(&acknowledgement.0.payload)? {
+        return Ok(ReceivedEntryOutcome::Invalid(format!(
+            "Invalid acknowledgement: {reason}"
+        )));
+    }
+
+    let events_hashes = acknowledgement.0.payload.content.private_event_hashes();
+
+    if events_hashes
+        .iter()
+        .all(|event_hash| current_events.contains_key(&EntryHashB64::from(event_hash.clone())))
+    {
+        info!(
+            "Received acknowledgement for entries {:?} from agent {}.",
+            events_hashes, provenance,
+        );
+        create_relaxed(EntryTypes::Acknowledgement(acknowledgement))?;
+        Ok(ReceivedEntryOutcome::Accepted)
+    } else {
+        create_relaxed(EntryTypes::AwaitingDependencies(
+            AwaitingDependencies::Acknowledgement { acknowledgement },
+        ))?;
+        Ok(ReceivedEntryOutcome::Deferred)
+    }
 }
 
+/// The events acknowledged by each agent, with the time of their first acknowledgement
 pub fn compute_acknowledgements_by_agents(
     acknowledgements: &Vec<Acknowledgement>,
-) -> ExternResult<BTreeMap<AgentPubKey, BTreeSet<EntryHash>>> {
-    let mut all_acknowledgements: BTreeMap<AgentPubKey, BTreeSet<EntryHash>> = BTreeMap::new();
+) -> ExternResult<BTreeMap<AgentPubKey, BTreeMap<EntryHash, Timestamp>>> {
+    let mut all_acknowledgements: BTreeMap<AgentPubKey, BTreeMap<EntryHash, Timestamp>> =
+        BTreeMap::new();
 
     for acknowledgement in acknowledgements {
-        all_acknowledgements
+        let timestamp = acknowledgement.0.payload.timestamp;
+        let agent_acknowledgements = all_acknowledgements
             .entry(acknowledgement.0.author.clone())
-            .or_insert(BTreeSet::new())
-            .insert(acknowledgement.0.payload.content.private_event_hash.clone());
+            .or_insert(BTreeMap::new());
+
+        for event_hash in acknowledgement.0.payload.content.private_event_hashes() {
+            agent_acknowledgements
+                .entry(event_hash)
+                .and_modify(|first_acknowledged| {
+                    if timestamp < *first_acknowledged {
+                        *first_acknowledged = timestamp;
+                    }
+                })
+                .or_insert(timestamp);
+        }
     }
 
     Ok(all_acknowledgements)
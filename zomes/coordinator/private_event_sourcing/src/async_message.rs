@@ -4,26 +4,29 @@ use send_async_message_zome_trait::SendAsyncMessageInput;
 
 use crate::{
     events_sent_to_recipients::receive_events_sent_to_recipients, query_private_event_entries,
-    receive_acknowledgements, receive_private_events, PrivateEvent,
+    receive_acknowledgements, receive_private_events, receive_read_receipts, receive_rejections,
+    ClockTicker, ImportedEntries, PrivateEvent, Signal,
 };
 
 fn async_message_zome() -> Option<ZomeName> {
     std::option_env!("ASYNC_MESSAGE_ZOME").map(|z| z.to_string().into())
 }
 
+/// Sends the message to the recipients with the async message zome, returning the ones that it was not sent to
+/// because they reached its rate limit
 pub fn send_async_message(
     recipients: BTreeSet<AgentPubKey>,
     message_id: String,
     message: Message,
-) -> ExternResult<()> {
+) -> ExternResult<BTreeSet<AgentPubKey>> {
     let Some(zome) = async_message_zome() else {
-        return Ok(());
+        return Ok(BTreeSet::new());
     };
 
     let bytes = SerializedBytes::try_from(message)
         .map_err(|_err| wasm_error!("Failed to serialize bytes"))?;
 
-    call(
+    let response = call(
         CallTargetCell::Local,
         zome,
         FunctionName::from("send_async_message"),
@@ -35,22 +38,75 @@ pub fn send_async_message(
             message: bytes.bytes().to_vec(),
         },
     )?;
+    let ZomeCallResponse::Ok(result) = response else {
+        return Err(wasm_error!("Failed to send async message: {response:?}."));
+    };
+
+    result.decode().map_err(|err| wasm_error!(err))
+}
+
+/// The outcome of receiving each of the entries in a message
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ReceiveMessageReport {
+    pub private_events: ImportedEntries,
+    pub events_sent_to_recipients: ImportedEntries,
+    pub acknowledgements: ImportedEntries,
+    pub read_receipts: ImportedEntries,
+    pub rejections: ImportedEntries,
+}
 
-    Ok(())
+/// What happened to a single received entry
+pub enum ReceivedEntryOutcome {
+    /// We already had the entry
+    AlreadyKnown,
+    Accepted,
+    /// The entry was added to the awaiting dependencies queue
+    Deferred,
+    Invalid(String),
+}
+
+impl ImportedEntries {
+    /// Records the outcome of receiving an entry
+    ///
+    /// Errors are recorded as rejections of that entry only, so that they don't prevent the rest of the message from being received
+    pub fn record(
+        &mut self,
+        entry_hash: EntryHashB64,
+        outcome: ExternResult<ReceivedEntryOutcome>,
+    ) {
+        match outcome {
+            Ok(ReceivedEntryOutcome::AlreadyKnown) => {}
+            Ok(ReceivedEntryOutcome::Accepted) => self.accepted.push(entry_hash),
+            Ok(ReceivedEntryOutcome::Deferred) => self.deferred.push(entry_hash),
+            Ok(ReceivedEntryOutcome::Invalid(reason)) => {
+                warn!("Discarding received entry {entry_hash}: {reason}");
+                self.rejected.insert(entry_hash, reason);
+            }
+            Err(err) => {
+                warn!("Error receiving entry {entry_hash}: {err:?}.");
+                self.rejected.insert(entry_hash, format!("{err:?}"));
+            }
+        }
+    }
 }
 
 pub fn receive_message<T: PrivateEvent>(
     provenance: AgentPubKey,
     message: Message,
-) -> ExternResult<()> {
+) -> ExternResult<ReceiveMessageReport> {
     debug!("[receive_message] start.");
 
+    let mut report = ReceiveMessageReport::default();
+
     let mut private_event_entries = query_private_event_entries(())?;
+    let mut clock_ticker = ClockTicker::new()?;
 
     let mut new_events = receive_private_events::<T>(
         &private_event_entries,
         provenance.clone(),
         message.private_events,
+        &mut report.private_events,
+        &mut clock_ticker,
     )?;
     debug!(
         "[receive_message] received {} new private events.",
@@ -64,6 +120,7 @@ pub fn receive_message<T: PrivateEvent>(
         &private_event_entries,
         provenance.clone(),
         message.events_sent_to_recipients,
+        &mut report.events_sent_to_recipients,
     )?;
     debug!(
         "[receive_message] received {} events_sent_to_recipients.",
@@ -75,8 +132,32 @@ pub fn receive_message<T: PrivateEvent>(
         &private_event_entries,
         provenance.clone(),
         message.acknowledgements,
+        &mut report.acknowledgements,
     )?;
     debug!("[receive_message] received {} acknowledgements.", count);
 
-    Ok(())
+    let count = message.read_receipts.len();
+    receive_read_receipts::<T>(
+        &private_event_entries,
+        provenance.clone(),
+        message.read_receipts,
+        &mut report.read_receipts,
+    )?;
+    debug!("[receive_message] received {} read receipts.", count);
+
+    let count = message.rejections.len();
+    receive_rejections::<T>(
+        &private_event_entries,
+        provenance.clone(),
+        message.rejections,
+        &mut report.rejections,
+    )?;
+    debug!("[receive_message] received {} rejections.", count);
+
+    emit_signal(Signal::MessageReceived {
+        provenance,
+        report: report.clone(),
+    })?;
+
+    Ok(report)
 }
@@ -4,60 +4,404 @@ use hdk::prelude::*;
 use private_event_sourcing_integrity::*;
 
 use crate::{
-    acknowledgements::query_acknowledgement_entries,
-    events_sent_to_recipients::query_events_sent_to_recipients_entries, query_event_histories,
-    query_private_event_entries, utils::create_relaxed, validate_private_event_entry, PrivateEvent,
+    causally_order_private_event_entries, decode_private_event,
+    missing_dependencies::{are_unavailable_for, is_recipient_for, UnavailableEvents},
+    missing_previous_events,
+    outgoing_messages::OutgoingMessages,
+    query_my_linked_devices, query_private_event_entries, query_read_receipt_entries,
+    query_rejection_entries, reject_private_event,
+    utils::{create_relaxed, query_deleted_actions_since},
+    validate_clock, validate_private_event_entry, ClockTicker, PrivateEvent, Quarantine,
 };
 
+const DEFAULT_AWAITING_DEPENDENCIES_TTL_MS: i64 = 30 * 24 * 60 * 60 * 1000; // 30 days
+
+/// Time in microseconds after which an entry that is still awaiting its dependencies is discarded
+///
+/// Configurable at build time with the AWAITING_DEPENDENCIES_TTL_MS environment variable, ignoring non positive values
+pub fn awaiting_dependencies_ttl() -> i64 {
+    std::option_env!("AWAITING_DEPENDENCIES_TTL_MS")
+        .and_then(|ttl| ttl.parse::<i64>().ok())
+        .filter(|ttl| *ttl > 0)
+        .unwrap_or(DEFAULT_AWAITING_DEPENDENCIES_TTL_MS)
+        .saturating_mul(1000)
+}
+
+/// An entry awaiting its dependencies, together with the action that created it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AwaitingDependenciesRecord {
+    /// None for the entries awaiting dependencies inside an imported EventHistory, which can't be deleted
+    pub action_hash: Option<ActionHash>,
+    pub timestamp: Timestamp,
+    pub awaiting_dependencies: AwaitingDependencies,
+}
+
+impl AwaitingDependenciesRecord {
+    pub fn expires_at(&self) -> Timestamp {
+        Timestamp::from_micros(
+            self.timestamp
+                .as_micros()
+                .saturating_add(awaiting_dependencies_ttl()),
+        )
+    }
+
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.expires_at() <= now
+    }
+}
+
+/// Commits the entries awaiting dependencies whose dependencies are now in our source chain
+///
+/// Awaiting events whose only missing previous events are the given unavailable events are committed without them
+///
+/// The private events that get committed are added to `private_event_entries`
 pub fn attempt_commit_awaiting_deps_entries<T: PrivateEvent>(
-    private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    private_event_entries: &mut BTreeMap<EntryHashB64, PrivateEventEntry>,
+    existing_events_sent_to_recipients: &Vec<EventSentToRecipients>,
+    existing_acknowledgements: &Vec<Acknowledgement>,
+    clock_ticker: &mut ClockTicker,
+    unavailable_events: Option<&UnavailableEvents>,
 ) -> ExternResult<()> {
-    let mut entries: Vec<PrivateEventEntry> = query_awaiting_deps_private_event_entries()?;
+    let now = sys_time()?;
+    let chain_head_seq = agent_info()?.chain_head.1;
+    let mut resolved_actions: BTreeSet<ActionHash> = BTreeSet::new();
+
+    let oldest_awaiting_action_seq = query_oldest_awaiting_action_seq()?;
+    let (expired_records, awaiting_records): (Vec<(u32, AwaitingDependenciesRecord)>, Vec<_>) =
+        query_awaiting_deps_records_since(oldest_awaiting_action_seq)?
+            .into_iter()
+            .partition(|(_, record)| record.is_expired(now));
+
+    // Until they are resolved, the entries that are still awaiting keep the checkpoint from moving past them
+    let awaiting_actions: Vec<(u32, Option<ActionHash>)> = awaiting_records
+        .iter()
+        .map(|(action_seq, record)| (*action_seq, record.action_hash.clone()))
+        .collect();
+    let mut awaiting_records: Vec<AwaitingDependenciesRecord> = awaiting_records
+        .into_iter()
+        .map(|(_, record)| record)
+        .collect();
+
+    let mut awaiting_events_hashes: BTreeSet<EntryHashB64> = awaiting_records
+        .iter()
+        .filter_map(|record| match &record.awaiting_dependencies {
+            AwaitingDependencies::Event { event, .. } => hash_entry(event).ok().map(Into::into),
+            _ => None,
+        })
+        .collect();
+
+    for (_, record) in expired_records {
+        resolved_actions.extend(record.action_hash.clone());
+
+        // Undecodable events don't expire: they are kept to be replayed once the app is upgraded
+        if let AwaitingDependencies::Event { event, .. } = &record.awaiting_dependencies {
+            if decode_private_event::<T>(&event.0.payload.content).is_err() {
+                let event_hash = EntryHashB64::from(hash_entry(event)?);
+                if awaiting_events_hashes.insert(event_hash) {
+                    let action_hash = create_relaxed(EntryTypes::AwaitingDependencies(
+                        record.awaiting_dependencies.clone(),
+                    ))?;
+                    awaiting_records.push(AwaitingDependenciesRecord {
+                        action_hash: Some(action_hash),
+                        timestamp: now,
+                        awaiting_dependencies: record.awaiting_dependencies,
+                    });
+                }
+                continue;
+            }
+        }
+
+        // The expired entries inside an imported EventHistory can't be deleted:
+        // they stop being queried once the checkpoint moves past their EventHistory
+        if record.action_hash.is_some() {
+            warn!(
+                "Discarding expired awaiting dependencies entry: {:?}.",
+                record.awaiting_dependencies
+            );
+        }
+    }
+
+    // The actions that created each awaiting entry, to delete them once they are resolved
+    let mut awaiting_events_actions: BTreeMap<EntryHashB64, Vec<ActionHash>> = BTreeMap::new();
+    let mut awaiting_events: BTreeMap<EntryHashB64, PrivateEventEntry> = BTreeMap::new();
+    // The agents that sent us each awaiting event, to send them the rejection if it's invalid
+    let mut awaiting_events_provenances: BTreeMap<EntryHashB64, AgentPubKey> = BTreeMap::new();
+    let mut other_awaiting_records: Vec<AwaitingDependenciesRecord> = vec![];
+
+    for record in awaiting_records {
+        match &record.awaiting_dependencies {
+            AwaitingDependencies::Event {
+                event, provenance, ..
+            } => {
+                let entry_hash = EntryHashB64::from(hash_entry(event)?);
+                awaiting_events_actions
+                    .entry(entry_hash.clone())
+                    .or_default()
+                    .extend(record.action_hash.clone());
+                if let Some(provenance) = provenance {
+                    awaiting_events_provenances.insert(entry_hash.clone(), provenance.clone());
+                }
+                awaiting_events.insert(entry_hash, event.clone());
+            }
+            _ => other_awaiting_records.push(record),
+        }
+    }
+
+    let mut entries: Vec<PrivateEventEntry> = causally_order_private_event_entries(awaiting_events)
+        .into_iter()
+        .map(|(_, private_event_entry)| private_event_entry)
+        .collect();
+
+    let mut outgoing_rejections = OutgoingMessages::default();
+    let mut quarantine = Quarantine::default();
+
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+    let my_rejected_events: BTreeSet<EntryHash> = query_rejection_entries(())?
+        .into_iter()
+        .filter(|r| r.0.author.eq(&my_pub_key))
+        .map(|r| r.0.payload.content.event_hash)
+        .collect();
+
+    // Committing an event may resolve the previous events of other awaiting events
+    loop {
+        let mut committed_any = false;
+        let mut still_awaiting: Vec<PrivateEventEntry> = vec![];
+
+        for private_event_entry in entries {
+            let entry_hash = hash_entry(&private_event_entry)?;
+            let event_hash = EntryHashB64::from(entry_hash.clone());
+            let actions = awaiting_events_actions
+                .get(&event_hash)
+                .cloned()
+                .unwrap_or_default();
 
-    entries.sort_by_key(|e1| e1.0.payload.timestamp);
+            if private_event_entries.contains_key(&event_hash) {
+                // We already have this event
+                resolved_actions.extend(actions);
+                continue;
+            }
+            if my_rejected_events.contains(&entry_hash) {
+                // We already rejected this event
+                resolved_actions.extend(actions);
+                continue;
+            }
 
-    for private_event_entry in entries {
-        let entry_hash = hash_entry(&private_event_entry)?;
+            let missing_previous_events =
+                missing_previous_events(&private_event_entry, private_event_entries);
+            if !missing_previous_events.is_empty()
+                && !are_unavailable_for(
+                    &missing_previous_events,
+                    &private_event_entry,
+                    unavailable_events,
+                )?
+            {
+                still_awaiting.push(private_event_entry);
+                continue;
+            }
 
-        if !private_event_entries.contains_key(&entry_hash.clone().into()) {
-            let valid = validate_private_event_entry::<T>(&private_event_entry)?;
+            // Events that fail to validate, usually because they can't be decoded yet, keep waiting
+            let Ok(valid) = validate_private_event_entry::<T>(&private_event_entry) else {
+                still_awaiting.push(private_event_entry);
+                continue;
+            };
 
             match valid {
                 ValidateCallbackResult::Valid => {
-                    create_relaxed(EntryTypes::PrivateEvent(private_event_entry))?;
+                    create_relaxed(EntryTypes::PrivateEvent(private_event_entry.clone()))?;
+                    clock_ticker.observe(private_event_entry.0.payload.clock())?;
+                    private_event_entries.insert(event_hash, private_event_entry);
+                    resolved_actions.extend(actions);
+                    committed_any = true;
                 }
                 ValidateCallbackResult::Invalid(reason) => {
                     error!("Invalid awaiting dependencies entry: {reason}");
+                    // The agent that sent us the event is unknown for imported events, so the author is used instead
+                    let provenance = awaiting_events_provenances
+                        .get(&event_hash)
+                        .cloned()
+                        .unwrap_or(private_event_entry.0.author.clone());
+                    // Rejections are capped per provenance the same way as the quarantine
+                    if quarantine.quarantine_private_event(
+                        &event_hash,
+                        &private_event_entry,
+                        &provenance,
+                        reason.clone(),
+                    )? {
+                        reject_private_event(
+                            &mut outgoing_rejections,
+                            clock_ticker,
+                            &event_hash,
+                            &private_event_entry,
+                            &provenance,
+                            reason,
+                        )?;
+                    }
+                    resolved_actions.extend(actions);
+                }
+                ValidateCallbackResult::UnresolvedDependencies(_) => {
+                    still_awaiting.push(private_event_entry);
                 }
-                ValidateCallbackResult::UnresolvedDependencies(_) => {}
             }
         }
+
+        if !committed_any {
+            break;
+        }
+        entries = still_awaiting;
     }
 
-    let private_event_entries = query_private_event_entries(())?;
+    outgoing_rejections.send()?;
+    quarantine.commit_overflow()?;
 
-    let events_sent_to_recipients = query_awaiting_deps_events_sent_to_recipients()?;
-    for events_sent_to_recipients in events_sent_to_recipients {
-        if private_event_entries.contains_key(&EntryHashB64::from(
-            events_sent_to_recipients
-                .0
-                .payload
-                .content
-                .event_hash
-                .clone(),
-        )) {
-            create_relaxed(EntryTypes::EventSentToRecipients(events_sent_to_recipients))?;
+    let existing_read_receipts = query_read_receipt_entries(())?;
+    let existing_rejections = query_rejection_entries(())?;
+    let my_linked_devices = query_my_linked_devices()?;
+
+    for record in other_awaiting_records {
+        // Entries with a malformed clock or a timestamp too far in the future are discarded
+        let (clock_outcome, clock) = match &record.awaiting_dependencies {
+            AwaitingDependencies::Event { .. } => continue,
+            AwaitingDependencies::EventsSentToRecipients {
+                event_sent_to_recipients,
+            } => (
+                validate_clock(&event_sent_to_recipients.0.payload)?,
+                event_sent_to_recipients.0.payload.clock(),
+            ),
+            AwaitingDependencies::Acknowledgement { acknowledgement } => (
+                validate_clock(&acknowledgement.0.payload)?,
+                acknowledgement.0.payload.clock(),
+            ),
+            AwaitingDependencies::ReadReceipt { read_receipt } => (
+                validate_clock(&read_receipt.0.payload)?,
+                read_receipt.0.payload.clock(),
+            ),
+            AwaitingDependencies::Rejection { rejection } => (
+                validate_clock(&rejection.0.payload)?,
+                rejection.0.payload.clock(),
+            ),
+        };
+        if let Err(reason) = clock_outcome {
+            warn!("Discarding entry awaiting dependencies: {reason}");
+            resolved_actions.extend(record.action_hash);
+            continue;
+        }
+        // The entries that we sign later in this zome call must come after the ones committed here
+        clock_ticker.observe(clock)?;
+
+        let resolved = match record.awaiting_dependencies {
+            AwaitingDependencies::Event { .. } => false,
+            AwaitingDependencies::EventsSentToRecipients {
+                event_sent_to_recipients,
+            } => {
+                if existing_events_sent_to_recipients.contains(&event_sent_to_recipients) {
+                    true
+                } else if private_event_entries.contains_key(&EntryHashB64::from(
+                    event_sent_to_recipients
+                        .0
+                        .payload
+                        .content
+                        .event_hash
+                        .clone(),
+                )) {
+                    create_relaxed(EntryTypes::EventSentToRecipients(event_sent_to_recipients))?;
+                    true
+                } else {
+                    false
+                }
+            }
+            AwaitingDependencies::Acknowledgement { acknowledgement } => {
+                if existing_acknowledgements.contains(&acknowledgement) {
+                    true
+                } else if acknowledgement
+                    .0
+                    .payload
+                    .content
+                    .private_event_hashes()
+                    .into_iter()
+                    .all(|event_hash| {
+                        private_event_entries.contains_key(&EntryHashB64::from(event_hash))
+                    })
+                {
+                    create_relaxed(EntryTypes::Acknowledgement(acknowledgement))?;
+                    true
+                } else {
+                    false
+                }
+            }
+            AwaitingDependencies::ReadReceipt { read_receipt } => {
+                let event_hash =
+                    EntryHashB64::from(read_receipt.0.payload.content.private_event_hash.clone());
+                if existing_read_receipts.contains(&read_receipt) {
+                    true
+                } else if let Some(private_event_entry) = private_event_entries.get(&event_hash) {
+                    if is_recipient_for::<T>(
+                        &read_receipt.0.author,
+                        &event_hash,
+                        private_event_entry,
+                        &my_linked_devices,
+                    )? {
+                        create_relaxed(EntryTypes::ReadReceipt(read_receipt))?;
+                    } else {
+                        warn!("Discarding read receipt for event {event_hash}: its author is not a recipient of the event.");
+                    }
+                    true
+                } else {
+                    false
+                }
+            }
+            AwaitingDependencies::Rejection { rejection } => {
+                let event_hash = EntryHashB64::from(rejection.0.payload.content.event_hash.clone());
+                if existing_rejections.contains(&rejection) {
+                    true
+                } else if rejection.0.author.eq(&my_pub_key)
+                    || my_linked_devices.contains(&rejection.0.author)
+                {
+                    create_relaxed(EntryTypes::Rejection(rejection))?;
+                    true
+                } else if let Some(private_event_entry) = private_event_entries.get(&event_hash) {
+                    if is_recipient_for::<T>(
+                        &rejection.0.author,
+                        &event_hash,
+                        private_event_entry,
+                        &my_linked_devices,
+                    )? {
+                        create_relaxed(EntryTypes::Rejection(rejection))?;
+                    } else {
+                        warn!("Discarding rejection for event {event_hash}: its author is not a recipient of the event.");
+                    }
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        if resolved {
+            resolved_actions.extend(record.action_hash);
         }
     }
 
-    let acknowledgements = query_awaiting_deps_acknowledgements()?;
+    // The entries committed from now on are all above the chain head
+    let new_oldest_awaiting_action_seq = awaiting_actions
+        .into_iter()
+        .filter(|(_, action_hash)| {
+            action_hash
+                .as_ref()
+                .is_none_or(|action_hash| !resolved_actions.contains(action_hash))
+        })
+        .map(|(action_seq, _)| action_seq)
+        .min()
+        .unwrap_or(chain_head_seq.saturating_add(1));
+    if new_oldest_awaiting_action_seq > oldest_awaiting_action_seq {
+        create_relaxed(EntryTypes::AwaitingDependenciesCheckpoint(
+            AwaitingDependenciesCheckpoint {
+                oldest_awaiting_action_seq: new_oldest_awaiting_action_seq,
+            },
+        ))?;
+    }
 
-    for acknowledgement in acknowledgements {
-        if private_event_entries.contains_key(&EntryHashB64::from(
-            acknowledgement.0.payload.content.private_event_hash.clone(),
-        )) {
-            create_relaxed(EntryTypes::Acknowledgement(acknowledgement))?;
-        }
+    // Once resolved or expired, the entries awaiting dependencies are not needed anymore
+    for action_hash in resolved_actions {
+        delete_entry(DeleteInput::new(action_hash, ChainTopOrdering::Relaxed))?;
     }
 
     Ok(())
@@ -85,59 +429,214 @@ pub fn query_awaiting_deps_private_event_entries() -> ExternResult<Vec<PrivateEv
     Ok(entries)
 }
 
-pub fn query_awaiting_deps_events_sent_to_recipients() -> ExternResult<Vec<EventSentToRecipients>> {
-    let existing_events_sent_to_recipients = query_events_sent_to_recipients_entries(())?;
+/// The entries awaiting dependencies that haven't been resolved yet, with the dependencies they are still missing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StuckAwaitingDependencies {
+    pub record: AwaitingDependenciesRecord,
+    pub unresolved_dependencies: UnresolvedDependencies,
+    /// After this time the entry will be discarded
+    pub expires_at: Timestamp,
+}
 
-    let awaiting_deps = query_awaiting_deps()?;
+/// Lists the entries that are still awaiting their dependencies
+#[hdk_extern]
+pub fn query_stuck_awaiting_dependencies() -> ExternResult<Vec<StuckAwaitingDependencies>> {
+    let private_event_entries = query_private_event_entries(())?;
+    stuck_awaiting_dependencies(&private_event_entries)
+}
 
-    let events_sent_to_recipients: Vec<EventSentToRecipients> = awaiting_deps
+/// The entries that are still awaiting their dependencies, given the private events in our source chain
+pub fn stuck_awaiting_dependencies(
+    private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+) -> ExternResult<Vec<StuckAwaitingDependencies>> {
+    let now = sys_time()?;
+
+    let stuck = query_awaiting_deps_records()?
         .into_iter()
-        .filter_map(|awaiting_deps| match awaiting_deps {
-            AwaitingDependencies::EventsSentToRecipients {
-                event_sent_to_recipients,
-            } => Some(event_sent_to_recipients),
-            _ => None,
-        })
-        .filter(|events_sent_to_recipients| {
-            !existing_events_sent_to_recipients
-                .iter()
-                .any(|e| e.eq(events_sent_to_recipients))
+        .filter(|record| !record.is_expired(now))
+        .filter_map(|record| {
+            if let AwaitingDependencies::Event { event, .. } = &record.awaiting_dependencies {
+                let Ok(event_hash) = hash_entry(event) else {
+                    return None;
+                };
+                if private_event_entries.contains_key(&EntryHashB64::from(event_hash)) {
+                    // Already committed, it will be deleted in the next scheduled run
+                    return None;
+                }
+            }
+            let unresolved_dependencies =
+                unresolved_dependencies_for(&record.awaiting_dependencies, private_event_entries);
+            if let UnresolvedDependencies::Hashes(hashes) = &unresolved_dependencies {
+                if hashes.is_empty()
+                    && !matches!(
+                        record.awaiting_dependencies,
+                        AwaitingDependencies::Event { .. }
+                    )
+                {
+                    // Resolved, it will be committed in the next scheduled run
+                    return None;
+                }
+            }
+            Some(StuckAwaitingDependencies {
+                expires_at: record.expires_at(),
+                unresolved_dependencies,
+                record,
+            })
         })
         .collect();
 
-    Ok(events_sent_to_recipients)
+    Ok(stuck)
 }
 
-pub fn query_awaiting_deps_acknowledgements() -> ExternResult<Vec<Acknowledgement>> {
-    let existing_acknowledgements = query_acknowledgement_entries(())?;
-
-    let awaiting_deps = query_awaiting_deps()?;
+/// The dependencies that the given entry is still missing
+fn unresolved_dependencies_for(
+    awaiting_dependencies: &AwaitingDependencies,
+    private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+) -> UnresolvedDependencies {
+    let missing_events = |events_hashes: Vec<EntryHash>| {
+        UnresolvedDependencies::Hashes(
+            events_hashes
+                .into_iter()
+                .filter(|event_hash| {
+                    !private_event_entries.contains_key(&EntryHashB64::from(event_hash.clone()))
+                })
+                .map(AnyDhtHash::from)
+                .collect(),
+        )
+    };
+    match awaiting_dependencies {
+        AwaitingDependencies::Event {
+            event,
+            unresolved_dependencies,
+            ..
+        } => {
+            let missing_previous_events = missing_previous_events(event, private_event_entries);
+            if missing_previous_events.is_empty() {
+                // Waiting for the dependencies reported by the validation of the event
+                unresolved_dependencies.clone()
+            } else {
+                missing_events(missing_previous_events)
+            }
+        }
+        AwaitingDependencies::EventsSentToRecipients {
+            event_sent_to_recipients,
+        } => missing_events(vec![event_sent_to_recipients
+            .0
+            .payload
+            .content
+            .event_hash
+            .clone()]),
+        AwaitingDependencies::Acknowledgement { acknowledgement } => missing_events(
+            acknowledgement
+                .0
+                .payload
+                .content
+                .private_event_hashes()
+                .into_iter()
+                .collect(),
+        ),
+        AwaitingDependencies::ReadReceipt { read_receipt } => missing_events(vec![read_receipt
+            .0
+            .payload
+            .content
+            .private_event_hash
+            .clone()]),
+        AwaitingDependencies::Rejection { rejection } => {
+            missing_events(vec![rejection.0.payload.content.event_hash.clone()])
+        }
+    }
+}
 
-    let acknowledgements: Vec<Acknowledgement> = awaiting_deps
+pub fn query_awaiting_deps() -> ExternResult<Vec<AwaitingDependencies>> {
+    let now = sys_time()?;
+    Ok(query_awaiting_deps_records()?
         .into_iter()
-        .filter_map(|awaiting_deps| match awaiting_deps {
-            AwaitingDependencies::Acknowledgement { acknowledgement } => Some(acknowledgement),
-            _ => None,
-        })
-        .filter(|acknowledgement| {
-            !existing_acknowledgements
-                .iter()
-                .any(|a| a.eq(acknowledgement))
-        })
-        .collect();
+        .filter(|record| !record.is_expired(now))
+        .map(|record| record.awaiting_dependencies)
+        .collect())
+}
+
+/// Number of actions at the top of our source chain in which the latest AwaitingDependenciesCheckpoint is looked for at first
+const AWAITING_DEPENDENCIES_CHECKPOINT_WINDOW: u32 = 100;
 
-    Ok(acknowledgements)
+/// The action sequence from which on the entries awaiting dependencies that haven't been deleted were committed,
+/// recorded by the latest AwaitingDependenciesCheckpoint
+///
+/// The actions at the top of the source chain are queried in windows that double in size until a checkpoint is found
+fn query_oldest_awaiting_action_seq() -> ExternResult<u32> {
+    let chain_head_seq = agent_info()?.chain_head.1;
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::AwaitingDependenciesCheckpoint.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+
+    let mut window = AWAITING_DEPENDENCIES_CHECKPOINT_WINDOW;
+    loop {
+        let first_action_seq = chain_head_seq.saturating_sub(window);
+        let records = query(
+            filter
+                .clone()
+                .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+                    first_action_seq,
+                    u32::MAX,
+                )),
+        )?;
+        let latest_checkpoint = records.iter().rev().find_map(|record| {
+            record
+                .entry()
+                .as_option()
+                .and_then(|entry| AwaitingDependenciesCheckpoint::try_from(entry).ok())
+        });
+
+        if let Some(checkpoint) = latest_checkpoint {
+            return Ok(checkpoint.oldest_awaiting_action_seq);
+        }
+        if first_action_seq == 0 {
+            return Ok(0);
+        }
+        window = window.saturating_mul(2);
+    }
 }
 
-pub fn query_awaiting_deps() -> ExternResult<Vec<AwaitingDependencies>> {
+/// All the entries awaiting dependencies that haven't been deleted, including the ones inside imported event histories
+///
+/// Only the actions from the latest checkpoint on are queried, since all the older entries have been deleted
+/// or were inside event histories whose entries had all expired
+///
+/// Entries that outlived their TTL, for example because the scheduled task didn't run while we were offline,
+/// are returned too so that they are discarded as expired
+pub fn query_awaiting_deps_records() -> ExternResult<Vec<AwaitingDependenciesRecord>> {
+    Ok(
+        query_awaiting_deps_records_since(query_oldest_awaiting_action_seq()?)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .collect(),
+    )
+}
+
+/// The entries awaiting dependencies committed from the given action sequence on that haven't been deleted,
+/// with the action sequence of the action that committed them or of the EventHistory that contains them
+fn query_awaiting_deps_records_since(
+    first_action_seq: u32,
+) -> ExternResult<Vec<(u32, AwaitingDependenciesRecord)>> {
     let filter = ChainQueryFilter::new()
         .entry_type(UnitEntryTypes::AwaitingDependencies.try_into()?)
         .include_entries(true)
-        .action_type(ActionType::Create);
-    let create_records = query(filter)?;
+        .action_type(ActionType::Create)
+        .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+            first_action_seq,
+            u32::MAX,
+        ));
+    let create_records: Vec<Record> = query(filter)?;
 
-    let mut awaiting_dependencies: Vec<AwaitingDependencies> = create_records
+    let deleted_actions: BTreeSet<ActionHash> = match create_records.first() {
+        Some(first_record) => query_deleted_actions_since(first_record.action().action_seq())?,
+        None => BTreeSet::new(),
+    };
+
+    let mut awaiting_dependencies: Vec<(u32, AwaitingDependenciesRecord)> = create_records
         .into_iter()
+        .filter(|record| !deleted_actions.contains(record.action_address()))
         .filter_map(|record| {
             let Some(entry) = record.entry.as_option() else {
                 return None;
@@ -145,14 +644,44 @@ pub fn query_awaiting_deps() -> ExternResult<Vec<AwaitingDependencies>> {
             let Ok(awaiting_deps) = AwaitingDependencies::try_from(entry) else {
                 return None;
             };
-            Some(awaiting_deps)
+            Some((
+                record.action().action_seq(),
+                AwaitingDependenciesRecord {
+                    action_hash: Some(record.action_address().clone()),
+                    timestamp: record.action().timestamp(),
+                    awaiting_dependencies: awaiting_deps,
+                },
+            ))
         })
         .collect();
 
-    let mut histories = query_event_histories()?;
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::EventHistory.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create)
+        .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+            first_action_seq,
+            u32::MAX,
+        ));
+    let history_records = query(filter)?;
 
-    for history in &mut histories {
-        awaiting_dependencies.append(&mut history.awaiting_deps);
+    for record in history_records {
+        let Some(entry) = record.entry.as_option() else {
+            continue;
+        };
+        let history = EventHistory::try_from(entry)?;
+        let action_seq = record.action().action_seq();
+        let timestamp = record.action().timestamp();
+        awaiting_dependencies.extend(history.awaiting_deps.into_iter().map(|awaiting_deps| {
+            (
+                action_seq,
+                AwaitingDependenciesRecord {
+                    action_hash: None,
+                    timestamp,
+                    awaiting_dependencies: awaiting_deps,
+                },
+            )
+        }));
     }
 
     Ok(awaiting_dependencies)
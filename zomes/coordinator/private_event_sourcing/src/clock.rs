@@ -0,0 +1,232 @@
+use hdk::prelude::*;
+use private_event_sourcing_integrity::*;
+use serde::de::DeserializeOwned;
+
+const DEFAULT_MAX_CLOCK_SKEW_MS: i64 = 5 * 60 * 1000; // 5 minutes
+
+/// Maximum time in microseconds that the clock of a received entry can be ahead of our own clock
+///
+/// Configurable at build time with the MAX_CLOCK_SKEW_MS environment variable, ignoring negative values
+pub fn max_clock_skew() -> i64 {
+    std::option_env!("MAX_CLOCK_SKEW_MS")
+        .and_then(|skew| skew.parse::<i64>().ok())
+        .filter(|skew| *skew >= 0)
+        .unwrap_or(DEFAULT_MAX_CLOCK_SKEW_MS)
+        .saturating_mul(1000)
+}
+
+/// The latest time that the clock of a received entry can have right now
+fn max_valid_time() -> ExternResult<Timestamp> {
+    let now = sys_time()?;
+    Ok(Timestamp::from_micros(
+        now.as_micros().saturating_add(max_clock_skew()),
+    ))
+}
+
+/// Checks that the clock of the given content is well formed, and that its timestamp is not further in the future
+/// than the allowed clock skew
+///
+/// Returns the reason why the clock is invalid otherwise
+pub fn validate_clock<T>(content: &SignedContent<T>) -> ExternResult<Result<(), String>> {
+    if let Some(hlc) = &content.hlc {
+        if hlc.physical.ne(&content.timestamp) {
+            return Ok(Err(format!(
+                "The physical time of clock {:?} does not match the timestamp {:?}.",
+                hlc, content.timestamp
+            )));
+        }
+        if hlc.logical > MAX_LOGICAL_CLOCK {
+            return Ok(Err(format!(
+                "The logical counter of clock {:?} is greater than the maximum {}.",
+                hlc, MAX_LOGICAL_CLOCK
+            )));
+        }
+    }
+
+    let max_valid_time = max_valid_time()?;
+    if content.timestamp > max_valid_time {
+        return Ok(Err(format!(
+            "Timestamp {:?} is too far in the future: the maximum valid timestamp is {:?}.",
+            content.timestamp, max_valid_time
+        )));
+    }
+
+    Ok(Ok(()))
+}
+
+/// Number of actions at the top of our source chain in which the latest clock is looked for at first
+const LATEST_CLOCK_WINDOW: u32 = 100;
+
+/// The latest clock among all the signed entries committed to our source chain
+///
+/// Every entry that we sign has a clock ahead of the entries committed before it, so only the entries
+/// from our latest signed entry on are queried: the actions at the top of the source chain are queried
+/// in windows that double in size until one of our signed entries is found
+///
+/// Received entries are committed to our source chain, so this clock advances with every message we receive
+pub fn query_latest_clock() -> ExternResult<Option<HybridLogicalClock>> {
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+    let chain_head_seq = agent_info()?.chain_head.1;
+
+    let mut filter = ChainQueryFilter::new()
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    for entry_type in [
+        UnitEntryTypes::PrivateEvent,
+        UnitEntryTypes::EventHistory,
+        UnitEntryTypes::Acknowledgement,
+        UnitEntryTypes::EventSentToRecipients,
+        UnitEntryTypes::ReadReceipt,
+        UnitEntryTypes::Rejection,
+    ] {
+        filter = filter.entry_type(entry_type.try_into()?);
+    }
+
+    let mut window = LATEST_CLOCK_WINDOW;
+    loop {
+        let first_action_seq = chain_head_seq.saturating_sub(window);
+        let records = query(
+            filter
+                .clone()
+                .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+                    first_action_seq,
+                    u32::MAX,
+                )),
+        )?;
+
+        let mut latest: Option<HybridLogicalClock> = None;
+        let mut found_my_entry = false;
+        for record in records {
+            for (author, clock) in signed_entry_clocks(&record)? {
+                found_my_entry |= author.as_ref().eq(&Some(&my_pub_key));
+                if latest.as_ref().is_none_or(|latest| clock.gt(latest)) {
+                    latest = Some(clock);
+                }
+            }
+        }
+
+        if found_my_entry || first_action_seq == 0 {
+            return Ok(latest);
+        }
+        window = window.saturating_mul(2);
+    }
+}
+
+/// The clocks of the signed entries in the given record, with their authors
+///
+/// The entries inside an imported EventHistory have no author, since they were not signed
+/// after the entries committed before them
+fn signed_entry_clocks(
+    record: &Record,
+) -> ExternResult<Vec<(Option<AgentPubKey>, HybridLogicalClock)>> {
+    let (Some(EntryType::App(app_entry_def)), Some(entry)) =
+        (record.action().entry_type(), record.entry().as_option())
+    else {
+        return Ok(vec![]);
+    };
+    let Some(app_entry) = EntryTypes::deserialize_from_type(
+        app_entry_def.zome_index,
+        app_entry_def.entry_index,
+        entry,
+    )?
+    else {
+        return Ok(vec![]);
+    };
+
+    let clocks = match app_entry {
+        EntryTypes::PrivateEvent(private_event_entry) => vec![(
+            Some(private_event_entry.0.author),
+            private_event_entry.0.payload.clock(),
+        )],
+        EntryTypes::EventHistory(event_history) => event_history
+            .events
+            .values()
+            .map(|e| e.0.payload.clock())
+            .chain(
+                event_history
+                    .acknowledgements
+                    .iter()
+                    .map(|a| a.0.payload.clock()),
+            )
+            .chain(
+                event_history
+                    .events_sent_to_recipients
+                    .iter()
+                    .map(|e| e.0.payload.clock()),
+            )
+            .chain(
+                event_history
+                    .read_receipts
+                    .iter()
+                    .map(|r| r.0.payload.clock()),
+            )
+            .chain(event_history.rejections.iter().map(|r| r.0.payload.clock()))
+            .map(|clock| (None, clock))
+            .collect(),
+        EntryTypes::Acknowledgement(acknowledgement) => vec![(
+            Some(acknowledgement.0.author),
+            acknowledgement.0.payload.clock(),
+        )],
+        EntryTypes::EventSentToRecipients(event_sent_to_recipients) => vec![(
+            Some(event_sent_to_recipients.0.author),
+            event_sent_to_recipients.0.payload.clock(),
+        )],
+        EntryTypes::ReadReceipt(read_receipt) => {
+            vec![(Some(read_receipt.0.author), read_receipt.0.payload.clock())]
+        }
+        EntryTypes::Rejection(rejection) => {
+            vec![(Some(rejection.0.author), rejection.0.payload.clock())]
+        }
+        _ => vec![],
+    };
+
+    Ok(clocks)
+}
+
+/// Our hybrid logical clock during a zome call
+///
+/// It's read from the source chain once and then ticked locally for every signed entry that we build,
+/// so that building many entries in the same zome call doesn't query the source chain for each of them
+pub struct ClockTicker {
+    latest: Option<HybridLogicalClock>,
+}
+
+impl ClockTicker {
+    pub fn new() -> ExternResult<Self> {
+        Ok(Self::from_latest_clock(query_latest_clock()?))
+    }
+
+    pub fn from_latest_clock(latest: Option<HybridLogicalClock>) -> Self {
+        ClockTicker { latest }
+    }
+
+    /// Advances our clock past the clock of a received entry
+    ///
+    /// Our clock never goes further ahead of our own time than the allowed clock skew,
+    /// so that our next entries are still valid for their recipients
+    pub fn observe(&mut self, clock: HybridLogicalClock) -> ExternResult<()> {
+        let max_valid_time = max_valid_time()?;
+        let clock = if clock.physical > max_valid_time {
+            HybridLogicalClock {
+                physical: max_valid_time,
+                logical: 0,
+            }
+        } else {
+            clock
+        };
+        if self.latest.as_ref().is_none_or(|latest| clock.gt(latest)) {
+            self.latest = Some(clock);
+        }
+        Ok(())
+    }
+
+    /// Builds a signed entry with the next tick of our hybrid logical clock
+    pub fn build_signed_entry<T>(&mut self, content: T) -> ExternResult<SignedEntry<T>>
+    where
+        T: Clone + std::fmt::Debug + Serialize + DeserializeOwned,
+    {
+        let signed_entry = SignedEntry::build_with_clock(content, self.latest.clone())?;
+        self.latest = signed_entry.payload.hlc.clone();
+        Ok(signed_entry)
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use hdk::prelude::*;
+
+use crate::{
+    compute_acknowledgements_by_agents, compute_rejections_by_agents, decode_private_event,
+    events_sent_to_recipients::{
+        compute_events_sent_to_recipients, query_events_sent_to_recipients_entries,
+    },
+    query_acknowledgement_entries, query_my_linked_devices, query_private_event_entry,
+    query_rejection_entries, PrivateEvent,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum DeliveryStatus {
+    /// The event hasn't been sent to the recipient yet
+    Pending,
+    /// The event has been sent to the recipient, but they haven't acknowledged it yet
+    Sent { last_sent: Timestamp, attempts: u32 },
+    /// The recipient has acknowledged the event
+    Acknowledged { timestamp: Timestamp },
+    /// The recipient received the event but rejected it as invalid, so it won't be sent to them again
+    Rejected {
+        timestamp: Timestamp,
+        reason: String,
+    },
+    /// All the attempts to send the event allowed by its resend policy have been exhausted without an acknowledgement
+    Failed { last_sent: Timestamp, attempts: u32 },
+}
+
+/// The delivery status of the given event for each of its recipients, including our linked devices
+pub fn get_delivery_status<T: PrivateEvent>(
+    event_hash: EntryHash,
+) -> ExternResult<BTreeMap<AgentPubKeyB64, DeliveryStatus>> {
+    let Some(private_event_entry) = query_private_event_entry(event_hash.clone())? else {
+        return Err(wasm_error!("Private event {} not found.", event_hash));
+    };
+    let private_event = decode_private_event::<T>(&private_event_entry.0.payload.content)?;
+
+    let mut recipients = private_event.recipients(
+        event_hash.clone(),
+        private_event_entry.0.author.clone(),
+        private_event_entry.0.payload.timestamp,
+    )?;
+    recipients.append(&mut query_my_linked_devices()?);
+
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+
+    let events_sent_to_recipients =
+        compute_events_sent_to_recipients(query_events_sent_to_recipients_entries(())?)?;
+    let send_attempts = events_sent_to_recipients
+        .get(&event_hash)
+        .cloned()
+        .unwrap_or_default();
+    let acknowledgements = compute_acknowledgements_by_agents(&query_acknowledgement_entries(())?)?;
+    let rejections = compute_rejections_by_agents(&query_rejection_entries(())?);
+    let resend_policy = private_event.resend_policy();
+
+    let delivery_status = recipients
+        .into_iter()
+        .filter(|recipient| my_pub_key.ne(recipient)) // Filter me out
+        .filter(|recipient| private_event_entry.0.author.ne(recipient)) // Filter authors out
+        .map(|recipient| {
+            let acknowledged = acknowledgements
+                .get(&recipient)
+                .and_then(|acknowledged_events| acknowledged_events.get(&event_hash));
+
+            let rejection = rejections
+                .get(&recipient)
+                .and_then(|rejected_events| rejected_events.get(&event_hash));
+
+            let status = match (acknowledged, rejection, send_attempts.get(&recipient)) {
+                (Some(timestamp), _, _) => DeliveryStatus::Acknowledged {
+                    timestamp: *timestamp,
+                },
+                (None, Some(rejection), _) => DeliveryStatus::Rejected {
+                    timestamp: rejection.0.payload.timestamp,
+                    reason: rejection.0.payload.content.reason.clone(),
+                },
+                (None, None, None) => DeliveryStatus::Pending,
+                (None, None, Some(send_attempts)) if resend_policy.is_exhausted(send_attempts) => {
+                    DeliveryStatus::Failed {
+                        last_sent: send_attempts.last_sent,
+                        attempts: send_attempts.attempts,
+                    }
+                }
+                (None, None, Some(send_attempts)) => DeliveryStatus::Sent {
+                    last_sent: send_attempts.last_sent,
+                    attempts: send_attempts.attempts,
+                },
+            };
+            (recipient.into(), status)
+        })
+        .collect();
+
+    Ok(delivery_status)
+}
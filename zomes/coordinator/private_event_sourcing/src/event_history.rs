@@ -1,10 +1,18 @@
+use std::collections::BTreeMap;
+
 use hdk::prelude::*;
-use private_event_sourcing_integrity::{EntryTypes, EventHistory, UnitEntryTypes};
+use private_event_sourcing_integrity::*;
+use serde::de::DeserializeOwned;
 
 use crate::{
-    acknowledgements::query_acknowledgement_entries, awaiting_dependencies::query_awaiting_deps,
+    acknowledgements::query_acknowledgement_entries,
+    awaiting_dependencies::query_awaiting_deps,
+    causally_order_private_event_entries,
     events_sent_to_recipients::query_events_sent_to_recipients_entries,
-    query_private_event_entries, utils::create_relaxed,
+    missing_previous_events, query_private_event_entries, query_read_receipt_entries,
+    query_rejection_entries,
+    utils::{create_relaxed, query_deleted_actions_since},
+    validate_clock, validate_private_event_entry, AwaitingDependenciesRecord, PrivateEvent,
 };
 
 pub fn query_event_histories() -> ExternResult<Vec<EventHistory>> {
@@ -33,19 +41,777 @@ pub fn export_event_history() -> ExternResult<EventHistory> {
     let awaiting_deps = query_awaiting_deps()?;
     let events_sent_to_recipients = query_events_sent_to_recipients_entries(())?;
     let events = query_private_event_entries(())?;
+    let read_receipts = query_read_receipt_entries(())?;
+    let rejections = query_rejection_entries(())?;
 
     Ok(EventHistory {
         awaiting_deps,
         events,
         events_sent_to_recipients,
         acknowledgements,
+        read_receipts,
+        rejections,
+        chunk: None,
     })
 }
 
-pub fn import_event_history(history: EventHistory) -> ExternResult<()> {
-    // TODO: what to do about validation?
+/// Default maximum size in bytes of an exported page of the event history, the same as for async messages
+pub const DEFAULT_EVENT_HISTORY_PAGE_SIZE_BYTES: u32 = 512 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportEventHistoryPageInput {
+    /// Position of the last entry of the previous page, or None for the first page
+    pub cursor: Option<EventHistoryCursor>,
+    pub max_page_size_bytes: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventHistoryPage {
+    pub history: EventHistory,
+    /// Cursor for the next page, or None if this is the last page
+    pub next_cursor: Option<EventHistoryCursor>,
+}
+
+/// An entry of the event history, to be exported in a page
+enum EventHistoryItem {
+    Event(EntryHashB64, PrivateEventEntry),
+    EventSentToRecipients(EventSentToRecipients),
+    Acknowledgement(Acknowledgement),
+    ReadReceipt(ReadReceipt),
+    Rejection(Rejection),
+    AwaitingDependencies(AwaitingDependencies),
+}
+
+impl EventHistoryItem {
+    fn encoded_size(&self) -> ExternResult<usize> {
+        let bytes = match self {
+            EventHistoryItem::Event(_, private_event_entry) => encode(private_event_entry),
+            EventHistoryItem::EventSentToRecipients(event_sent_to_recipients) => {
+                encode(event_sent_to_recipients)
+            }
+            EventHistoryItem::Acknowledgement(acknowledgement) => encode(acknowledgement),
+            EventHistoryItem::ReadReceipt(read_receipt) => encode(read_receipt),
+            EventHistoryItem::Rejection(rejection) => encode(rejection),
+            EventHistoryItem::AwaitingDependencies(awaiting_dependencies) => {
+                encode(awaiting_dependencies)
+            }
+        }
+        .map_err(|err| wasm_error!(err))?;
+        Ok(bytes.len())
+    }
+}
+
+/// Number of actions of the source chain that are queried at a time while filling a page
+const EXPORT_WINDOW_ACTIONS: u32 = 100;
+
+/// The entries of our event history committed between the given action sequences, both included,
+/// with their position in it, in the order in which they are exported
+///
+/// The entries inside an imported EventHistory are exported in the same order as its own export,
+/// with their private events in causal order
+fn event_history_items_between(
+    first_action_seq: u32,
+    last_action_seq: u32,
+    deleted_actions: &BTreeSet<ActionHash>,
+    now: Timestamp,
+) -> ExternResult<Vec<(EventHistoryCursor, EventHistoryItem)>> {
+    let mut filter = ChainQueryFilter::new()
+        .include_entries(true)
+        .action_type(ActionType::Create)
+        .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+            first_action_seq,
+            last_action_seq,
+        ));
+    for entry_type in [
+        UnitEntryTypes::PrivateEvent,
+        UnitEntryTypes::EventHistory,
+        UnitEntryTypes::EventSentToRecipients,
+        UnitEntryTypes::Acknowledgement,
+        UnitEntryTypes::ReadReceipt,
+        UnitEntryTypes::Rejection,
+        UnitEntryTypes::AwaitingDependencies,
+    ] {
+        filter = filter.entry_type(entry_type.try_into()?);
+    }
+
+    let mut items: Vec<(EventHistoryCursor, EventHistoryItem)> = vec![];
+
+    for record in query(filter)? {
+        let (Some(EntryType::App(app_entry_def)), Some(entry)) =
+            (record.action().entry_type(), record.entry().as_option())
+        else {
+            continue;
+        };
+        let Some(app_entry) = EntryTypes::deserialize_from_type(
+            app_entry_def.zome_index,
+            app_entry_def.entry_index,
+            entry,
+        )?
+        else {
+            continue;
+        };
+        let action_seq = record.action().action_seq();
+        let timestamp = record.action().timestamp();
+        let is_awaiting = |awaiting_dependencies: &AwaitingDependencies| {
+            let awaiting_record = AwaitingDependenciesRecord {
+                action_hash: None,
+                timestamp,
+                awaiting_dependencies: awaiting_dependencies.clone(),
+            };
+            !awaiting_record.is_expired(now)
+        };
+
+        let record_items = match app_entry {
+            EntryTypes::PrivateEvent(private_event_entry) => {
+                let event_hash = EntryHashB64::from(hash_entry(&private_event_entry)?);
+                vec![EventHistoryItem::Event(event_hash, private_event_entry)]
+            }
+            EntryTypes::EventSentToRecipients(event_sent_to_recipients) => {
+                vec![EventHistoryItem::EventSentToRecipients(
+                    event_sent_to_recipients,
+                )]
+            }
+            EntryTypes::Acknowledgement(acknowledgement) => {
+                vec![EventHistoryItem::Acknowledgement(acknowledgement)]
+            }
+            EntryTypes::ReadReceipt(read_receipt) => {
+                vec![EventHistoryItem::ReadReceipt(read_receipt)]
+            }
+            EntryTypes::Rejection(rejection) => vec![EventHistoryItem::Rejection(rejection)],
+            EntryTypes::AwaitingDependencies(awaiting_dependencies) => {
+                if deleted_actions.contains(record.action_address()) {
+                    continue;
+                }
+                vec![EventHistoryItem::AwaitingDependencies(
+                    awaiting_dependencies,
+                )]
+            }
+            EntryTypes::EventHistory(history) => {
+                causally_order_private_event_entries(history.events)
+                    .into_iter()
+                    .map(|(event_hash, private_event_entry)| {
+                        EventHistoryItem::Event(event_hash, private_event_entry)
+                    })
+                    .chain(
+                        history
+                            .events_sent_to_recipients
+                            .into_iter()
+                            .map(EventHistoryItem::EventSentToRecipients),
+                    )
+                    .chain(
+                        history
+                            .acknowledgements
+                            .into_iter()
+                            .map(EventHistoryItem::Acknowledgement),
+                    )
+                    .chain(
+                        history
+                            .read_receipts
+                            .into_iter()
+                            .map(EventHistoryItem::ReadReceipt),
+                    )
+                    .chain(
+                        history
+                            .rejections
+                            .into_iter()
+                            .map(EventHistoryItem::Rejection),
+                    )
+                    .chain(
+                        history
+                            .awaiting_deps
+                            .into_iter()
+                            .map(EventHistoryItem::AwaitingDependencies),
+                    )
+                    .collect()
+            }
+            _ => continue,
+        };
+
+        // Expired entries are skipped after numbering the items of the record,
+        // so that the cursors of the other items don't change when they expire
+        for (index, item) in record_items.into_iter().enumerate() {
+            if let EventHistoryItem::AwaitingDependencies(awaiting_dependencies) = &item {
+                if !is_awaiting(awaiting_dependencies) {
+                    continue;
+                }
+            }
+            items.push((
+                EventHistoryCursor {
+                    action_seq,
+                    index: index as u32,
+                },
+                item,
+            ));
+        }
+    }
+
+    Ok(items)
+}
+
+/// Exports the page of the event history that comes right after the given cursor,
+/// with as many entries as fit in `max_page_size_bytes`
+///
+/// Entries are exported in the order in which they were committed to our source chain, so private events
+/// come after their previous events, and only the actions after the cursor are queried to build each page
+///
+/// The cursor is the position of the last exported entry in the source chain, so the pages stay consistent
+/// even if new entries are committed during the export: they are exported in the last pages.
+/// The same private event may still be exported twice if it's both in an imported EventHistory
+/// and in its own entry, so the importer skips the entries that it already has
+#[hdk_extern]
+pub fn export_event_history_page(
+    input: ExportEventHistoryPageInput,
+) -> ExternResult<EventHistoryPage> {
+    if input.max_page_size_bytes == 0 {
+        return Err(wasm_error!("Page size must be greater than 0."));
+    }
+    let mut history = EventHistory {
+        awaiting_deps: vec![],
+        events: BTreeMap::new(),
+        events_sent_to_recipients: vec![],
+        acknowledgements: vec![],
+        read_receipts: vec![],
+        rejections: vec![],
+        chunk: None,
+    };
 
-    create_relaxed(EntryTypes::EventHistory(history))?;
+    let now = sys_time()?;
+    let chain_head_seq = agent_info()?.chain_head.1;
+    let mut first_action_seq = input.cursor.as_ref().map_or(0, |cursor| cursor.action_seq);
+    // Awaiting entries are always deleted after they are created
+    let deleted_actions = query_deleted_actions_since(first_action_seq)?;
+
+    let mut page_size: usize = 0;
+    let mut last_cursor: Option<EventHistoryCursor> = None;
+    let mut next_cursor: Option<EventHistoryCursor> = None;
+
+    'page: while first_action_seq <= chain_head_seq {
+        let last_action_seq = first_action_seq
+            .saturating_add(EXPORT_WINDOW_ACTIONS - 1)
+            .min(chain_head_seq);
+        let items =
+            event_history_items_between(first_action_seq, last_action_seq, &deleted_actions, now)?;
+
+        for (cursor, item) in items {
+            if input.cursor.as_ref().is_some_and(|c| cursor.le(c)) {
+                continue;
+            }
+            let size = item.encoded_size()?;
+            // A page always includes at least one entry, even if it's bigger than the page size
+            if page_size > 0 && page_size + size > input.max_page_size_bytes as usize {
+                next_cursor = last_cursor;
+                break 'page;
+            }
+            page_size += size;
+            last_cursor = Some(cursor);
+
+            match item {
+                EventHistoryItem::Event(event_hash, private_event_entry) => {
+                    history.events.insert(event_hash, private_event_entry);
+                }
+                EventHistoryItem::EventSentToRecipients(event_sent_to_recipients) => history
+                    .events_sent_to_recipients
+                    .push(event_sent_to_recipients),
+                EventHistoryItem::Acknowledgement(acknowledgement) => {
+                    history.acknowledgements.push(acknowledgement)
+                }
+                EventHistoryItem::ReadReceipt(read_receipt) => {
+                    history.read_receipts.push(read_receipt)
+                }
+                EventHistoryItem::Rejection(rejection) => history.rejections.push(rejection),
+                EventHistoryItem::AwaitingDependencies(awaiting_dependencies) => {
+                    history.awaiting_deps.push(awaiting_dependencies)
+                }
+            }
+        }
+
+        if last_action_seq == chain_head_seq {
+            break;
+        }
+        first_action_seq = last_action_seq + 1;
+    }
+
+    Ok(EventHistoryPage {
+        history,
+        next_cursor,
+    })
+}
+
+/// The outcome of importing or receiving the entries of one type, identified by their entry hash
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ImportedEntries {
+    /// Entries that were valid and had all their dependencies
+    pub accepted: Vec<EntryHashB64>,
+    /// Entries that were added to the awaiting dependencies queue
+    pub deferred: Vec<EntryHashB64>,
+    /// Entries that were discarded, with the reason why
+    pub rejected: BTreeMap<EntryHashB64, String>,
+}
+
+impl ImportedEntries {
+    pub fn append(&mut self, mut other: ImportedEntries) {
+        self.accepted.append(&mut other.accepted);
+        self.deferred.append(&mut other.deferred);
+        self.rejected.append(&mut other.rejected);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ImportEventHistoryReport {
+    pub events: ImportedEntries,
+    pub events_sent_to_recipients: ImportedEntries,
+    pub acknowledgements: ImportedEntries,
+    pub read_receipts: ImportedEntries,
+    pub rejections: ImportedEntries,
+}
+
+impl ImportEventHistoryReport {
+    /// Merges the report of another chunk of the same import into this one
+    pub fn append(&mut self, other: ImportEventHistoryReport) {
+        self.events.append(other.events);
+        self.events_sent_to_recipients
+            .append(other.events_sent_to_recipients);
+        self.acknowledgements.append(other.acknowledgements);
+        self.read_receipts.append(other.read_receipts);
+        self.rejections.append(other.rejections);
+    }
+}
+
+/// Validates every entry in the given history, and imports the ones that are valid
+///
+/// Entries that are already in our source chain are skipped, and entries with unmet dependencies
+/// are imported in the awaiting dependencies queue of the history
+pub fn import_event_history<T: PrivateEvent>(
+    history: EventHistory,
+) -> ExternResult<ImportEventHistoryReport> {
+    let existing_entries = ExistingEntries::query(query_event_histories()?)?;
+    import_event_history_chunk::<T>(history, None, existing_entries)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportEventHistoryChunkOutput {
+    pub report: ImportEventHistoryReport,
+    /// Whether all the history of the source cell has been imported
+    pub finished: bool,
+}
+
+/// Imports the next chunk of the event history of the given cell, which must be running the same zome
+///
+/// Each chunk is committed in its own entry, which records the cursor for the next one:
+/// calling this again after an interrupted import resumes it from the last imported chunk
+pub fn import_next_event_history_chunk<T: PrivateEvent>(
+    source_cell: CellId,
+    max_page_size_bytes: u32,
+) -> ExternResult<ImportEventHistoryChunkOutput> {
+    let source_dna_hash = source_cell.dna_hash().clone();
+
+    let histories = query_event_histories()?;
+    let last_chunk = histories
+        .iter()
+        .filter_map(|history| history.chunk.as_ref())
+        .filter(|chunk| chunk.source_dna_hash.eq(&source_dna_hash))
+        .last()
+        .cloned();
+
+    let cursor = match last_chunk {
+        None => None,
+        Some(EventHistoryChunk {
+            next_cursor: Some(next_cursor),
+            ..
+        }) => Some(next_cursor),
+        Some(EventHistoryChunk {
+            next_cursor: None, ..
+        }) => {
+            return Ok(ImportEventHistoryChunkOutput {
+                report: ImportEventHistoryReport::default(),
+                finished: true,
+            });
+        }
+    };
+
+    let response = call(
+        CallTargetCell::OtherCell(source_cell),
+        zome_info()?.name,
+        "export_event_history_page".into(),
+        None,
+        ExportEventHistoryPageInput {
+            cursor,
+            max_page_size_bytes,
+        },
+    )?;
+    let ZomeCallResponse::Ok(result) = response else {
+        return Err(wasm_error!(
+            "Error exporting the event history page: {:?}.",
+            response
+        ));
+    };
+    let page: EventHistoryPage = result.decode().map_err(|err| wasm_error!(err))?;
+
+    let report = import_event_history_chunk::<T>(
+        page.history,
+        Some(EventHistoryChunk {
+            source_dna_hash,
+            next_cursor: page.next_cursor.clone(),
+        }),
+        ExistingEntries::query(histories)?,
+    )?;
+
+    Ok(ImportEventHistoryChunkOutput {
+        report,
+        finished: page.next_cursor.is_none(),
+    })
+}
+
+/// The entries that are already in our source chain, including the ones inside imported event histories
+struct ExistingEntries {
+    private_event_entries: BTreeMap<EntryHashB64, PrivateEventEntry>,
+    events_sent_to_recipients: Vec<EventSentToRecipients>,
+    acknowledgements: Vec<Acknowledgement>,
+    read_receipts: Vec<ReadReceipt>,
+    rejections: Vec<Rejection>,
+}
+
+impl ExistingEntries {
+    /// Queries all the entries committed to our source chain at once, adding the ones inside the given event histories,
+    /// which were already queried by the caller
+    fn query(histories: Vec<EventHistory>) -> ExternResult<Self> {
+        let mut existing_entries = ExistingEntries {
+            private_event_entries: BTreeMap::new(),
+            events_sent_to_recipients: vec![],
+            acknowledgements: vec![],
+            read_receipts: vec![],
+            rejections: vec![],
+        };
+
+        let mut filter = ChainQueryFilter::new()
+            .include_entries(true)
+            .action_type(ActionType::Create);
+        for entry_type in [
+            UnitEntryTypes::PrivateEvent,
+            UnitEntryTypes::EventSentToRecipients,
+            UnitEntryTypes::Acknowledgement,
+            UnitEntryTypes::ReadReceipt,
+            UnitEntryTypes::Rejection,
+        ] {
+            filter = filter.entry_type(entry_type.try_into()?);
+        }
+
+        for record in query(filter)? {
+            let (Some(EntryType::App(app_entry_def)), Some(entry_hash), Some(entry)) = (
+                record.action().entry_type(),
+                record.action().entry_hash(),
+                record.entry().as_option(),
+            ) else {
+                continue;
+            };
+            match EntryTypes::deserialize_from_type(
+                app_entry_def.zome_index,
+                app_entry_def.entry_index,
+                entry,
+            )? {
+                Some(EntryTypes::PrivateEvent(private_event_entry)) => {
+                    existing_entries
+                        .private_event_entries
+                        .insert(entry_hash.clone().into(), private_event_entry);
+                }
+                Some(EntryTypes::EventSentToRecipients(event_sent_to_recipients)) => {
+                    existing_entries
+                        .events_sent_to_recipients
+                        .push(event_sent_to_recipients)
+                }
+                Some(EntryTypes::Acknowledgement(acknowledgement)) => {
+                    existing_entries.acknowledgements.push(acknowledgement)
+                }
+                Some(EntryTypes::ReadReceipt(read_receipt)) => {
+                    existing_entries.read_receipts.push(read_receipt)
+                }
+                Some(EntryTypes::Rejection(rejection)) => {
+                    existing_entries.rejections.push(rejection)
+                }
+                _ => {}
+            }
+        }
+
+        for mut history in histories {
+            existing_entries
+                .private_event_entries
+                .append(&mut history.events);
+            existing_entries
+                .events_sent_to_recipients
+                .append(&mut history.events_sent_to_recipients);
+            existing_entries
+                .acknowledgements
+                .append(&mut history.acknowledgements);
+            existing_entries
+                .read_receipts
+                .append(&mut history.read_receipts);
+            existing_entries.rejections.append(&mut history.rejections);
+        }
+
+        Ok(existing_entries)
+    }
+}
+
+fn import_event_history_chunk<T: PrivateEvent>(
+    history: EventHistory,
+    chunk: Option<EventHistoryChunk>,
+    existing_entries: ExistingEntries,
+) -> ExternResult<ImportEventHistoryReport> {
+    let mut report = ImportEventHistoryReport::default();
+
+    let ExistingEntries {
+        private_event_entries: mut known_private_event_entries,
+        events_sent_to_recipients: existing_events_sent_to_recipients,
+        acknowledgements: existing_acknowledgements,
+        read_receipts: existing_read_receipts,
+        rejections: existing_rejections,
+    } = existing_entries;
+
+    let mut events: BTreeMap<EntryHashB64, PrivateEventEntry> = BTreeMap::new();
+    let mut awaiting_deps: Vec<AwaitingDependencies> = vec![];
+
+    let mut private_event_entries: BTreeMap<EntryHashB64, PrivateEventEntry> = BTreeMap::new();
+    for (event_hash, private_event_entry) in history.events {
+        let actual_event_hash = EntryHashB64::from(hash_entry(&private_event_entry)?);
+        if actual_event_hash.ne(&event_hash) {
+            report.events.rejected.insert(
+                event_hash,
+                format!("The hash of the event does not match its contents."),
+            );
+            continue;
+        }
+        private_event_entries.insert(event_hash, private_event_entry);
+    }
+
+    // Previous events are always processed before the events that reference them
+    for (event_hash, private_event_entry) in
+        causally_order_private_event_entries(private_event_entries)
+    {
+        if known_private_event_entries.contains_key(&event_hash) {
+            continue;
+        }
+
+        let missing_previous_events =
+            missing_previous_events(&private_event_entry, &known_private_event_entries);
+        // Events with an invalid signature are rejected right away instead of waiting for their previous events
+        if !missing_previous_events.is_empty() && private_event_entry.0.verify()? {
+            awaiting_deps.push(AwaitingDependencies::Event {
+                event: private_event_entry,
+                unresolved_dependencies: UnresolvedDependencies::Hashes(
+                    missing_previous_events
+                        .into_iter()
+                        .map(AnyDhtHash::from)
+                        .collect(),
+                ),
+                provenance: None,
+            });
+            report.events.deferred.push(event_hash);
+            continue;
+        }
+
+        match validate_private_event_entry::<T>(&private_event_entry) {
+            Ok(ValidateCallbackResult::Valid) => {
+                known_private_event_entries.insert(event_hash.clone(), private_event_entry.clone());
+                events.insert(event_hash.clone(), private_event_entry);
+                report.events.accepted.push(event_hash);
+            }
+            Ok(ValidateCallbackResult::Invalid(reason)) => {
+                report.events.rejected.insert(event_hash, reason);
+            }
+            Ok(ValidateCallbackResult::UnresolvedDependencies(unresolved_dependencies)) => {
+                awaiting_deps.push(AwaitingDependencies::Event {
+                    event: private_event_entry,
+                    unresolved_dependencies,
+                    provenance: None,
+                });
+                report.events.deferred.push(event_hash);
+            }
+            Err(err) => {
+                warn!("Failed to validate imported PrivateEvent {event_hash}: {err:?}.");
+                report
+                    .events
+                    .rejected
+                    .insert(event_hash, format!("Failed to validate: {err:?}"));
+            }
+        }
+    }
+
+    let mut events_sent_to_recipients: Vec<EventSentToRecipients> = vec![];
+    for event_sent_to_recipients in history.events_sent_to_recipients {
+        if existing_events_sent_to_recipients.contains(&event_sent_to_recipients) {
+            continue;
+        }
+        let entry_hash = EntryHashB64::from(hash_entry(&event_sent_to_recipients)?);
+        if let Err(reason) = validate_signed_content(&event_sent_to_recipients.0)? {
+            report
+                .events_sent_to_recipients
+                .rejected
+                .insert(entry_hash, reason);
+            continue;
+        }
+
+        let event_hash = EntryHashB64::from(
+            event_sent_to_recipients
+                .0
+                .payload
+                .content
+                .event_hash
+                .clone(),
+        );
+        if known_private_event_entries.contains_key(&event_hash) {
+            events_sent_to_recipients.push(event_sent_to_recipients);
+            report.events_sent_to_recipients.accepted.push(entry_hash);
+        } else {
+            awaiting_deps.push(AwaitingDependencies::EventsSentToRecipients {
+                event_sent_to_recipients,
+            });
+            report.events_sent_to_recipients.deferred.push(entry_hash);
+        }
+    }
+
+    let mut acknowledgements: Vec<Acknowledgement> = vec![];
+    for acknowledgement in history.acknowledgements {
+        if existing_acknowledgements.contains(&acknowledgement) {
+            continue;
+        }
+        let entry_hash = EntryHashB64::from(hash_entry(&acknowledgement)?);
+        if let Err(reason) = validate_signed_content(&acknowledgement.0)? {
+            report.acknowledgements.rejected.insert(entry_hash, reason);
+            continue;
+        }
+
+        let all_events_known = acknowledgement
+            .0
+            .payload
+            .content
+            .private_event_hashes()
+            .into_iter()
+            .all(|event_hash| {
+                known_private_event_entries.contains_key(&EntryHashB64::from(event_hash))
+            });
+        if all_events_known {
+            acknowledgements.push(acknowledgement);
+            report.acknowledgements.accepted.push(entry_hash);
+        } else {
+            awaiting_deps.push(AwaitingDependencies::Acknowledgement { acknowledgement });
+            report.acknowledgements.deferred.push(entry_hash);
+        }
+    }
+
+    let mut read_receipts: Vec<ReadReceipt> = vec![];
+    for read_receipt in history.read_receipts {
+        if existing_read_receipts.contains(&read_receipt) {
+            continue;
+        }
+        let entry_hash = EntryHashB64::from(hash_entry(&read_receipt)?);
+        if let Err(reason) = validate_signed_content(&read_receipt.0)? {
+            report.read_receipts.rejected.insert(entry_hash, reason);
+            continue;
+        }
+
+        let event_hash =
+            EntryHashB64::from(read_receipt.0.payload.content.private_event_hash.clone());
+        if known_private_event_entries.contains_key(&event_hash) {
+            read_receipts.push(read_receipt);
+            report.read_receipts.accepted.push(entry_hash);
+        } else {
+            awaiting_deps.push(AwaitingDependencies::ReadReceipt { read_receipt });
+            report.read_receipts.deferred.push(entry_hash);
+        }
+    }
+
+    // Rejections don't depend on the rejected events, which were never committed
+    let mut rejections: Vec<Rejection> = vec![];
+    for rejection in history.rejections {
+        if existing_rejections.contains(&rejection) {
+            continue;
+        }
+        let entry_hash = EntryHashB64::from(hash_entry(&rejection)?);
+        match validate_signed_content(&rejection.0)? {
+            Ok(()) => {
+                rejections.push(rejection);
+                report.rejections.accepted.push(entry_hash);
+            }
+            Err(reason) => {
+                report.rejections.rejected.insert(entry_hash, reason);
+            }
+        }
+    }
+
+    // Entries that were already awaiting dependencies in the exported history keep waiting for them
+    for awaiting_dependencies in history.awaiting_deps {
+        let (entry_hash, outcome, imported_entries) = match &awaiting_dependencies {
+            AwaitingDependencies::Event { event, .. } => (
+                hash_entry(event)?,
+                validate_signed_content(&event.0)?,
+                &mut report.events,
+            ),
+            AwaitingDependencies::EventsSentToRecipients {
+                event_sent_to_recipients,
+            } => (
+                hash_entry(event_sent_to_recipients)?,
+                validate_signed_content(&event_sent_to_recipients.0)?,
+                &mut report.events_sent_to_recipients,
+            ),
+            AwaitingDependencies::Acknowledgement { acknowledgement } => (
+                hash_entry(acknowledgement)?,
+                validate_signed_content(&acknowledgement.0)?,
+                &mut report.acknowledgements,
+            ),
+            AwaitingDependencies::ReadReceipt { read_receipt } => (
+                hash_entry(read_receipt)?,
+                validate_signed_content(&read_receipt.0)?,
+                &mut report.read_receipts,
+            ),
+            AwaitingDependencies::Rejection { rejection } => (
+                hash_entry(rejection)?,
+                validate_signed_content(&rejection.0)?,
+                &mut report.rejections,
+            ),
+        };
+        match outcome {
+            Err(reason) => {
+                imported_entries.rejected.insert(entry_hash.into(), reason);
+            }
+            Ok(()) => {
+                imported_entries.deferred.push(entry_hash.into());
+                awaiting_deps.push(awaiting_dependencies);
+            }
+        }
+    }
+
+    // Chunks are always committed, to record the progress of the import
+    if chunk.is_none()
+        && events.is_empty()
+        && awaiting_deps.is_empty()
+        && events_sent_to_recipients.is_empty()
+        && acknowledgements.is_empty()
+        && read_receipts.is_empty()
+        && rejections.is_empty()
+    {
+        return Ok(report);
+    }
+
+    create_relaxed(EntryTypes::EventHistory(EventHistory {
+        awaiting_deps,
+        events,
+        events_sent_to_recipients,
+        acknowledgements,
+        read_receipts,
+        rejections,
+        chunk,
+    }))?;
+
+    Ok(report)
+}
 
-    Ok(())
+/// Checks the signature and the clock of the given signed entry, returning the reason why it's invalid otherwise
+fn validate_signed_content<T>(signed_entry: &SignedEntry<T>) -> ExternResult<Result<(), String>>
+where
+    T: Clone + std::fmt::Debug + Serialize + DeserializeOwned,
+{
+    if !signed_entry.verify()? {
+        return Ok(Err(String::from("Invalid signature.")));
+    }
+    validate_clock(&signed_entry.payload)
 }
@@ -3,73 +3,106 @@ use std::collections::BTreeMap;
 use hdk::prelude::*;
 use private_event_sourcing_integrity::*;
 
-use crate::{query_event_histories, utils::create_relaxed, PrivateEvent};
+use crate::{
+    query_event_histories, utils::create_relaxed, validate_clock, ImportedEntries, PrivateEvent,
+    ReceivedEntryOutcome,
+};
 
 pub fn receive_events_sent_to_recipients<T: PrivateEvent>(
     current_events: &BTreeMap<EntryHashB64, PrivateEventEntry>,
     _provenance: AgentPubKey,
     events_sent_to_recipients: Vec<EventSentToRecipients>,
+    report: &mut ImportedEntries,
 ) -> ExternResult<()> {
     let current_events_sent_to_recipients = query_events_sent_to_recipients_entries(())?;
 
     for event_sent_to_recipients in events_sent_to_recipients {
-        if current_events_sent_to_recipients
-            .iter()
-            .find(|a| a.eq(&&event_sent_to_recipients))
-            .is_some()
-        {
-            // We already have this event_sent_to_recipients committed, nothing to do
-            continue;
-        }
+        let entry_hash = EntryHashB64::from(hash_entry(&event_sent_to_recipients)?);
+        let outcome = receive_event_sent_to_recipients(
+            current_events,
+            &current_events_sent_to_recipients,
+            event_sent_to_recipients,
+        );
+        report.record(entry_hash, outcome);
+    }
 
-        let valid = event_sent_to_recipients.0.verify()?;
+    Ok(())
+}
 
-        if !valid {
-            return Err(wasm_error!(
-                "Invalid event_sent_to_recipients: invalid signature."
-            ));
-        }
+fn receive_event_sent_to_recipients(
+    current_events: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    current_events_sent_to_recipients: &Vec<EventSentToRecipients>,
+    event_sent_to_recipients: EventSentToRecipients,
+) -> ExternResult<ReceivedEntryOutcome> {
+    if current_events_sent_to_recipients.contains(&event_sent_to_recipients) {
+        // We already have this event_sent_to_recipients committed, nothing to do
+        return Ok(ReceivedEntryOutcome::AlreadyKnown);
+    }
 
-        if current_events.contains_key(&EntryHashB64::from(
-            event_sent_to_recipients
-                .0
-                .payload
-                .content
-                .event_hash
-                .clone(),
-        )) {
-            create_relaxed(EntryTypes::EventSentToRecipients(event_sent_to_recipients))?;
-        } else {
-            create_relaxed(EntryTypes::AwaitingDependencies(
-                AwaitingDependencies::EventsSentToRecipients {
-                    event_sent_to_recipients,
-                },
-            ))?;
-        }
+    if !event_sent_to_recipients.0.verify()? {
+        return Ok(ReceivedEntryOutcome::Invalid(String::from(
+            "Invalid event_sent_to_recipients: invalid signature.",
+        )));
     }
 
-    Ok(())
+    if let Err(reason) = validate_clock(&event_sent_to_recipients.0.payload)? {
+        return Ok(ReceivedEntryOutcome::Invalid(format!(
+            "Invalid event_sent_to_recipients: {reason}"
+        )));
+    }
+
+    if current_events.contains_key(&EntryHashB64::from(
+        event_sent_to_recipients
+            .0
+            .payload
+            .content
+            .event_hash
+            .clone(),
+    )) {
+        create_relaxed(EntryTypes::EventSentToRecipients(event_sent_to_recipients))?;
+        Ok(ReceivedEntryOutcome::Accepted)
+    } else {
+        create_relaxed(EntryTypes::AwaitingDependencies(
+            AwaitingDependencies::EventsSentToRecipients {
+                event_sent_to_recipients,
+            },
+        ))?;
+        Ok(ReceivedEntryOutcome::Deferred)
+    }
+}
+
+/// The times an event has been sent to a recipient
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SendAttempts {
+    pub last_sent: Timestamp,
+    pub attempts: u32,
 }
 
 pub fn compute_events_sent_to_recipients(
     mut events_sent_to_recipients: Vec<EventSentToRecipients>,
-) -> ExternResult<BTreeMap<EntryHash, BTreeMap<AgentPubKey, Timestamp>>> {
+) -> ExternResult<BTreeMap<EntryHash, BTreeMap<AgentPubKey, SendAttempts>>> {
     events_sent_to_recipients.sort_by_key(|e| e.0.payload.timestamp.clone());
 
-    let mut all_events: BTreeMap<EntryHash, BTreeMap<AgentPubKey, Timestamp>> = BTreeMap::new();
+    let mut all_events: BTreeMap<EntryHash, BTreeMap<AgentPubKey, SendAttempts>> = BTreeMap::new();
 
     for event_sent_to_recipients in events_sent_to_recipients {
-        let mut agents_with_last_sent: BTreeMap<AgentPubKey, Timestamp> = BTreeMap::new();
+        let timestamp = event_sent_to_recipients.0.payload.timestamp;
+        let agents_send_attempts = all_events
+            .entry(event_sent_to_recipients.0.payload.content.event_hash)
+            .or_insert(BTreeMap::new());
 
         for agent in event_sent_to_recipients.0.payload.content.recipients {
-            agents_with_last_sent
-                .insert(agent, event_sent_to_recipients.0.payload.timestamp.clone());
+            agents_send_attempts
+                .entry(agent)
+                .and_modify(|send_attempts| {
+                    send_attempts.last_sent = timestamp;
+                    send_attempts.attempts += 1;
+                })
+                .or_insert(SendAttempts {
+                    last_sent: timestamp,
+                    attempts: 1,
+                });
         }
-
-        all_events
-            .entry(event_sent_to_recipients.0.payload.content.event_hash)
-            .or_insert(BTreeMap::new())
-            .append(&mut agents_with_last_sent);
     }
 
     Ok(all_events)
@@ -6,19 +6,42 @@ pub use acknowledgements::*;
 pub use private_event_sourcing_integrity::*;
 
 mod awaiting_dependencies;
-pub use awaiting_dependencies::attempt_commit_awaiting_deps_entries;
+pub use awaiting_dependencies::{
+    attempt_commit_awaiting_deps_entries, awaiting_dependencies_ttl, AwaitingDependenciesRecord,
+    StuckAwaitingDependencies,
+};
 
 mod linked_devices;
 pub use linked_devices::*;
 mod private_event;
 pub use private_event::*;
+mod clock;
+pub use clock::*;
+mod ordering;
+pub use ordering::*;
+mod projection;
+pub use projection::*;
 mod acknowledgements;
+mod read_receipts;
+pub use read_receipts::*;
+mod rejections;
+pub use rejections::*;
+mod quarantine;
+pub use quarantine::*;
+mod missing_dependencies;
+pub use missing_dependencies::{request_events, request_missing_dependencies};
 mod event_history;
 mod utils;
 pub use event_history::*;
+mod delivery_status;
+pub use delivery_status::*;
+mod resend_policy;
+pub use resend_policy::*;
+mod outgoing_messages;
 mod send_events;
 pub use send_events::{resend_events_if_necessary, send_new_events};
 mod events_sent_to_recipients;
+pub use events_sent_to_recipients::SendAttempts;
 
 mod async_message;
 pub use async_message::*;
@@ -26,7 +49,8 @@ pub use async_message::*;
 pub use private_event_proc_macro::*;
 
 pub fn scheduled_tasks<T: PrivateEvent>() -> ExternResult<()> {
-    let entries = query_private_event_entries(())?;
+    let mut entries = query_private_event_entries(())?;
+    let mut clock_ticker = ClockTicker::new()?;
     let events_sent_to_recipients_entries = query_events_sent_to_recipients_entries(())?;
     let acknowledgements_entries = query_acknowledgement_entries(())?;
 
@@ -34,9 +58,18 @@ pub fn scheduled_tasks<T: PrivateEvent>() -> ExternResult<()> {
         &entries,
         &events_sent_to_recipients_entries,
         &acknowledgements_entries,
+        &mut clock_ticker,
     )?;
-    attempt_commit_awaiting_deps_entries::<T>(&entries)?;
-    create_pending_acknowledgements::<T>(&entries, &acknowledgements_entries)?;
+    attempt_commit_awaiting_deps_entries::<T>(
+        &mut entries,
+        &events_sent_to_recipients_entries,
+        &acknowledgements_entries,
+        &mut clock_ticker,
+        None,
+    )?;
+    request_missing_dependencies(&entries)?;
+    create_pending_acknowledgements::<T>(&entries, &acknowledgements_entries, &mut clock_ticker)?;
+    T::persist_projection_snapshots()?;
     Ok(())
 }
 
@@ -73,6 +106,13 @@ pub enum Signal {
         event_hash: EntryHash,
         private_event_entry: PrivateEventEntry,
     },
+    UndecodablePrivateEvent {
+        undecodable_private_event: UndecodablePrivateEvent,
+    },
+    MessageReceived {
+        provenance: AgentPubKey,
+        report: ReceiveMessageReport,
+    },
     EntryCreated {
         action: SignedActionHashed,
         app_entry: EntryTypes,
@@ -91,6 +131,10 @@ pub enum Signal {
 #[derive(Serialize, Deserialize, Debug, SerializedBytes)]
 pub enum PrivateEventSourcingRemoteSignal {
     SendMessage(Message),
+    /// Request to send the given events to the agent that sent this signal
+    RequestEvents(BTreeSet<EntryHash>),
+    /// Answer to `RequestEvents` with the requested events that won't be sent because we are not entitled to receive them
+    EventsUnavailable(BTreeSet<EntryHash>),
 }
 
 pub fn recv_private_events_remote_signal<T: PrivateEvent>(
@@ -99,7 +143,14 @@ pub fn recv_private_events_remote_signal<T: PrivateEvent>(
     let provenance = call_info()?.provenance;
     match signal {
         PrivateEventSourcingRemoteSignal::SendMessage(message) => {
-            receive_message::<T>(provenance, message)
+            receive_message::<T>(provenance, message)?;
+            Ok(())
+        }
+        PrivateEventSourcingRemoteSignal::RequestEvents(events_hashes) => {
+            missing_dependencies::respond_to_events_request::<T>(provenance, events_hashes)
+        }
+        PrivateEventSourcingRemoteSignal::EventsUnavailable(events_hashes) => {
+            missing_dependencies::receive_unavailable_events::<T>(provenance, events_hashes)
         }
     }
 }
@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+
+use hdk::prelude::*;
+use private_event_sourcing_integrity::*;
+
+use crate::{
+    attempt_commit_awaiting_deps_entries, awaiting_dependencies::stuck_awaiting_dependencies,
+    decode_private_event, events_sent_to_recipients::query_events_sent_to_recipients_entries,
+    get_all_agents_for, outgoing_messages::OutgoingMessages, query_acknowledgement_entries,
+    query_my_linked_devices, query_private_event_entries, ClockTicker, PrivateEvent,
+    PrivateEventSourcingRemoteSignal,
+};
+
+/// Asks the given agents to send us the given events
+pub fn request_events(
+    events_hashes: BTreeSet<EntryHash>,
+    agents: BTreeSet<AgentPubKey>,
+) -> ExternResult<()> {
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+    let agents: Vec<AgentPubKey> = agents
+        .into_iter()
+        .filter(|agent| my_pub_key.ne(agent))
+        .collect();
+    if events_hashes.is_empty() || agents.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Requesting events {:?} from agents {:?}.",
+        events_hashes, agents
+    );
+
+    send_remote_signal(
+        SerializedBytes::try_from(PrivateEventSourcingRemoteSignal::RequestEvents(
+            events_hashes,
+        ))
+        .map_err(|err| wasm_error!(err))?,
+        agents,
+    )?;
+
+    Ok(())
+}
+
+/// Requests the events that the entries awaiting dependencies are missing from the authors of those entries
+/// and their linked devices
+pub fn request_missing_dependencies(
+    private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+) -> ExternResult<()> {
+    let stuck = stuck_awaiting_dependencies(private_event_entries)?;
+
+    let mut missing_events_by_author: BTreeMap<AgentPubKey, BTreeSet<EntryHash>> = BTreeMap::new();
+
+    for stuck_entry in stuck {
+        let UnresolvedDependencies::Hashes(hashes) = stuck_entry.unresolved_dependencies else {
+            continue;
+        };
+        let events_hashes: BTreeSet<EntryHash> = hashes
+            .into_iter()
+            .filter_map(|hash| hash.into_entry_hash())
+            .collect();
+        if events_hashes.is_empty() {
+            continue;
+        }
+        missing_events_by_author
+            .entry(awaiting_entry_author(
+                &stuck_entry.record.awaiting_dependencies,
+            ))
+            .or_default()
+            .extend(events_hashes);
+    }
+
+    for (author, events_hashes) in missing_events_by_author {
+        let agents = get_all_agents_for(author)?;
+        request_events(events_hashes, agents)?;
+    }
+
+    Ok(())
+}
+
+fn awaiting_entry_author(awaiting_dependencies: &AwaitingDependencies) -> AgentPubKey {
+    match awaiting_dependencies {
+        AwaitingDependencies::Event { event, .. } => event.0.author.clone(),
+        AwaitingDependencies::Acknowledgement { acknowledgement } => {
+            acknowledgement.0.author.clone()
+        }
+        AwaitingDependencies::EventsSentToRecipients {
+            event_sent_to_recipients,
+        } => event_sent_to_recipients.0.author.clone(),
+        AwaitingDependencies::ReadReceipt { read_receipt } => read_receipt.0.author.clone(),
+        AwaitingDependencies::Rejection { rejection } => rejection.0.author.clone(),
+    }
+}
+
+/// Sends the requested events that we have to the requester, but only those that the requester is supposed to receive
+///
+/// The events are only sent with remote signals, so that requests from other agents never make us commit entries
+pub fn respond_to_events_request<T: PrivateEvent>(
+    requester: AgentPubKey,
+    events_hashes: BTreeSet<EntryHash>,
+) -> ExternResult<()> {
+    let private_event_entries = query_private_event_entries(())?;
+    let my_linked_devices = query_my_linked_devices()?;
+
+    let mut outgoing_messages = OutgoingMessages::default();
+    // Events that the requester is not entitled to, so that it stops waiting for them
+    let mut unavailable_events: BTreeSet<EntryHash> = BTreeSet::new();
+
+    for event_hash in events_hashes {
+        let event_hash = EntryHashB64::from(event_hash);
+        let Some(private_event_entry) = private_event_entries.get(&event_hash) else {
+            continue;
+        };
+        match is_recipient_for::<T>(
+            &requester,
+            &event_hash,
+            private_event_entry,
+            &my_linked_devices,
+        ) {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!(
+                    "Agent {} requested the event {} but is not one of its recipients.",
+                    requester, event_hash
+                );
+                unavailable_events.insert(event_hash.into());
+                continue;
+            }
+            Err(err) => {
+                warn!(
+                    "Agent {} requested the event {} but its recipients could not be computed: {:?}.",
+                    requester, event_hash, err
+                );
+                unavailable_events.insert(event_hash.into());
+                continue;
+            }
+        }
+        outgoing_messages.add_private_event(&requester, &event_hash, private_event_entry);
+    }
+
+    outgoing_messages.send_by_remote_signal()?;
+
+    if !unavailable_events.is_empty() {
+        send_remote_signal(
+            SerializedBytes::try_from(PrivateEventSourcingRemoteSignal::EventsUnavailable(
+                unavailable_events,
+            ))
+            .map_err(|err| wasm_error!(err))?,
+            vec![requester],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Previous events that an agent told us that we are not entitled to receive
+pub struct UnavailableEvents {
+    pub agent: AgentPubKey,
+    pub events_hashes: BTreeSet<EntryHash>,
+}
+
+/// Stops waiting for the given events, which the given agent won't send us because we are not entitled to receive them
+///
+/// The awaiting events whose only missing previous events are these are committed without them
+pub fn receive_unavailable_events<T: PrivateEvent>(
+    provenance: AgentPubKey,
+    events_hashes: BTreeSet<EntryHash>,
+) -> ExternResult<()> {
+    info!(
+        "Agent {} won't send us the events {:?}: we are not entitled to receive them.",
+        provenance, events_hashes
+    );
+    let mut private_event_entries = query_private_event_entries(())?;
+    let mut clock_ticker = ClockTicker::new()?;
+
+    attempt_commit_awaiting_deps_entries::<T>(
+        &mut private_event_entries,
+        &query_events_sent_to_recipients_entries(())?,
+        &query_acknowledgement_entries(())?,
+        &mut clock_ticker,
+        Some(&UnavailableEvents {
+            agent: provenance,
+            events_hashes,
+        }),
+    )
+}
+
+/// Whether all the given missing previous events of the given event are unavailable
+///
+/// Only the author of the event and its linked devices know which of its previous events we are entitled to,
+/// so the unavailable events told by any other agent are ignored
+pub fn are_unavailable_for(
+    missing_previous_events: &Vec<EntryHash>,
+    private_event_entry: &PrivateEventEntry,
+    unavailable_events: Option<&UnavailableEvents>,
+) -> ExternResult<bool> {
+    let Some(unavailable_events) = unavailable_events else {
+        return Ok(false);
+    };
+    if !missing_previous_events
+        .iter()
+        .all(|previous_event| unavailable_events.events_hashes.contains(previous_event))
+    {
+        return Ok(false);
+    }
+
+    let author_agents = get_all_agents_for(private_event_entry.0.author.clone())?;
+    Ok(author_agents.contains(&unavailable_events.agent))
+}
+
+/// Whether the given agent is supposed to have the given event:
+/// its author, one of its recipients, or a linked device of its author or ourselves
+pub fn is_recipient_for<T: PrivateEvent>(
+    agent: &AgentPubKey,
+    event_hash: &EntryHashB64,
+    private_event_entry: &PrivateEventEntry,
+    my_linked_devices: &BTreeSet<AgentPubKey>,
+) -> ExternResult<bool> {
+    if private_event_entry.0.author.eq(agent) || my_linked_devices.contains(agent) {
+        return Ok(true);
+    }
+
+    let private_event = decode_private_event::<T>(&private_event_entry.0.payload.content)?;
+    let recipients = private_event.recipients(
+        event_hash.clone().into(),
+        private_event_entry.0.author.clone(),
+        private_event_entry.0.payload.timestamp,
+    )?;
+    if recipients.contains(agent) {
+        return Ok(true);
+    }
+
+    // The linked devices of the author also receive its events
+    let author_agents = get_all_agents_for(private_event_entry.0.author.clone())?;
+    Ok(author_agents.contains(agent))
+}
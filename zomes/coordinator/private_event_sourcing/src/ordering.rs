@@ -0,0 +1,401 @@
+use std::collections::BTreeMap;
+
+use hdk::prelude::*;
+use private_event_sourcing_integrity::*;
+
+use crate::{query_private_event_entries, query_private_events, PrivateEvent};
+
+/// The events in the given DAG that are not the previous event of any other event in it
+pub fn dag_heads(
+    previous_events: &BTreeMap<EntryHashB64, Vec<EntryHash>>,
+) -> BTreeSet<EntryHashB64> {
+    let referenced_events: BTreeSet<EntryHashB64> = previous_events
+        .values()
+        .flatten()
+        .cloned()
+        .map(EntryHashB64::from)
+        .collect();
+
+    previous_events
+        .keys()
+        .filter(|event_hash| !referenced_events.contains(event_hash))
+        .cloned()
+        .collect()
+}
+
+/// Computes the lamport clock for each of the events in the given DAG:
+/// 0 for events without previous events, and 1 + the maximum lamport clock of its previous events otherwise
+///
+/// The lamport clocks for previous events that are not in the DAG are taken from `known_lamport_clocks`.
+/// Events with a previous event that can't be found in either of them, and the events that come after them,
+/// get no lamport clock: their position in the total order is unknown until that previous event is received
+pub fn compute_lamport_clocks(
+    previous_events: &BTreeMap<EntryHashB64, Vec<EntryHash>>,
+    known_lamport_clocks: &BTreeMap<EntryHashB64, u32>,
+) -> BTreeMap<EntryHashB64, u32> {
+    let mut lamport_clocks: BTreeMap<EntryHashB64, u32> = BTreeMap::new();
+    let mut missing_ancestors: BTreeSet<EntryHashB64> = BTreeSet::new();
+
+    // Iterative depth first traversal, since event DAGs can be too deep for recursion
+    for event_hash in previous_events.keys() {
+        let mut stack: Vec<EntryHashB64> = vec![event_hash.clone()];
+
+        while let Some(current) = stack.last().cloned() {
+            if lamport_clocks.contains_key(&current) || missing_ancestors.contains(&current) {
+                stack.pop();
+                continue;
+            }
+            let current_previous_events: Vec<EntryHashB64> = previous_events
+                .get(&current)
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .map(EntryHashB64::from)
+                .collect();
+
+            let unresolved_previous_events: Vec<EntryHashB64> = current_previous_events
+                .iter()
+                .filter(|previous_event| {
+                    previous_events.contains_key(previous_event)
+                        && !lamport_clocks.contains_key(previous_event)
+                        && !missing_ancestors.contains(previous_event)
+                })
+                .cloned()
+                .collect();
+
+            if !unresolved_previous_events.is_empty() {
+                stack.extend(unresolved_previous_events);
+                continue;
+            }
+
+            let previous_lamport_clocks: Option<Vec<u32>> = current_previous_events
+                .iter()
+                .map(|previous_event| {
+                    lamport_clocks
+                        .get(previous_event)
+                        .or(known_lamport_clocks.get(previous_event))
+                        .cloned()
+                })
+                .collect();
+
+            match previous_lamport_clocks {
+                Some(previous_lamport_clocks) => {
+                    let lamport_clock = previous_lamport_clocks
+                        .into_iter()
+                        .max()
+                        .map(|max_lamport_clock| max_lamport_clock + 1)
+                        .unwrap_or(0);
+                    lamport_clocks.insert(current, lamport_clock);
+                }
+                None => {
+                    missing_ancestors.insert(current);
+                }
+            }
+            stack.pop();
+        }
+    }
+
+    lamport_clocks
+}
+
+/// Computes the order keys for the given events, using `known_lamport_clocks` for the previous events
+/// that are not among them
+///
+/// Events whose lamport clock can't be computed because some previous event is missing get no order key
+pub fn compute_event_order_keys<T>(
+    private_events: &BTreeMap<EntryHashB64, SignedEvent<T>>,
+    known_lamport_clocks: &BTreeMap<EntryHashB64, u32>,
+) -> BTreeMap<EntryHashB64, EventOrderKey> {
+    let previous_events: BTreeMap<EntryHashB64, Vec<EntryHash>> = private_events
+        .iter()
+        .map(|(event_hash, event)| {
+            (
+                event_hash.clone(),
+                event.payload.content.previous_events.clone(),
+            )
+        })
+        .collect();
+    let lamport_clocks = compute_lamport_clocks(&previous_events, known_lamport_clocks);
+
+    private_events
+        .iter()
+        .filter_map(|(event_hash, event)| {
+            let lamport_clock = lamport_clocks.get(event_hash)?;
+            Some((
+                event_hash.clone(),
+                EventOrderKey {
+                    lamport_clock: *lamport_clock,
+                    clock: event.payload.clock(),
+                    author: event.author.clone(),
+                    event_hash: event_hash.clone(),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Computes the order keys for the given events assuming that the previous events that are not among them
+/// are already known, so that every event gets an order key greater than the ones of its previous events among them
+pub fn compute_causal_order_keys<T>(
+    private_events: &BTreeMap<EntryHashB64, SignedEvent<T>>,
+) -> BTreeMap<EntryHashB64, EventOrderKey> {
+    let external_previous_events: BTreeMap<EntryHashB64, u32> = private_events
+        .values()
+        .flat_map(|event| event.payload.content.previous_events.clone())
+        .map(EntryHashB64::from)
+        .filter(|previous_event| !private_events.contains_key(previous_event))
+        .map(|previous_event| (previous_event, 0))
+        .collect();
+
+    compute_event_order_keys(private_events, &external_previous_events)
+}
+
+/// Sorts the given events by the given order keys, leaving out the ones without an order key
+fn sort_by_order_keys<T>(
+    private_events: BTreeMap<EntryHashB64, SignedEvent<T>>,
+    order_keys: &BTreeMap<EntryHashB64, EventOrderKey>,
+) -> Vec<(EntryHashB64, SignedEvent<T>)> {
+    let mut ordered_private_events: Vec<(EventOrderKey, (EntryHashB64, SignedEvent<T>))> =
+        private_events
+            .into_iter()
+            .filter_map(|(event_hash, event)| {
+                let order_key = order_keys.get(&event_hash)?.clone();
+                Some((order_key, (event_hash, event)))
+            })
+            .collect();
+    ordered_private_events.sort_by(|(a, _), (b, _)| a.cmp(b));
+    ordered_private_events
+        .into_iter()
+        .map(|(_, private_event)| private_event)
+        .collect()
+}
+
+/// Orders the given events in their deterministic total order:
+/// by causal order, then by lamport clock, hybrid logical clock, and finally by author and hash as tiebreaks
+///
+/// Events with some previous event that is not among the given events are placed after their known
+/// previous events, as if the missing ones had no previous events: their position in the total order
+/// may change once the missing previous events are received
+pub fn order_private_events<T>(
+    private_events: BTreeMap<EntryHashB64, SignedEvent<T>>,
+) -> Vec<(EntryHashB64, SignedEvent<T>)> {
+    let orphans_count = count_events_with_missing_ancestors(&private_events);
+    if orphans_count > 0 {
+        warn!(
+            "Ordering {orphans_count} private events whose previous events are missing after their known previous events."
+        );
+    }
+    let order_keys = compute_causal_order_keys(&private_events);
+    sort_by_order_keys(private_events, &order_keys)
+}
+
+/// The number of events that have some previous event, direct or indirect, that is not among the given events
+pub fn count_events_with_missing_ancestors<T>(
+    private_events: &BTreeMap<EntryHashB64, SignedEvent<T>>,
+) -> usize {
+    private_events.len() - compute_event_order_keys(private_events, &BTreeMap::new()).len()
+}
+
+/// Orders the given private event entries in their deterministic total order
+///
+/// Entries with some previous event that is not among the given entries are placed after their known previous events
+pub fn order_private_event_entries(
+    private_event_entries: BTreeMap<EntryHashB64, PrivateEventEntry>,
+) -> Vec<(EntryHashB64, PrivateEventEntry)> {
+    let private_events: BTreeMap<EntryHashB64, SignedEvent<SerializedBytes>> =
+        private_event_entries
+            .into_iter()
+            .map(|(event_hash, entry)| (event_hash, entry.0))
+            .collect();
+
+    order_private_events(private_events)
+        .into_iter()
+        .map(|(event_hash, event)| (event_hash, PrivateEventEntry(event)))
+        .collect()
+}
+
+/// Orders the given private event entries so that every entry comes after its previous events among them
+///
+/// Unlike `order_private_event_entries`, the previous events that are not among the given entries are assumed
+/// to be known already, so no entry is left out: use it to process or send a batch of events
+pub fn causally_order_private_event_entries(
+    private_event_entries: BTreeMap<EntryHashB64, PrivateEventEntry>,
+) -> Vec<(EntryHashB64, PrivateEventEntry)> {
+    let private_events: BTreeMap<EntryHashB64, SignedEvent<SerializedBytes>> =
+        private_event_entries
+            .into_iter()
+            .map(|(event_hash, entry)| (event_hash, entry.0))
+            .collect();
+    let order_keys = compute_causal_order_keys(&private_events);
+
+    sort_by_order_keys(private_events, &order_keys)
+        .into_iter()
+        .map(|(event_hash, event)| (event_hash, PrivateEventEntry(event)))
+        .collect()
+}
+
+/// Queries all the private events of type T, in their deterministic total order
+///
+/// Every agent that has the same set of events gets them in the same order
+pub fn query_ordered_private_events<T: PrivateEvent>(
+) -> ExternResult<Vec<(EntryHashB64, SignedEvent<T>)>> {
+    let private_events = query_private_events::<T>()?;
+
+    Ok(order_private_events(private_events))
+}
+
+#[hdk_extern]
+pub fn query_ordered_private_event_entries() -> ExternResult<Vec<(EntryHashB64, PrivateEventEntry)>>
+{
+    let private_event_entries = query_private_event_entries(())?;
+
+    Ok(order_private_event_entries(private_event_entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(n: u8) -> EntryHash {
+        EntryHash::from_raw_36(vec![n; 36])
+    }
+
+    fn dag(events: Vec<(u8, Vec<u8>)>) -> BTreeMap<EntryHashB64, Vec<EntryHash>> {
+        events
+            .into_iter()
+            .map(|(event, previous_events)| {
+                (
+                    EntryHashB64::from(hash(event)),
+                    previous_events.into_iter().map(hash).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn clock_of(lamport_clocks: &BTreeMap<EntryHashB64, u32>, event: u8) -> Option<u32> {
+        lamport_clocks
+            .get(&EntryHashB64::from(hash(event)))
+            .cloned()
+    }
+
+    #[test]
+    fn lamport_clocks_follow_the_longest_path() {
+        let previous_events = dag(vec![
+            (1, vec![]),
+            (2, vec![1]),
+            (3, vec![1]),
+            (4, vec![2]),
+            (5, vec![3, 4]),
+        ]);
+
+        let lamport_clocks = compute_lamport_clocks(&previous_events, &BTreeMap::new());
+
+        assert_eq!(clock_of(&lamport_clocks, 1), Some(0));
+        assert_eq!(clock_of(&lamport_clocks, 2), Some(1));
+        assert_eq!(clock_of(&lamport_clocks, 3), Some(1));
+        assert_eq!(clock_of(&lamport_clocks, 4), Some(2));
+        assert_eq!(clock_of(&lamport_clocks, 5), Some(3));
+    }
+
+    #[test]
+    fn lamport_clocks_use_known_clocks_for_external_previous_events() {
+        let previous_events = dag(vec![(2, vec![1]), (3, vec![2])]);
+        let known_lamport_clocks = BTreeMap::from([(EntryHashB64::from(hash(1)), 7)]);
+
+        let lamport_clocks = compute_lamport_clocks(&previous_events, &known_lamport_clocks);
+
+        assert_eq!(clock_of(&lamport_clocks, 2), Some(8));
+        assert_eq!(clock_of(&lamport_clocks, 3), Some(9));
+    }
+
+    #[test]
+    fn events_after_a_missing_previous_event_get_no_lamport_clock() {
+        let previous_events = dag(vec![
+            (1, vec![]),
+            (3, vec![1, 2]),
+            (4, vec![3]),
+            (5, vec![1]),
+        ]);
+
+        let lamport_clocks = compute_lamport_clocks(&previous_events, &BTreeMap::new());
+
+        assert_eq!(clock_of(&lamport_clocks, 1), Some(0));
+        assert_eq!(clock_of(&lamport_clocks, 3), None);
+        assert_eq!(clock_of(&lamport_clocks, 4), None);
+        assert_eq!(clock_of(&lamport_clocks, 5), Some(1));
+    }
+
+    fn event(author: u8, timestamp: i64, previous_events: Vec<u8>) -> SignedEvent<()> {
+        SignedEntry {
+            author: AgentPubKey::from_raw_36(vec![author; 36]),
+            signature: Signature([0; 64]),
+            payload: SignedContent {
+                timestamp: Timestamp::from_micros(timestamp),
+                content: PrivateEventContent {
+                    event_type: String::from("Event"),
+                    event: (),
+                    previous_events: previous_events.into_iter().map(hash).collect(),
+                    schema_version: 0,
+                },
+                hlc: None,
+            },
+        }
+    }
+
+    fn ordered_hashes(events: Vec<(u8, SignedEvent<()>)>) -> Vec<EntryHashB64> {
+        let private_events = events
+            .into_iter()
+            .map(|(event_hash, event)| (EntryHashB64::from(hash(event_hash)), event))
+            .collect();
+        order_private_events(private_events)
+            .into_iter()
+            .map(|(event_hash, _)| event_hash)
+            .collect()
+    }
+
+    #[test]
+    fn events_without_previous_events_are_ordered_by_their_clock() {
+        let ordered = ordered_hashes(vec![
+            (1, event(2, 30, vec![])),
+            (2, event(1, 10, vec![])),
+            (3, event(3, 20, vec![])),
+        ]);
+
+        assert_eq!(
+            ordered,
+            vec![
+                EntryHashB64::from(hash(2)),
+                EntryHashB64::from(hash(3)),
+                EntryHashB64::from(hash(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_with_missing_previous_events_are_placed_after_their_known_previous_events() {
+        let ordered = ordered_hashes(vec![
+            (1, event(1, 10, vec![])),
+            (3, event(1, 5, vec![1, 2])),
+            (4, event(1, 1, vec![3])),
+        ]);
+
+        assert_eq!(
+            ordered,
+            vec![
+                EntryHashB64::from(hash(1)),
+                EntryHashB64::from(hash(3)),
+                EntryHashB64::from(hash(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn dag_heads_are_the_unreferenced_events() {
+        let previous_events = dag(vec![(1, vec![]), (2, vec![1]), (3, vec![1])]);
+
+        assert_eq!(
+            dag_heads(&previous_events),
+            BTreeSet::from([EntryHashB64::from(hash(2)), EntryHashB64::from(hash(3))])
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+
+use hdk::prelude::*;
+use private_event_sourcing_integrity::*;
+
+use crate::{
+    causally_order_private_event_entries, send_async_message, utils::create_relaxed, ClockTicker,
+    PrivateEventSourcingRemoteSignal,
+};
+
+/// Maximum size of the serialized contents of each message, so that they fit in a remote signal and an async message
+pub const MAX_MESSAGE_SIZE_BYTES: usize = 512 * 1024;
+
+/// Contents pending to be sent to each recipient
+#[derive(Default)]
+struct RecipientContents {
+    private_events: BTreeMap<EntryHashB64, PrivateEventEntry>,
+    events_sent_to_recipients: Vec<EventSentToRecipients>,
+    acknowledgements: Vec<Acknowledgement>,
+    read_receipts: Vec<ReadReceipt>,
+    rejections: Vec<Rejection>,
+}
+
+/// Accumulates all the contents to be sent to each recipient, to send them batched together
+/// in as few messages as possible instead of one message per item
+#[derive(Default)]
+pub struct OutgoingMessages {
+    recipients: BTreeMap<AgentPubKey, RecipientContents>,
+}
+
+impl OutgoingMessages {
+    pub fn is_empty(&self) -> bool {
+        self.recipients.is_empty()
+    }
+
+    pub fn add_private_event(
+        &mut self,
+        recipient: &AgentPubKey,
+        event_hash: &EntryHashB64,
+        private_event_entry: &PrivateEventEntry,
+    ) {
+        self.recipients
+            .entry(recipient.clone())
+            .or_default()
+            .private_events
+            .insert(event_hash.clone(), private_event_entry.clone());
+    }
+
+    pub fn add_event_sent_to_recipients(
+        &mut self,
+        recipient: &AgentPubKey,
+        event_sent_to_recipients: &EventSentToRecipients,
+    ) {
+        let contents = self.recipients.entry(recipient.clone()).or_default();
+        if !contents
+            .events_sent_to_recipients
+            .contains(event_sent_to_recipients)
+        {
+            contents
+                .events_sent_to_recipients
+                .push(event_sent_to_recipients.clone());
+        }
+    }
+
+    pub fn add_acknowledgement(
+        &mut self,
+        recipient: &AgentPubKey,
+        acknowledgement: &Acknowledgement,
+    ) {
+        let contents = self.recipients.entry(recipient.clone()).or_default();
+        if !contents.acknowledgements.contains(acknowledgement) {
+            contents.acknowledgements.push(acknowledgement.clone());
+        }
+    }
+
+    pub fn add_read_receipt(&mut self, recipient: &AgentPubKey, read_receipt: &ReadReceipt) {
+        let contents = self.recipients.entry(recipient.clone()).or_default();
+        if !contents.read_receipts.contains(read_receipt) {
+            contents.read_receipts.push(read_receipt.clone());
+        }
+    }
+
+    pub fn add_rejection(&mut self, recipient: &AgentPubKey, rejection: &Rejection) {
+        let contents = self.recipients.entry(recipient.clone()).or_default();
+        if !contents.rejections.contains(rejection) {
+            contents.rejections.push(rejection.clone());
+        }
+    }
+
+    /// Sends all the accumulated contents, split in size-bounded messages
+    ///
+    /// Recipients that need to receive exactly the same message get it with a single remote signal and async message
+    ///
+    /// Returns the recipients to which all their messages were sent successfully
+    pub fn send(self) -> ExternResult<BTreeSet<AgentPubKey>> {
+        let mut failed_recipients: BTreeSet<AgentPubKey> = BTreeSet::new();
+        let mut all_recipients: BTreeSet<AgentPubKey> = BTreeSet::new();
+
+        for (message, recipients) in self.into_messages()? {
+            debug!(
+                "[send] Sending message with {} private events, {} events sent to recipients, {} acknowledgements, {} read receipts and {} rejections to {:?}.",
+                message.private_events.len(),
+                message.events_sent_to_recipients.len(),
+                message.acknowledgements.len(),
+                message.read_receipts.len(),
+                message.rejections.len(),
+                recipients
+            );
+            all_recipients.append(&mut recipients.clone());
+
+            send_message_remote_signal(&message, &recipients);
+
+            let message_id = message_id(&message)?;
+            match send_async_message(recipients.clone(), message_id, message) {
+                Ok(mut rate_limited_recipients) => {
+                    failed_recipients.append(&mut rate_limited_recipients)
+                }
+                Err(err) => {
+                    warn!("Failed to send async message to {recipients:?}: {err:?}.");
+                    failed_recipients.append(&mut recipients.clone());
+                }
+            }
+        }
+
+        Ok(all_recipients
+            .difference(&failed_recipients)
+            .cloned()
+            .collect())
+    }
+
+    /// Sends all the accumulated contents only with remote signals, without committing any async message
+    ///
+    /// Used to reply to requests from other agents, which must not make us commit entries
+    pub fn send_by_remote_signal(self) -> ExternResult<()> {
+        for (message, recipients) in self.into_messages()? {
+            send_message_remote_signal(&message, &recipients);
+        }
+        Ok(())
+    }
+
+    /// The size-bounded messages for all the accumulated contents, each with the recipients that need to receive it
+    fn into_messages(self) -> ExternResult<Vec<(Message, BTreeSet<AgentPubKey>)>> {
+        let mut messages: BTreeMap<Vec<u8>, (Message, BTreeSet<AgentPubKey>)> = BTreeMap::new();
+
+        for (recipient, contents) in self.recipients {
+            for message in split_in_batches(contents)? {
+                let bytes = encode(&message).map_err(|err| wasm_error!(err))?;
+                messages
+                    .entry(bytes)
+                    .or_insert((message, BTreeSet::new()))
+                    .1
+                    .insert(recipient.clone());
+            }
+        }
+
+        Ok(messages.into_values().collect())
+    }
+}
+
+/// Sends the given message to each of the given recipients with a remote signal
+///
+/// Remote signals are best effort: failing to send one to a recipient doesn't prevent sending it to the others
+fn send_message_remote_signal(message: &Message, recipients: &BTreeSet<AgentPubKey>) {
+    let signal = match SerializedBytes::try_from(PrivateEventSourcingRemoteSignal::SendMessage(
+        message.clone(),
+    )) {
+        Ok(signal) => signal,
+        Err(err) => {
+            warn!("Failed to serialize message remote signal: {err:?}.");
+            return;
+        }
+    };
+    for recipient in recipients {
+        if let Err(err) = send_remote_signal(signal.clone(), vec![recipient.clone()]) {
+            warn!("Failed to send message remote signal to {recipient}: {err:?}.");
+        }
+    }
+}
+
+/// Splits the contents for a recipient in messages whose contents don't exceed MAX_MESSAGE_SIZE_BYTES
+///
+/// Private events are added in their total order, so that their previous events are always
+/// sent in the same message or in an earlier one
+fn split_in_batches(contents: RecipientContents) -> ExternResult<Vec<Message>> {
+    let mut batches = MessageBatches::default();
+
+    for (_, private_event_entry) in causally_order_private_event_entries(contents.private_events) {
+        let size = encoded_size(&private_event_entry)?;
+        batches
+            .message_for(size)
+            .private_events
+            .push(private_event_entry);
+    }
+    for event_sent_to_recipients in contents.events_sent_to_recipients {
+        let size = encoded_size(&event_sent_to_recipients)?;
+        batches
+            .message_for(size)
+            .events_sent_to_recipients
+            .push(event_sent_to_recipients);
+    }
+    for acknowledgement in contents.acknowledgements {
+        let size = encoded_size(&acknowledgement)?;
+        batches
+            .message_for(size)
+            .acknowledgements
+            .push(acknowledgement);
+    }
+    for read_receipt in contents.read_receipts {
+        let size = encoded_size(&read_receipt)?;
+        batches.message_for(size).read_receipts.push(read_receipt);
+    }
+    for rejection in contents.rejections {
+        let size = encoded_size(&rejection)?;
+        batches.message_for(size).rejections.push(rejection);
+    }
+
+    Ok(batches.finish())
+}
+
+struct MessageBatches {
+    messages: Vec<Message>,
+    current: Message,
+    current_size: usize,
+}
+
+impl Default for MessageBatches {
+    fn default() -> Self {
+        MessageBatches {
+            messages: vec![],
+            current: empty_message(),
+            current_size: 0,
+        }
+    }
+}
+
+impl MessageBatches {
+    /// The message to which an item of the given size has to be added
+    fn message_for(&mut self, size: usize) -> &mut Message {
+        if self.current_size > 0 && self.current_size + size > MAX_MESSAGE_SIZE_BYTES {
+            let full = std::mem::replace(&mut self.current, empty_message());
+            self.messages.push(full);
+            self.current_size = 0;
+        }
+        self.current_size += size;
+        &mut self.current
+    }
+
+    fn finish(mut self) -> Vec<Message> {
+        if self.current_size > 0 {
+            self.messages.push(self.current);
+        }
+        self.messages
+    }
+}
+
+fn empty_message() -> Message {
+    Message {
+        private_events: vec![],
+        events_sent_to_recipients: vec![],
+        acknowledgements: vec![],
+        read_receipts: vec![],
+        rejections: vec![],
+    }
+}
+
+fn encoded_size<T: Serialize + std::fmt::Debug>(item: &T) -> ExternResult<usize> {
+    Ok(encode(item).map_err(|err| wasm_error!(err))?.len())
+}
+
+/// Deterministic id for the message, derived from the identities of its contents
+///
+/// Acknowledgements are signed again with a new timestamp every time they are resent, so the id can't be derived from the serialized message
+///
+/// Every resend of a private event carries a new EventSentToRecipients, so its timestamp is part of the id:
+/// this way a resend is not discarded as a duplicate by the recipient, which acknowledges the event again if the first acknowledgement was lost
+fn message_id(message: &Message) -> ExternResult<String> {
+    let mut identities: BTreeSet<String> = BTreeSet::new();
+
+    for private_event_entry in &message.private_events {
+        let event_hash = EntryHashB64::from(hash_entry(private_event_entry)?);
+        identities.insert(format!("{event_hash}/private_event"));
+    }
+    for event_sent_to_recipients in &message.events_sent_to_recipients {
+        let content = &event_sent_to_recipients.0.payload.content;
+        let recipients: Vec<String> = content
+            .recipients
+            .iter()
+            .map(|recipient| AgentPubKeyB64::from(recipient.clone()).to_string())
+            .collect();
+        identities.insert(format!(
+            "{}/event_sent_to_recipients/{}/{}/{}",
+            EntryHashB64::from(content.event_hash.clone()),
+            AgentPubKeyB64::from(event_sent_to_recipients.0.author.clone()),
+            recipients.join(","),
+            event_sent_to_recipients.0.payload.timestamp.as_micros()
+        ));
+    }
+    for acknowledgement in &message.acknowledgements {
+        let author = AgentPubKeyB64::from(acknowledgement.0.author.clone());
+        for event_hash in acknowledgement.0.payload.content.private_event_hashes() {
+            identities.insert(format!(
+                "{}/acknowledgement/{author}",
+                EntryHashB64::from(event_hash)
+            ));
+        }
+    }
+    for read_receipt in &message.read_receipts {
+        identities.insert(format!(
+            "{}/read_receipt/{}",
+            EntryHashB64::from(read_receipt.0.payload.content.private_event_hash.clone()),
+            AgentPubKeyB64::from(read_receipt.0.author.clone())
+        ));
+    }
+    for rejection in &message.rejections {
+        identities.insert(format!(
+            "{}/rejection/{}",
+            EntryHashB64::from(rejection.0.payload.content.event_hash.clone()),
+            AgentPubKeyB64::from(rejection.0.author.clone())
+        ));
+    }
+
+    let identities: Vec<String> = identities.into_iter().collect();
+    let hash = hash_blake2b(identities.join("\n").into_bytes(), 32)?;
+    Ok(hash.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Commits the given EventSentToRecipients, only for the recipients to which the event was actually sent
+pub fn commit_events_sent_to_recipients(
+    events_sent_to_recipients: Vec<EventSentToRecipients>,
+    sent_recipients: &BTreeSet<AgentPubKey>,
+    clock_ticker: &mut ClockTicker,
+) -> ExternResult<()> {
+    for event_sent_to_recipients in events_sent_to_recipients {
+        let recipients: BTreeSet<AgentPubKey> = event_sent_to_recipients
+            .0
+            .payload
+            .content
+            .recipients
+            .intersection(sent_recipients)
+            .cloned()
+            .collect();
+
+        if recipients.is_empty() {
+            continue;
+        }
+        if recipients.eq(&event_sent_to_recipients.0.payload.content.recipients) {
+            create_relaxed(EntryTypes::EventSentToRecipients(event_sent_to_recipients))?;
+        } else {
+            let content = EventSentToRecipientsContent {
+                event_hash: event_sent_to_recipients.0.payload.content.event_hash,
+                recipients,
+            };
+            let signed = clock_ticker.build_signed_entry(content)?;
+            create_relaxed(EntryTypes::EventSentToRecipients(EventSentToRecipients(
+                signed,
+            )))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejection(n: u8, reason_bytes: usize) -> Rejection {
+        Rejection(SignedEntry {
+            author: AgentPubKey::from_raw_36(vec![n; 36]),
+            signature: Signature([n; 64]),
+            payload: SignedContent {
+                timestamp: Timestamp::from_micros(0),
+                content: RejectionContent {
+                    event_hash: EntryHash::from_raw_36(vec![n; 36]),
+                    reason: "a".repeat(reason_bytes),
+                },
+                hlc: None,
+            },
+        })
+    }
+
+    fn contents_with_rejections(rejections: Vec<Rejection>) -> RecipientContents {
+        RecipientContents {
+            rejections,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn small_contents_are_sent_in_a_single_message() {
+        let messages = split_in_batches(contents_with_rejections(vec![
+            rejection(1, 10),
+            rejection(2, 10),
+        ]))
+        .unwrap();
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].rejections.len(), 2);
+    }
+
+    #[test]
+    fn contents_exceeding_the_max_size_are_split_in_messages() {
+        let reason_bytes = MAX_MESSAGE_SIZE_BYTES * 2 / 5;
+        let messages = split_in_batches(contents_with_rejections(vec![
+            rejection(1, reason_bytes),
+            rejection(2, reason_bytes),
+            rejection(3, reason_bytes),
+        ]))
+        .unwrap();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[0].rejections,
+            vec![rejection(1, reason_bytes), rejection(2, reason_bytes)]
+        );
+        assert_eq!(messages[1].rejections, vec![rejection(3, reason_bytes)]);
+    }
+
+    #[test]
+    fn no_messages_are_created_for_empty_contents() {
+        let messages = split_in_batches(RecipientContents::default()).unwrap();
+
+        assert!(messages.is_empty());
+    }
+}
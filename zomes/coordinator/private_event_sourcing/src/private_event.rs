@@ -4,10 +4,21 @@ use serde::de::DeserializeOwned;
 use std::collections::BTreeMap;
 
 use crate::{
-    linked_devices::query_my_linked_devices, query_event_histories,
-    send_acknowledgement_for_event_to_recipient, utils::create_relaxed, Signal,
+    add_acknowledgement_for_event_to_recipient, add_read_receipt_for_event_to_recipient,
+    awaiting_dependencies::query_awaiting_deps_private_event_entries,
+    causally_order_private_event_entries, dag_heads,
+    missing_dependencies::is_recipient_for,
+    outgoing_messages::{OutgoingMessages, MAX_MESSAGE_SIZE_BYTES},
+    query_event_histories, query_latest_clock, query_my_linked_devices, reject_private_event,
+    request_events,
+    utils::create_relaxed,
+    validate_clock, ClockTicker, ImportedEntries, Quarantine, ReceivedEntryOutcome, ResendPolicy,
+    Signal,
 };
 
+/// Maximum number of times that a new event is signed while looking for the previous events that its recipients can receive
+const MAX_PREVIOUS_EVENTS_ATTEMPTS: usize = 3;
+
 pub trait EventType {
     fn event_type(&self) -> String;
 }
@@ -46,6 +57,75 @@ pub trait PrivateEvent:
         author: AgentPubKey,
         timestamp: Timestamp,
     ) -> ExternResult<bool>;
+
+    /// The policy to resend this event to the recipients that haven't acknowledged it yet
+    /// Override it to configure it for the whole app, or per event type by matching on the event
+    fn resend_policy(&self) -> ResendPolicy {
+        ResendPolicy::default()
+    }
+
+    /// The current version of the schema of the events
+    /// Increment it every time the shape of the events changes, and handle the old versions in `upcast`
+    fn schema_version() -> u32 {
+        0
+    }
+
+    /// Migrates an event created with an older schema version into the current shape of the events
+    ///
+    /// The given bytes are the serialized event as it was created, which can be decoded with the old shape of the events,
+    /// e.g. `decode::<_, EventV0>(event.bytes())`
+    fn upcast(
+        schema_version: u32,
+        event_type: &String,
+        event: SerializedBytes,
+    ) -> ExternResult<Self> {
+        decode::<_, Self>(event.bytes()).map_err(|err| {
+            wasm_error!(
+                "Failed to deserialize private event of type '{}' with schema version {}: {:?}.",
+                event_type,
+                schema_version,
+                err
+            )
+        })
+    }
+
+    /// The event type that an event created with an older schema version has after being upcasted
+    /// Override it if `upcast` changes the event type of some events
+    fn upcast_event_type(_schema_version: u32, event_type: &String) -> String {
+        event_type.clone()
+    }
+
+    /// Persists the snapshots of the projections of these events, run periodically by the scheduled tasks
+    /// Override it to call `persist_projection_snapshot::<Self, P>()` for every projection P of these events,
+    /// and `prune_projection_snapshots(&[P::PROJECTION_NAME, ...])` to delete the snapshots of renamed or removed projections
+    fn persist_projection_snapshots() -> ExternResult<()> {
+        Ok(())
+    }
+}
+
+/// Deserializes the event of the given content, upcasting it if it was created with an older schema version
+pub fn decode_private_event<T: PrivateEvent>(
+    content: &PrivateEventContent<SerializedBytes>,
+) -> ExternResult<T> {
+    let current_schema_version = T::schema_version();
+
+    if content.schema_version == current_schema_version {
+        return decode::<_, T>(content.event.bytes())
+            .map_err(|err| wasm_error!("Failed to deserialize private event: {:?}.", err));
+    }
+    if content.schema_version > current_schema_version {
+        return Err(wasm_error!(
+            "Private event has schema version {}, which is newer than our schema version {}.",
+            content.schema_version,
+            current_schema_version
+        ));
+    }
+
+    T::upcast(
+        content.schema_version,
+        &content.event_type,
+        content.event.clone(),
+    )
 }
 
 pub fn create_private_event<T: PrivateEvent>(private_event: T) -> ExternResult<EntryHash> {
@@ -53,16 +133,70 @@ pub fn create_private_event<T: PrivateEvent>(private_event: T) -> ExternResult<E
         .clone()
         .try_into()
         .map_err(|_err| wasm_error!("Failed to serialize."))?;
-    let signed = SignedEntry::build(PrivateEventContent {
-        event_type: private_event.event_type(),
-        event: event_bytes,
-    })?;
-    let author = signed.author.clone();
-    let timestamp = signed.payload.timestamp.clone();
-    let private_event_entry = PrivateEventEntry(signed);
+    let private_event_entries = query_private_event_entries(())?;
+    let latest_clock = query_latest_clock()?;
+    let my_linked_devices = query_my_linked_devices()?;
+
+    // Recipients that don't have some of the previous events request them from us, so the previous events
+    // are only the heads that all the recipients are entitled to receive
+    //
+    // The recipients of the event may depend on its hash, which depends on its previous events:
+    // the event is only signed again if its recipients are not entitled to all of our heads
+    let mut previous_events: BTreeSet<EntryHashB64> = compute_heads(&private_event_entries);
+    let mut attempts: usize = 0;
+    let (private_event_entry, entry_hash) = loop {
+        let signed = SignedEntry::build_with_clock(
+            PrivateEventContent {
+                event_type: private_event.event_type(),
+                event: event_bytes.clone(),
+                previous_events: previous_events
+                    .iter()
+                    .cloned()
+                    .map(EntryHash::from)
+                    .collect(),
+                schema_version: T::schema_version(),
+            },
+            latest_clock.clone(),
+        )?;
+        let private_event_entry = PrivateEventEntry(signed);
+        let entry_hash = hash_entry(&private_event_entry)?;
+        if previous_events.is_empty() {
+            break (private_event_entry, entry_hash);
+        }
 
-    let entry_hash = hash_entry(&private_event_entry)?;
-    let validation_outcome = private_event.validate(entry_hash, author, timestamp)?;
+        let recipients = private_event.recipients(
+            entry_hash.clone(),
+            private_event_entry.0.author.clone(),
+            private_event_entry.0.payload.timestamp,
+        )?;
+        attempts += 1;
+        // Recipients that keep changing with the previous events get an event without previous events
+        let shared_heads = if attempts < MAX_PREVIOUS_EVENTS_ATTEMPTS {
+            compute_shared_heads::<T>(&private_event_entries, &recipients, &my_linked_devices)?
+        } else {
+            BTreeSet::new()
+        };
+        if shared_heads.eq(&previous_events) {
+            break (private_event_entry, entry_hash);
+        }
+        previous_events = shared_heads;
+    };
+
+    // Bigger events could never be sent in a message to their recipients
+    let size = encode(&private_event_entry)
+        .map_err(|err| wasm_error!(err))?
+        .len();
+    if size > MAX_MESSAGE_SIZE_BYTES {
+        return Err(wasm_error!(
+            "Private event of {size} bytes exceeds the maximum size of {MAX_MESSAGE_SIZE_BYTES} bytes."
+        ));
+    }
+
+    let validation_outcome = private_event.validate(
+        entry_hash.clone(),
+        private_event_entry.0.author.clone(),
+        private_event_entry.0.payload.timestamp,
+    )?;
 
     match validation_outcome {
         ValidateCallbackResult::Valid => {}
@@ -75,7 +209,6 @@ pub fn create_private_event<T: PrivateEvent>(private_event: T) -> ExternResult<E
         ))?,
     };
 
-    let entry_hash = hash_entry(&private_event_entry)?;
     let app_entry = EntryTypes::PrivateEvent(private_event_entry.clone());
     create_relaxed(app_entry)?;
     emit_signal(Signal::NewPrivateEvent {
@@ -86,6 +219,94 @@ pub fn create_private_event<T: PrivateEvent>(private_event: T) -> ExternResult<E
     Ok(entry_hash)
 }
 
+/// The events in the given set that are not the previous event of any other event
+pub fn compute_heads(
+    private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+) -> BTreeSet<EntryHashB64> {
+    let previous_events: BTreeMap<EntryHashB64, Vec<EntryHash>> = private_event_entries
+        .iter()
+        .map(|(event_hash, entry)| {
+            (
+                event_hash.clone(),
+                entry.0.payload.content.previous_events.clone(),
+            )
+        })
+        .collect();
+
+    dag_heads(&previous_events)
+}
+
+/// The latest of the given events that all the given recipients are entitled to receive
+///
+/// Walks back from the heads of the DAG, replacing every event that some recipient is not entitled to
+/// with its previous events, so that recipients are never pointed to events that they can't request from us
+pub fn compute_shared_heads<T: PrivateEvent>(
+    private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    recipients: &BTreeSet<AgentPubKey>,
+    my_linked_devices: &BTreeSet<AgentPubKey>,
+) -> ExternResult<BTreeSet<EntryHashB64>> {
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+    let mut shared_heads: BTreeSet<EntryHashB64> = BTreeSet::new();
+    let mut visited: BTreeSet<EntryHashB64> = BTreeSet::new();
+    let mut pending: Vec<EntryHashB64> = compute_heads(private_event_entries).into_iter().collect();
+
+    while let Some(event_hash) = pending.pop() {
+        if !visited.insert(event_hash.clone()) {
+            continue;
+        }
+        let Some(private_event_entry) = private_event_entries.get(&event_hash) else {
+            continue;
+        };
+        // Events whose recipients can't be computed are not shared with anyone
+        let shared = recipients
+            .iter()
+            .filter(|recipient| my_pub_key.ne(recipient))
+            .all(|recipient| {
+                is_recipient_for::<T>(
+                    recipient,
+                    &event_hash,
+                    private_event_entry,
+                    my_linked_devices,
+                )
+                .unwrap_or(false)
+            });
+        if shared {
+            shared_heads.insert(event_hash);
+        } else {
+            pending.extend(
+                private_event_entry
+                    .0
+                    .payload
+                    .content
+                    .previous_events
+                    .iter()
+                    .cloned()
+                    .map(EntryHashB64::from),
+            );
+        }
+    }
+
+    Ok(shared_heads)
+}
+
+/// The previous events of the given entry that are not among the given events
+pub fn missing_previous_events(
+    private_event_entry: &PrivateEventEntry,
+    private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+) -> Vec<EntryHash> {
+    private_event_entry
+        .0
+        .payload
+        .content
+        .previous_events
+        .iter()
+        .filter(|previous_event| {
+            !private_event_entries.contains_key(&EntryHashB64::from((*previous_event).clone()))
+        })
+        .cloned()
+        .collect()
+}
+
 pub fn validate_private_event_entry<T: PrivateEvent>(
     private_event_entry: &PrivateEventEntry,
 ) -> ExternResult<ValidateCallbackResult> {
@@ -97,16 +318,23 @@ pub fn validate_private_event_entry<T: PrivateEvent>(
         )));
     }
 
-    let private_event = T::try_from(private_event_entry.0.payload.content.event.clone())
-        .map_err(|_err| wasm_error!("Failed to deserialize the private event."))?;
+    if let Err(reason) = validate_clock(&private_event_entry.0.payload)? {
+        return Ok(ValidateCallbackResult::Invalid(reason));
+    }
+
+    let private_event = decode_private_event::<T>(&private_event_entry.0.payload.content)?;
 
-    if private_event
-        .event_type()
-        .ne(&private_event_entry.0.payload.content.event_type)
-    {
+    // Upcasted events may have a different event type than the one they were created with
+    let content = &private_event_entry.0.payload.content;
+    let expected_event_type = if content.schema_version == T::schema_version() {
+        content.event_type.clone()
+    } else {
+        T::upcast_event_type(content.schema_version, &content.event_type)
+    };
+    if private_event.event_type().ne(&expected_event_type) {
         return Ok(ValidateCallbackResult::Invalid(format!(
             "Invalid event type: expected '{}', but got '{}'.",
-            private_event_entry.0.payload.content.event_type,
+            expected_event_type,
             private_event.event_type()
         )));
     }
@@ -124,43 +352,222 @@ pub fn receive_private_events<T: PrivateEvent>(
     my_private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
     provenance: AgentPubKey,
     private_event_entries: Vec<PrivateEventEntry>,
+    report: &mut ImportedEntries,
+    clock_ticker: &mut ClockTicker,
 ) -> ExternResult<BTreeMap<EntryHashB64, PrivateEventEntry>> {
     debug!("[receive_private_events/start]");
     // check_is_linked_device(provenance)?;
 
-    let mut ordered_their_private_event_entries: Vec<PrivateEventEntry> = private_event_entries;
-    ordered_their_private_event_entries.sort_by_key(|e| e.0.payload.timestamp);
+    let their_private_event_entries = private_event_entries
+        .into_iter()
+        .map(|private_event_entry| {
+            let entry_hash = EntryHashB64::from(hash_entry(&private_event_entry)?);
+            Ok((entry_hash, private_event_entry))
+        })
+        .collect::<ExternResult<BTreeMap<EntryHashB64, PrivateEventEntry>>>()?;
 
     let my_pub_key = agent_info()?.agent_initial_pubkey;
 
-    let mut new_entries: BTreeMap<EntryHashB64, PrivateEventEntry> = BTreeMap::new();
+    let mut receiving = ReceivingPrivateEvents {
+        provenance: provenance.clone(),
+        clock_ticker,
+        new_entries: BTreeMap::new(),
+        outgoing_messages: OutgoingMessages::default(),
+        quarantine: Quarantine::default(),
+        missing_events: BTreeSet::new(),
+    };
 
-    for private_event_entry in ordered_their_private_event_entries {
-        let entry_hash = EntryHashB64::from(hash_entry(&private_event_entry)?);
-        if let Some(event) = my_private_event_entries.get(&entry_hash) {
-            // We already have this event
-            if event.0.author.ne(&my_pub_key) {
-                send_acknowledgement_for_event_to_recipient::<T>(&entry_hash, &provenance)?;
+    let mut pending_entries = causally_order_private_event_entries(their_private_event_entries);
+
+    // Events whose previous events come later in this same message are postponed until those are processed
+    while !pending_entries.is_empty() {
+        let pending_hashes: BTreeSet<EntryHashB64> = pending_entries
+            .iter()
+            .map(|(entry_hash, _)| entry_hash.clone())
+            .collect();
+        let pending_count = pending_entries.len();
+        let mut postponed_entries: Vec<(EntryHashB64, PrivateEventEntry)> = vec![];
+
+        for (entry_hash, private_event_entry) in pending_entries {
+            if let Some(event) = my_private_event_entries.get(&entry_hash) {
+                // We already have this event
+                if event.0.author.ne(&my_pub_key) {
+                    // Failing to send back our receipts doesn't prevent receiving the rest of the message
+                    let receipts = add_acknowledgement_for_event_to_recipient(
+                        &mut receiving.outgoing_messages,
+                        &entry_hash,
+                        &provenance,
+                    )
+                    .and_then(|_| {
+                        add_read_receipt_for_event_to_recipient(
+                            &mut receiving.outgoing_messages,
+                            &entry_hash,
+                            &provenance,
+                        )
+                    });
+                    if let Err(err) = receipts {
+                        warn!("Failed to send back our receipts for PrivateEvent {entry_hash}: {err:?}.");
+                    }
+                }
+                continue;
             }
-            continue;
+            if receiving.new_entries.contains_key(&entry_hash) {
+                continue;
+            }
+
+            // Events with an invalid signature are rejected right away instead of waiting for their previous events
+            let missing_previous_events = match private_event_entry.0.verify() {
+                Ok(true) => receiving
+                    .missing_previous_events(&private_event_entry, my_private_event_entries),
+                Ok(false) => vec![],
+                Err(err) => {
+                    report.record(entry_hash, Err(err));
+                    continue;
+                }
+            };
+
+            if !missing_previous_events.is_empty() {
+                let previous_events_are_pending =
+                    missing_previous_events.iter().all(|previous_event| {
+                        pending_hashes.contains(&EntryHashB64::from(previous_event.clone()))
+                    });
+                if previous_events_are_pending {
+                    postponed_entries.push((entry_hash, private_event_entry));
+                    continue;
+                }
+
+                let outcome = receiving.defer_until_previous_events(
+                    &entry_hash,
+                    private_event_entry,
+                    missing_previous_events,
+                );
+                report.record(entry_hash, outcome);
+                continue;
+            }
+
+            let outcome = receiving.receive_private_event::<T>(&entry_hash, private_event_entry);
+            report.record(entry_hash, outcome);
         }
 
-        let outcome = validate_private_event_entry::<T>(&private_event_entry);
+        if postponed_entries.len() == pending_count {
+            // No progress was made: the previous events of the postponed events will never be resolved in this message
+            for (entry_hash, private_event_entry) in postponed_entries {
+                let missing_previous_events = receiving
+                    .missing_previous_events(&private_event_entry, my_private_event_entries);
+                let outcome = receiving.defer_until_previous_events(
+                    &entry_hash,
+                    private_event_entry,
+                    missing_previous_events,
+                );
+                report.record(entry_hash, outcome);
+            }
+            break;
+        }
+
+        pending_entries = postponed_entries;
+    }
+
+    receiving.outgoing_messages.send()?;
+    receiving.quarantine.commit_overflow()?;
 
-        match outcome {
+    request_events(receiving.missing_events, BTreeSet::from([provenance]))?;
+
+    Ok(receiving.new_entries)
+}
+
+/// The state of receiving the private events of a message, shared by all of its events
+struct ReceivingPrivateEvents<'a> {
+    provenance: AgentPubKey,
+    clock_ticker: &'a mut ClockTicker,
+    new_entries: BTreeMap<EntryHashB64, PrivateEventEntry>,
+    /// Acknowledgements for the events we already had and rejections for the invalid events,
+    /// sent back all together
+    outgoing_messages: OutgoingMessages,
+    quarantine: Quarantine,
+    /// Previous events that we don't have, requested from the provenance after processing the message
+    missing_events: BTreeSet<EntryHash>,
+}
+
+impl ReceivingPrivateEvents<'_> {
+    /// The previous events of the given entry that are neither in our source chain nor received in this message
+    fn missing_previous_events(
+        &self,
+        private_event_entry: &PrivateEventEntry,
+        my_private_event_entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    ) -> Vec<EntryHash> {
+        missing_previous_events(private_event_entry, my_private_event_entries)
+            .into_iter()
+            .filter(|previous_event| {
+                !self
+                    .new_entries
+                    .contains_key(&EntryHashB64::from(previous_event.clone()))
+            })
+            .collect()
+    }
+
+    /// Adds the given event to the awaiting dependencies queue until we receive its missing previous events
+    fn defer_until_previous_events(
+        &mut self,
+        entry_hash: &EntryHashB64,
+        private_event_entry: PrivateEventEntry,
+        missing_previous_events: Vec<EntryHash>,
+    ) -> ExternResult<ReceivedEntryOutcome> {
+        warn!(
+            "Received a PrivateEvent {entry_hash} but we don't have all its previous events: adding it to the awaiting dependencies queue."
+        );
+        create_relaxed(EntryTypes::AwaitingDependencies(
+            AwaitingDependencies::Event {
+                event: private_event_entry,
+                unresolved_dependencies: UnresolvedDependencies::Hashes(
+                    missing_previous_events
+                        .iter()
+                        .cloned()
+                        .map(AnyDhtHash::from)
+                        .collect(),
+                ),
+                provenance: Some(self.provenance.clone()),
+            },
+        ))?;
+        self.missing_events.extend(missing_previous_events);
+        Ok(ReceivedEntryOutcome::Deferred)
+    }
+
+    /// Validates the given event, whose previous events we all have, and commits it if it's valid
+    fn receive_private_event<T: PrivateEvent>(
+        &mut self,
+        entry_hash: &EntryHashB64,
+        private_event_entry: PrivateEventEntry,
+    ) -> ExternResult<ReceivedEntryOutcome> {
+        match validate_private_event_entry::<T>(&private_event_entry) {
             Ok(ValidateCallbackResult::Valid) => {
                 let app_entry = EntryTypes::PrivateEvent(private_event_entry.clone());
                 create_relaxed(app_entry)?;
+                self.clock_ticker
+                    .observe(private_event_entry.0.payload.clock())?;
                 info!("Received a PrivateEvent {entry_hash}.");
-                new_entries.insert(entry_hash, private_event_entry);
+                self.new_entries
+                    .insert(entry_hash.clone(), private_event_entry);
+                Ok(ReceivedEntryOutcome::Accepted)
             }
             Ok(ValidateCallbackResult::Invalid(reason)) => {
-                warn!("Received an invalid PrivateEvent {entry_hash}: discarding.");
-                return Err(wasm_error!(
-                    "Invalid PrivateEvent {}: {}.",
+                warn!("Received an invalid PrivateEvent {entry_hash}: rejecting it.");
+                // Rejections are capped per provenance the same way as the quarantine
+                if self.quarantine.quarantine_private_event(
                     entry_hash,
-                    reason
-                ));
+                    &private_event_entry,
+                    &self.provenance,
+                    reason.clone(),
+                )? {
+                    reject_private_event(
+                        &mut self.outgoing_messages,
+                        self.clock_ticker,
+                        entry_hash,
+                        &private_event_entry,
+                        &self.provenance,
+                        reason.clone(),
+                    )?;
+                }
+                Ok(ReceivedEntryOutcome::Invalid(reason))
             }
             Ok(ValidateCallbackResult::UnresolvedDependencies(unresolved_dependencies)) => {
                 warn!(
@@ -170,23 +577,82 @@ pub fn receive_private_events<T: PrivateEvent>(
                     AwaitingDependencies::Event {
                         event: private_event_entry,
                         unresolved_dependencies,
+                        provenance: Some(self.provenance.clone()),
                     },
                 ))?;
+                Ok(ReceivedEntryOutcome::Deferred)
             }
             Err(_) => {
                 warn!(
                     "Received a PrivateEvent {entry_hash} but its validation failed: adding it to the awaiting dependencies queue."
                 );
+                if let Err(err) = decode_private_event::<T>(&private_event_entry.0.payload.content)
+                {
+                    emit_signal(Signal::UndecodablePrivateEvent {
+                        undecodable_private_event: UndecodablePrivateEvent {
+                            event_hash: entry_hash.clone(),
+                            event_type: private_event_entry.0.payload.content.event_type.clone(),
+                            schema_version: private_event_entry.0.payload.content.schema_version,
+                            error: format!("{err:?}"),
+                            awaiting_dependencies: true,
+                        },
+                    })?;
+                }
                 create_relaxed(EntryTypes::AwaitingDependencies(
                     AwaitingDependencies::Event {
                         event: private_event_entry,
                         unresolved_dependencies: UnresolvedDependencies::Hashes(vec![]),
+                        provenance: Some(self.provenance.clone()),
                     },
                 ))?;
+                Ok(ReceivedEntryOutcome::Deferred)
             }
         }
     }
-    Ok(new_entries)
+}
+
+/// A private event that could not be deserialized into the current shape of the events
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UndecodablePrivateEvent {
+    pub event_hash: EntryHashB64,
+    pub event_type: String,
+    pub schema_version: u32,
+    pub error: String,
+    /// Whether the event is in the awaiting dependencies queue instead of committed as a private event
+    pub awaiting_dependencies: bool,
+}
+
+/// Queries the private events that can't be deserialized, which `query_private_events` skips
+///
+/// These are usually events created by a different version of the app with a different schema
+pub fn query_undecodable_private_events<T: PrivateEvent>(
+) -> ExternResult<Vec<UndecodablePrivateEvent>> {
+    let private_event_entries = query_private_event_entries(())?
+        .into_iter()
+        .map(|(event_hash, private_event_entry)| (event_hash, private_event_entry, false));
+    let awaiting_private_event_entries = query_awaiting_deps_private_event_entries()?
+        .into_iter()
+        .map(|private_event_entry| {
+            let event_hash = EntryHashB64::from(hash_entry(&private_event_entry)?);
+            Ok((event_hash, private_event_entry, true))
+        })
+        .collect::<ExternResult<Vec<(EntryHashB64, PrivateEventEntry, bool)>>>()?;
+
+    let undecodable_private_events = private_event_entries
+        .chain(awaiting_private_event_entries)
+        .filter_map(|(event_hash, private_event_entry, awaiting_dependencies)| {
+            let err = decode_private_event::<T>(&private_event_entry.0.payload.content).err()?;
+            Some(UndecodablePrivateEvent {
+                event_hash,
+                event_type: private_event_entry.0.payload.content.event_type,
+                schema_version: private_event_entry.0.payload.content.schema_version,
+                error: format!("{err:?}"),
+                awaiting_dependencies,
+            })
+        })
+        .collect();
+
+    Ok(undecodable_private_events)
 }
 
 pub fn query_private_event_entries_by_type(
@@ -279,8 +745,7 @@ pub fn query_private_event_entry(event_hash: EntryHash) -> ExternResult<Option<P
 pub fn private_event_entry_to_signed_event<T: PrivateEvent>(
     private_event_entry: PrivateEventEntry,
 ) -> ExternResult<SignedEntry<PrivateEventContent<T>>> {
-    let private_event = T::try_from(private_event_entry.0.payload.content.event)
-        .map_err(|_err| wasm_error!("Failed to deserialize private event."))?;
+    let private_event = decode_private_event::<T>(&private_event_entry.0.payload.content)?;
     Ok(SignedEntry {
         author: private_event_entry.0.author,
         signature: private_event_entry.0.signature,
@@ -289,7 +754,10 @@ pub fn private_event_entry_to_signed_event<T: PrivateEvent>(
             content: PrivateEventContent {
                 event_type: private_event_entry.0.payload.content.event_type,
                 event: private_event,
+                previous_events: private_event_entry.0.payload.content.previous_events,
+                schema_version: private_event_entry.0.payload.content.schema_version,
             },
+            hlc: private_event_entry.0.payload.hlc,
         },
     })
 }
@@ -304,6 +772,8 @@ pub fn query_private_event<T: PrivateEvent>(
     Ok(Some(signed_event))
 }
 
+// Only used by the linked devices guard in receive_private_events, which is disabled for now
+#[allow(dead_code)]
 fn check_is_linked_device(agent: AgentPubKey) -> ExternResult<()> {
     let my_devices = query_my_linked_devices()?;
     if my_devices.contains(&agent) {
@@ -0,0 +1,487 @@
+use std::collections::BTreeMap;
+
+use hdk::prelude::*;
+use private_event_sourcing_integrity::*;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    compute_event_order_keys, dag_heads, order_private_events, private_event_entry_to_signed_event,
+    utils::{create_relaxed, delete_relaxed, query_deleted_actions_since},
+    PrivateEvent,
+};
+
+/// Number of events that need to be applied on top of the latest snapshot
+/// before a new snapshot for the projection is persisted
+const SNAPSHOT_INTERVAL: usize = 100;
+
+/// Number of actions at the top of our source chain in which the snapshots of a projection are looked for at first
+const SNAPSHOTS_WINDOW: u32 = 100;
+
+/// The contents of a new snapshot for the state of a projection, before it is persisted
+struct NewSnapshot {
+    last_applied_event: EventOrderKey,
+    heads_lamport_clocks: BTreeMap<EntryHashB64, u32>,
+    last_action: (u32, ActionHash),
+    /// Number of events applied since the latest persisted snapshot
+    applied_events_count: usize,
+}
+
+/// State derived from folding all the private events of type T
+///
+/// Implement this trait for the type that holds the derived state,
+/// and use `query_projection::<T, P>()` to compute it
+pub trait Projection<T: PrivateEvent>:
+    Sized + Serialize + DeserializeOwned + std::fmt::Debug
+{
+    /// The state before any event has been applied
+    fn initial_state() -> Self;
+
+    /// Applies the given event on top of the given state, returning the new state
+    fn apply(
+        state: Self,
+        event_hash: EntryHash,
+        signed_event: SignedEvent<T>,
+    ) -> ExternResult<Self>;
+
+    /// Identifies the snapshots of this projection in the source chain, so it must be stable across app versions
+    /// Change it whenever the shape of the state or the logic of `apply` changes,
+    /// so that the projection is recomputed from scratch
+    const PROJECTION_NAME: &'static str;
+}
+
+/// Folds the given events into the projection P, in their deterministic total order
+pub fn fold_projection<T: PrivateEvent, P: Projection<T>>(
+    state: P,
+    private_events: BTreeMap<EntryHashB64, SignedEvent<T>>,
+) -> ExternResult<P> {
+    let mut state = state;
+
+    for (event_hash, signed_event) in order_private_events(private_events) {
+        state = P::apply(state, event_hash.into(), signed_event)?;
+    }
+
+    Ok(state)
+}
+
+/// Computes the projection P from all the private events of type T in our source chain
+///
+/// Only the events committed after the latest snapshot of the projection are applied
+///
+/// This never commits any entry: snapshots are persisted by `persist_projection_snapshot`
+pub fn query_projection<T: PrivateEvent, P: Projection<T>>() -> ExternResult<P> {
+    let (state, _) = compute_projection::<T, P>()?;
+    Ok(state)
+}
+
+/// Persists a new snapshot of the projection P if at least `SNAPSHOT_INTERVAL` events were applied
+/// on top of the latest one, deleting the snapshots that it supersedes
+///
+/// Call it from `PrivateEvent::persist_projection_snapshots`, which is run by the scheduled tasks
+pub fn persist_projection_snapshot<T: PrivateEvent, P: Projection<T>>() -> ExternResult<()> {
+    let (state, new_snapshot) = compute_projection::<T, P>()?;
+    let Some(new_snapshot) = new_snapshot else {
+        return Ok(());
+    };
+    if new_snapshot.applied_events_count < SNAPSHOT_INTERVAL {
+        return Ok(());
+    }
+
+    // Snapshots with an older shape of ProjectionSnapshot can't be used by any projection
+    for (action_hash, _) in query_projection_snapshots(P::PROJECTION_NAME)? {
+        delete_relaxed(action_hash)?;
+    }
+
+    create_projection_snapshot::<T, P>(&state, new_snapshot)?;
+
+    Ok(())
+}
+
+/// Deletes the snapshots of the projections that are not in the given list of `PROJECTION_NAME`s,
+/// and the snapshots with an older shape of ProjectionSnapshot
+///
+/// `persist_projection_snapshot` only replaces the snapshots of its own projection, so the snapshots
+/// of projections that were renamed or removed are left behind until this is called
+///
+/// The snapshots of those projections can be anywhere in the source chain, so all the ProjectionSnapshot actions are queried,
+/// but their entries are only fetched for the ones that haven't been deleted
+pub fn prune_projection_snapshots(projection_names: &[&str]) -> ExternResult<()> {
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::ProjectionSnapshot.try_into()?)
+        .action_type(ActionType::Create);
+    let records = query(filter)?;
+
+    let deleted_actions = match records.first() {
+        Some(first_record) => query_deleted_actions_since(first_record.action().action_seq())?,
+        None => BTreeSet::new(),
+    };
+
+    for record in records {
+        if deleted_actions.contains(record.action_address()) {
+            continue;
+        }
+        let action_seq = record.action().action_seq();
+        let filter = ChainQueryFilter::new()
+            .include_entries(true)
+            .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+                action_seq, action_seq,
+            ));
+        let in_use = query(filter)?.first().is_some_and(|record| {
+            decode_projection_snapshot(record).is_some_and(|snapshot| {
+                projection_names.contains(&snapshot.projection_name.as_str())
+            })
+        });
+        if !in_use {
+            delete_relaxed(record.action_address().clone())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes the projection P, together with the snapshot that can be persisted for it
+///
+/// There is no snapshot to persist if no event was applied on top of the latest snapshot,
+/// or if some event is missing previous events and so its position in the total order can still change
+fn compute_projection<T: PrivateEvent, P: Projection<T>>() -> ExternResult<(P, Option<NewSnapshot>)>
+{
+    if let Some((snapshot, state)) = query_latest_projection_snapshot::<T, P>()? {
+        let (new_events, last_action) =
+            query_private_events_since::<T>(Some(snapshot.last_action_seq))?;
+        let Some(last_action) = last_action else {
+            return Ok((state, None));
+        };
+
+        if let Some(result) = apply_on_top_of_snapshot(state, &snapshot, new_events, last_action)? {
+            return Ok(result);
+        }
+
+        // Some new event needs to be applied before the events already included in the snapshot:
+        // we need to recompute the whole projection
+    }
+
+    let (private_events, last_action) = query_private_events_since::<T>(None)?;
+    let order_keys = compute_event_order_keys(&private_events, &BTreeMap::new());
+    let heads_lamport_clocks = heads_lamport_clocks(&private_events, &order_keys);
+    let last_applied_event = order_keys.values().max().cloned();
+    // Events with missing previous events may still move in the total order, so they must not be covered by a snapshot
+    let all_events_ordered = order_keys.len() == private_events.len();
+    let applied_events_count = order_keys.len();
+
+    let mut state = P::initial_state();
+    for (event_hash, signed_event) in order_private_events(private_events) {
+        state = P::apply(state, event_hash.into(), signed_event)?;
+    }
+
+    let new_snapshot = match (last_applied_event, last_action) {
+        (Some(last_applied_event), Some(last_action)) if all_events_ordered => Some(NewSnapshot {
+            last_applied_event,
+            heads_lamport_clocks,
+            last_action,
+            applied_events_count,
+        }),
+        _ => None,
+    };
+
+    Ok((state, new_snapshot))
+}
+
+/// Applies the given new events on top of the state of the given snapshot
+///
+/// Returns None if that's not possible because some of the new events
+/// need to be applied before the events already included in the snapshot
+fn apply_on_top_of_snapshot<T: PrivateEvent, P: Projection<T>>(
+    state: P,
+    snapshot: &ProjectionSnapshot,
+    new_events: BTreeMap<EntryHashB64, SignedEvent<T>>,
+    last_action: (u32, ActionHash),
+) -> ExternResult<Option<(P, Option<NewSnapshot>)>> {
+    // The lamport clocks of the new events can only be computed if all their previous events
+    // are either new events or heads of the events included in the snapshot
+    let all_previous_events_are_known = new_events.values().all(|event| {
+        event
+            .payload
+            .content
+            .previous_events
+            .iter()
+            .map(|previous_event| EntryHashB64::from(previous_event.clone()))
+            .all(|previous_event| {
+                new_events.contains_key(&previous_event)
+                    || snapshot.heads_lamport_clocks.contains_key(&previous_event)
+            })
+    });
+    if !all_previous_events_are_known {
+        return Ok(None);
+    }
+
+    let order_keys = compute_event_order_keys(&new_events, &snapshot.heads_lamport_clocks);
+
+    if !order_keys
+        .values()
+        .all(|order_key| order_key.gt(&snapshot.last_applied_event))
+    {
+        return Ok(None);
+    }
+
+    let applied_events_count = new_events.len();
+
+    let mut lamport_clocks = snapshot.heads_lamport_clocks.clone();
+    let mut previous_events: BTreeMap<EntryHashB64, Vec<EntryHash>> = snapshot
+        .heads_lamport_clocks
+        .keys()
+        .map(|head| (head.clone(), vec![]))
+        .collect();
+    for (event_hash, event) in &new_events {
+        previous_events.insert(
+            event_hash.clone(),
+            event.payload.content.previous_events.clone(),
+        );
+        if let Some(order_key) = order_keys.get(event_hash) {
+            lamport_clocks.insert(event_hash.clone(), order_key.lamport_clock);
+        }
+    }
+    let heads_lamport_clocks: BTreeMap<EntryHashB64, u32> = dag_heads(&previous_events)
+        .into_iter()
+        .filter_map(|head| lamport_clocks.get(&head).map(|clock| (head, *clock)))
+        .collect();
+
+    let last_applied_event = order_keys
+        .values()
+        .max()
+        .cloned()
+        .unwrap_or(snapshot.last_applied_event.clone());
+
+    let mut ordered_new_events: Vec<(EntryHashB64, SignedEvent<T>)> =
+        new_events.into_iter().collect();
+    ordered_new_events.sort_by_key(|(event_hash, _)| order_keys.get(event_hash).cloned());
+
+    let mut state = state;
+    for (event_hash, signed_event) in ordered_new_events {
+        state = P::apply(state, event_hash.into(), signed_event)?;
+    }
+
+    let new_snapshot = NewSnapshot {
+        last_applied_event,
+        heads_lamport_clocks,
+        last_action,
+        applied_events_count,
+    };
+
+    Ok(Some((state, Some(new_snapshot))))
+}
+
+/// The lamport clocks for the heads of the DAG formed by the given events
+fn heads_lamport_clocks<T>(
+    private_events: &BTreeMap<EntryHashB64, SignedEvent<T>>,
+    order_keys: &BTreeMap<EntryHashB64, EventOrderKey>,
+) -> BTreeMap<EntryHashB64, u32> {
+    let previous_events: BTreeMap<EntryHashB64, Vec<EntryHash>> = private_events
+        .iter()
+        .map(|(event_hash, event)| {
+            (
+                event_hash.clone(),
+                event.payload.content.previous_events.clone(),
+            )
+        })
+        .collect();
+
+    dag_heads(&previous_events)
+        .into_iter()
+        .filter_map(|head| {
+            order_keys
+                .get(&head)
+                .map(|order_key| (head, order_key.lamport_clock))
+        })
+        .collect()
+}
+
+/// Queries the private events committed in our source chain after the given action sequence,
+/// including the ones imported with an EventHistory
+///
+/// Also returns the sequence and the hash of the last action that included private events
+fn query_private_events_since<T: PrivateEvent>(
+    action_seq: Option<u32>,
+) -> ExternResult<(
+    BTreeMap<EntryHashB64, SignedEvent<T>>,
+    Option<(u32, ActionHash)>,
+)> {
+    let private_event_entry_type: EntryType = UnitEntryTypes::PrivateEvent.try_into()?;
+    let event_history_entry_type: EntryType = UnitEntryTypes::EventHistory.try_into()?;
+    let mut filter = ChainQueryFilter::new()
+        .entry_type(private_event_entry_type.clone())
+        .entry_type(event_history_entry_type.clone())
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    if let Some(action_seq) = action_seq {
+        filter = filter.sequence_range(ChainQueryFilterRange::ActionSeqRange(
+            action_seq + 1,
+            u32::MAX,
+        ));
+    }
+    let records = query(filter)?;
+
+    let last_action = records
+        .last()
+        .map(|r| (r.action().action_seq(), r.action_address().clone()));
+
+    let mut private_event_entries: BTreeMap<EntryHashB64, PrivateEventEntry> = BTreeMap::new();
+
+    for record in records {
+        let Some(entry) = record.entry().as_option().clone() else {
+            return Err(wasm_error!("PrivateEvents record contained no entry."));
+        };
+        if record
+            .action()
+            .entry_type()
+            .eq(&Some(&private_event_entry_type))
+        {
+            let Some(entry_hash) = record.action().entry_hash() else {
+                return Err(wasm_error!("PrivateEvents record contained no entry hash."));
+            };
+            let private_event_entry = PrivateEventEntry::try_from(entry)?;
+            private_event_entries.insert(entry_hash.clone().into(), private_event_entry);
+        } else if record
+            .action()
+            .entry_type()
+            .eq(&Some(&event_history_entry_type))
+        {
+            let mut event_history = EventHistory::try_from(entry)?;
+            private_event_entries.append(&mut event_history.events);
+        }
+    }
+
+    let private_events = private_event_entries
+        .into_iter()
+        .filter_map(|(entry_hash, entry)| {
+            private_event_entry_to_signed_event(entry)
+                .ok()
+                .map(|e| (entry_hash, e))
+        })
+        .collect();
+
+    Ok((private_events, last_action))
+}
+
+/// The snapshots of the given projection that haven't been deleted, from oldest to newest,
+/// with None for the snapshots with an older shape of ProjectionSnapshot that can't be decoded
+///
+/// Persisting a snapshot deletes all the previous snapshots of its projection, so only the actions from its latest
+/// snapshot that hasn't been deleted on are needed: the actions at the top of the source chain are queried
+/// in windows that double in size until one is found
+fn query_projection_snapshots(
+    projection_name: &str,
+) -> ExternResult<Vec<(ActionHash, Option<ProjectionSnapshot>)>> {
+    let chain_head_seq = agent_info()?.chain_head.1;
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::ProjectionSnapshot.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+
+    let mut window = SNAPSHOTS_WINDOW;
+    loop {
+        let first_action_seq = chain_head_seq.saturating_sub(window);
+        let records = query(
+            filter
+                .clone()
+                .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+                    first_action_seq,
+                    u32::MAX,
+                )),
+        )?;
+        let deleted_actions = query_deleted_actions_since(first_action_seq)?;
+
+        let snapshots: Vec<(ActionHash, Option<ProjectionSnapshot>)> = records
+            .into_iter()
+            .filter(|record| !deleted_actions.contains(record.action_address()))
+            .map(|record| {
+                (
+                    record.action_address().clone(),
+                    decode_projection_snapshot(&record),
+                )
+            })
+            .filter(|(_, snapshot)| {
+                snapshot
+                    .as_ref()
+                    .is_none_or(|snapshot| snapshot.projection_name.eq(projection_name))
+            })
+            .collect();
+
+        let found = snapshots.iter().any(|(_, snapshot)| snapshot.is_some());
+        if found || first_action_seq == 0 {
+            return Ok(snapshots);
+        }
+        window = window.saturating_mul(2);
+    }
+}
+
+fn decode_projection_snapshot(record: &Record) -> Option<ProjectionSnapshot> {
+    record
+        .entry()
+        .as_option()
+        .and_then(|entry| ProjectionSnapshot::try_from(entry.clone()).ok())
+}
+
+/// Returns the latest snapshot for the projection P that is still valid, with its decoded state
+///
+/// Snapshots that can't be used are skipped in favor of older ones
+fn query_latest_projection_snapshot<T: PrivateEvent, P: Projection<T>>(
+) -> ExternResult<Option<(ProjectionSnapshot, P)>> {
+    let projection_name = P::PROJECTION_NAME;
+
+    for (_, snapshot) in query_projection_snapshots(projection_name)?
+        .into_iter()
+        .rev()
+    {
+        // Snapshots committed with an older shape of ProjectionSnapshot can't be decoded
+        let Some(snapshot) = snapshot else {
+            warn!("Skipping a projection snapshot with an older shape.");
+            continue;
+        };
+
+        // The actions that the snapshot was computed from could have been
+        // rebased when the chain was committed: if so, the snapshot is not trustworthy
+        if !is_action_in_chain(snapshot.last_action_seq, &snapshot.last_action_hash)? {
+            warn!("Skipping snapshot for projection {projection_name}: its last action is not in the source chain.");
+            continue;
+        }
+
+        let Ok(state) = decode::<_, P>(snapshot.state.bytes()) else {
+            warn!("Skipping snapshot for projection {projection_name}: failed to deserialize its state.");
+            continue;
+        };
+
+        return Ok(Some((snapshot, state)));
+    }
+
+    Ok(None)
+}
+
+fn is_action_in_chain(action_seq: u32, action_hash: &ActionHash) -> ExternResult<bool> {
+    let filter = ChainQueryFilter::new().sequence_range(ChainQueryFilterRange::ActionSeqRange(
+        action_seq, action_seq,
+    ));
+    let records = query(filter)?;
+
+    Ok(records
+        .first()
+        .map(|r| r.action_address().eq(action_hash))
+        .unwrap_or(false))
+}
+
+/// Persists a snapshot of the given state
+fn create_projection_snapshot<T: PrivateEvent, P: Projection<T>>(
+    state: &P,
+    new_snapshot: NewSnapshot,
+) -> ExternResult<()> {
+    let bytes = encode(state).map_err(|err| wasm_error!(err))?;
+    let snapshot = ProjectionSnapshot {
+        projection_name: P::PROJECTION_NAME.to_string(),
+        last_applied_event: new_snapshot.last_applied_event,
+        heads_lamport_clocks: new_snapshot.heads_lamport_clocks,
+        last_action_seq: new_snapshot.last_action.0,
+        last_action_hash: new_snapshot.last_action.1,
+        state: SerializedBytes::from(UnsafeBytes::from(bytes)),
+    };
+    create_relaxed(EntryTypes::ProjectionSnapshot(snapshot))?;
+
+    Ok(())
+}
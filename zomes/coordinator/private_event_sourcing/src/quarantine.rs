@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use hdk::prelude::*;
+use private_event_sourcing_integrity::*;
+
+use crate::utils::{create_relaxed, delete_relaxed, query_deleted_actions_since};
+
+const DEFAULT_MAX_QUARANTINED_EVENTS_PER_AGENT: usize = 100;
+
+/// Maximum number of quarantined events kept for each provenance, after which their invalid events are not quarantined
+///
+/// Configurable at build time with the MAX_QUARANTINED_EVENTS_PER_AGENT environment variable
+pub fn max_quarantined_events_per_agent() -> usize {
+    std::option_env!("MAX_QUARANTINED_EVENTS_PER_AGENT")
+        .and_then(|max| max.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_QUARANTINED_EVENTS_PER_AGENT)
+}
+
+/// The quarantined events, queried once when the first event is quarantined and kept up to date afterwards
+#[derive(Default)]
+pub struct Quarantine {
+    records: Option<Vec<QuarantinedEventRecord>>,
+    /// Number of invalid events that were not quarantined for each provenance because it had reached the maximum,
+    /// pending to be committed with `commit_overflow`
+    overflow: BTreeMap<AgentPubKey, u32>,
+}
+
+impl Quarantine {
+    /// Records the given invalid event in the quarantine, unless it was already quarantined for the same provenance
+    ///
+    /// If the provenance already has the maximum number of quarantined events, the event is only counted,
+    /// so that a misbehaving agent can't make us commit an unbounded number of entries
+    ///
+    /// Returns whether the event is in the quarantine
+    pub fn quarantine_private_event(
+        &mut self,
+        event_hash: &EntryHashB64,
+        private_event_entry: &PrivateEventEntry,
+        provenance: &AgentPubKey,
+        reason: String,
+    ) -> ExternResult<bool> {
+        if self.records.is_none() {
+            self.records = Some(query_quarantined_events(())?);
+        }
+        let records = self.records.get_or_insert_with(Vec::new);
+
+        let already_quarantined = records
+            .iter()
+            .any(|q| q.event_hash.eq(event_hash) && q.quarantined_event.provenance.eq(provenance));
+        if already_quarantined {
+            return Ok(true);
+        }
+
+        let provenance_records_count = records
+            .iter()
+            .filter(|q| q.quarantined_event.provenance.eq(provenance))
+            .count();
+        if provenance_records_count >= max_quarantined_events_per_agent() {
+            let overflow = self.overflow.entry(provenance.clone()).or_default();
+            *overflow = overflow.saturating_add(1);
+            warn!(
+                "Not quarantining PrivateEvent {event_hash} sent by agent {provenance}, which already has {provenance_records_count} quarantined events: {reason}"
+            );
+            return Ok(false);
+        }
+
+        warn!("Quarantining PrivateEvent {event_hash} sent by agent {provenance}: {reason}");
+        let quarantined_event = QuarantinedEvent {
+            event: private_event_entry.clone(),
+            provenance: provenance.clone(),
+            reason,
+            timestamp: sys_time()?,
+            overflow: 0,
+        };
+        let action_hash = create_relaxed(EntryTypes::QuarantinedEvent(quarantined_event.clone()))?;
+        records.push(QuarantinedEventRecord {
+            action_hash,
+            event_hash: event_hash.clone(),
+            quarantined_event,
+        });
+
+        Ok(true)
+    }
+
+    /// Adds the invalid events that were not quarantined to the overflow of the latest quarantined event of their provenance
+    ///
+    /// The latest quarantined event is replaced at most once per provenance, however many events overflowed
+    pub fn commit_overflow(&mut self) -> ExternResult<()> {
+        let overflow = std::mem::take(&mut self.overflow);
+        let Some(records) = self.records.as_mut() else {
+            return Ok(());
+        };
+
+        for (provenance, count) in overflow {
+            let Some(latest_record) = records
+                .iter_mut()
+                .rev()
+                .find(|q| q.quarantined_event.provenance.eq(&provenance))
+            else {
+                // There is no quarantined event to add the overflow to if the maximum is 0
+                continue;
+            };
+
+            let mut quarantined_event = latest_record.quarantined_event.clone();
+            quarantined_event.overflow = quarantined_event.overflow.saturating_add(count);
+            warn!(
+                "Agent {provenance} has sent us {} invalid events that were not quarantined.",
+                quarantined_event.overflow
+            );
+            delete_relaxed(latest_record.action_hash.clone())?;
+            latest_record.action_hash =
+                create_relaxed(EntryTypes::QuarantinedEvent(quarantined_event.clone()))?;
+            latest_record.quarantined_event = quarantined_event;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuarantinedEventRecord {
+    /// The hash of the action that created the quarantined event, used to purge it
+    pub action_hash: ActionHash,
+    pub event_hash: EntryHashB64,
+    pub quarantined_event: QuarantinedEvent,
+}
+
+/// All the quarantined events that haven't been purged yet
+///
+/// Their `overflow` is the number of invalid events that their provenance sent us afterwards and that were not quarantined
+#[hdk_extern]
+pub fn query_quarantined_events() -> ExternResult<Vec<QuarantinedEventRecord>> {
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::QuarantinedEvent.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query(filter)?;
+
+    let purged_actions = match records.first() {
+        Some(first_record) => query_deleted_actions_since(first_record.action().action_seq())?,
+        None => BTreeSet::new(),
+    };
+
+    records
+        .into_iter()
+        .filter(|r| !purged_actions.contains(r.action_address()))
+        .map(|r| {
+            let Some(entry) = r.entry().as_option().clone() else {
+                return Err(wasm_error!("QuarantinedEvent record contained no entry."));
+            };
+            let quarantined_event = QuarantinedEvent::try_from(entry)?;
+            Ok(QuarantinedEventRecord {
+                action_hash: r.action_address().clone(),
+                event_hash: EntryHashB64::from(hash_entry(&quarantined_event.event)?),
+                quarantined_event,
+            })
+        })
+        .collect()
+}
+
+/// Deletes the given quarantined events
+#[hdk_extern]
+pub fn purge_quarantined_events(action_hashes: Vec<ActionHash>) -> ExternResult<()> {
+    let quarantined_actions: BTreeSet<ActionHash> = query_quarantined_events(())?
+        .into_iter()
+        .map(|q| q.action_hash)
+        .collect();
+
+    for action_hash in action_hashes {
+        if !quarantined_actions.contains(&action_hash) {
+            return Err(wasm_error!(
+                "Action {} is not a quarantined event.",
+                action_hash
+            ));
+        }
+        delete_entry(DeleteInput::new(action_hash, ChainTopOrdering::Relaxed))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+
+use hdk::prelude::*;
+use private_event_sourcing_integrity::*;
+
+use crate::{
+    decode_private_event, missing_dependencies::is_recipient_for,
+    outgoing_messages::OutgoingMessages, query_event_histories, query_my_linked_devices,
+    query_private_event_entries, utils::create_relaxed, validate_clock, ClockTicker,
+    ImportedEntries, PrivateEvent, ReceivedEntryOutcome,
+};
+
+/// Creates read receipts for the given events, and sends them to their authors and recipients
+///
+/// Unlike acknowledgements, which are created automatically when the events are received,
+/// read receipts are only created when the app explicitly marks the events as read
+///
+/// Fails without creating any read receipt if they can't be sent to some of their recipients,
+/// so that the events can be marked as read again
+pub fn mark_events_as_read<T: PrivateEvent>(
+    events_hashes: BTreeSet<EntryHash>,
+) -> ExternResult<()> {
+    let private_event_entries = query_private_event_entries(())?;
+    let mut clock_ticker = ClockTicker::new()?;
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+    let my_read_events: BTreeSet<EntryHash> = query_read_receipt_entries(())?
+        .into_iter()
+        .filter(|r| r.0.author.eq(&my_pub_key))
+        .map(|r| r.0.payload.content.private_event_hash)
+        .collect();
+    let my_linked_devices = query_my_linked_devices()?;
+
+    let mut outgoing_messages = OutgoingMessages::default();
+    let mut all_recipients: BTreeSet<AgentPubKey> = BTreeSet::new();
+
+    for event_hash in events_hashes {
+        let Some(private_event_entry) =
+            private_event_entries.get(&EntryHashB64::from(event_hash.clone()))
+        else {
+            return Err(wasm_error!("Private event {} not found.", event_hash));
+        };
+        if private_event_entry.0.author.eq(&my_pub_key) {
+            continue; // We are the author, no need to create a read receipt
+        }
+        if my_read_events.contains(&event_hash) {
+            // We have already created a read receipt for this event
+            continue;
+        }
+
+        let private_event = decode_private_event::<T>(&private_event_entry.0.payload.content)?;
+
+        let mut recipients = private_event.recipients(
+            event_hash.clone(),
+            private_event_entry.0.author.clone(),
+            private_event_entry.0.payload.timestamp,
+        )?;
+        recipients.insert(private_event_entry.0.author.clone());
+        recipients.append(&mut my_linked_devices.clone());
+        recipients.remove(&my_pub_key);
+
+        let signed_entry = clock_ticker.build_signed_entry(ReadReceiptContent {
+            private_event_hash: event_hash.clone(),
+        })?;
+        let read_receipt = ReadReceipt(signed_entry);
+
+        info!("Creating read receipt for entry {}.", event_hash);
+        create_relaxed(EntryTypes::ReadReceipt(read_receipt.clone()))?;
+
+        for recipient in &recipients {
+            outgoing_messages.add_read_receipt(recipient, &read_receipt);
+        }
+        all_recipients.append(&mut recipients);
+    }
+
+    let sent_recipients = outgoing_messages.send()?;
+    let failed_recipients: BTreeSet<AgentPubKey> = all_recipients
+        .difference(&sent_recipients)
+        .cloned()
+        .collect();
+    if !failed_recipients.is_empty() {
+        return Err(wasm_error!(
+            "Failed to send the read receipts to agents {:?}.",
+            failed_recipients
+        ));
+    }
+
+    Ok(())
+}
+
+/// Adds our read receipt for the given event, if we have read it, to the messages to be sent to the given recipient
+pub fn add_read_receipt_for_event_to_recipient(
+    outgoing_messages: &mut OutgoingMessages,
+    event_hash: &EntryHashB64,
+    recipient: &AgentPubKey,
+) -> ExternResult<()> {
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+    let my_read_receipt = query_read_receipt_entries(())?.into_iter().find(|r| {
+        r.0.author.eq(&my_pub_key)
+            && r.0
+                .payload
+                .content
+                .private_event_hash
+                .eq(&EntryHash::from(event_hash.clone()))
+    });
+    if let Some(read_receipt) = my_read_receipt {
+        info!(
+            "Sending read receipt for event {} to agent {}.",
+            event_hash, recipient
+        );
+        outgoing_messages.add_read_receipt(recipient, &read_receipt);
+    }
+
+    Ok(())
+}
+
+pub fn receive_read_receipts<T: PrivateEvent>(
+    current_events: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    provenance: AgentPubKey,
+    read_receipts: Vec<ReadReceipt>,
+    report: &mut ImportedEntries,
+) -> ExternResult<()> {
+    let current_read_receipts = query_read_receipt_entries(())?;
+    let my_linked_devices = query_my_linked_devices()?;
+
+    for read_receipt in read_receipts {
+        let entry_hash = EntryHashB64::from(hash_entry(&read_receipt)?);
+        let outcome = receive_read_receipt::<T>(
+            current_events,
+            &current_read_receipts,
+            &my_linked_devices,
+            &provenance,
+            read_receipt,
+        );
+        report.record(entry_hash, outcome);
+    }
+
+    Ok(())
+}
+
+fn receive_read_receipt<T: PrivateEvent>(
+    current_events: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    current_read_receipts: &Vec<ReadReceipt>,
+    my_linked_devices: &BTreeSet<AgentPubKey>,
+    provenance: &AgentPubKey,
+    read_receipt: ReadReceipt,
+) -> ExternResult<ReceivedEntryOutcome> {
+    if current_read_receipts.contains(&read_receipt) {
+        // We already have this read receipt committed
+        return Ok(ReceivedEntryOutcome::AlreadyKnown);
+    }
+
+    if !read_receipt.0.verify()? {
+        return Ok(ReceivedEntryOutcome::Invalid(String::from(
+            "Invalid read receipt: invalid signature.",
+        )));
+    }
+
+    if let Err(reason) = validate_clock(&read_receipt.0.payload)? {
+        return Ok(ReceivedEntryOutcome::Invalid(format!(
+            "Invalid read receipt: {reason}"
+        )));
+    }
+
+    let event_hash = read_receipt.0.payload.content.private_event_hash.clone();
+    let private_event_entry = current_events.get(&EntryHashB64::from(event_hash.clone()));
+
+    if let Some(private_event_entry) = private_event_entry {
+        if !is_recipient_for::<T>(
+            &read_receipt.0.author,
+            &EntryHashB64::from(event_hash.clone()),
+            private_event_entry,
+            my_linked_devices,
+        )? {
+            return Ok(ReceivedEntryOutcome::Invalid(format!(
+                "Invalid read receipt: its author is not a recipient of the event {event_hash}."
+            )));
+        }
+        info!(
+            "Received read receipt for entry {} from agent {}.",
+            event_hash, provenance,
+        );
+        create_relaxed(EntryTypes::ReadReceipt(read_receipt))?;
+        Ok(ReceivedEntryOutcome::Accepted)
+    } else {
+        create_relaxed(EntryTypes::AwaitingDependencies(
+            AwaitingDependencies::ReadReceipt { read_receipt },
+        ))?;
+        Ok(ReceivedEntryOutcome::Deferred)
+    }
+}
+
+/// The events read by each agent, with the time of their first read receipt
+pub fn compute_read_receipts_by_agents(
+    read_receipts: &Vec<ReadReceipt>,
+) -> BTreeMap<AgentPubKey, BTreeMap<EntryHash, Timestamp>> {
+    let mut all_read_receipts: BTreeMap<AgentPubKey, BTreeMap<EntryHash, Timestamp>> =
+        BTreeMap::new();
+
+    for read_receipt in read_receipts {
+        let timestamp = read_receipt.0.payload.timestamp;
+        all_read_receipts
+            .entry(read_receipt.0.author.clone())
+            .or_default()
+            .entry(read_receipt.0.payload.content.private_event_hash.clone())
+            .and_modify(|first_read| {
+                if timestamp < *first_read {
+                    *first_read = timestamp;
+                }
+            })
+            .or_insert(timestamp);
+    }
+
+    all_read_receipts
+}
+
+/// The agents that have read the given event, with the time at which they read it
+#[hdk_extern]
+pub fn query_read_receipts_for_event(
+    event_hash: EntryHash,
+) -> ExternResult<BTreeMap<AgentPubKeyB64, Timestamp>> {
+    let read_receipts = compute_read_receipts_by_agents(&query_read_receipt_entries(())?);
+
+    Ok(read_receipts
+        .into_iter()
+        .filter_map(|(agent, read_events)| {
+            read_events
+                .get(&event_hash)
+                .map(|timestamp| (agent.into(), *timestamp))
+        })
+        .collect())
+}
+
+/// The events that the given agent has read, with the time at which they read them
+#[hdk_extern]
+pub fn query_events_read_by_agent(
+    agent: AgentPubKey,
+) -> ExternResult<BTreeMap<EntryHashB64, Timestamp>> {
+    let mut read_receipts = compute_read_receipts_by_agents(&query_read_receipt_entries(())?);
+
+    Ok(read_receipts
+        .remove(&agent)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(event_hash, timestamp)| (event_hash.into(), timestamp))
+        .collect())
+}
+
+#[hdk_extern]
+pub fn query_read_receipt_entries() -> ExternResult<Vec<ReadReceipt>> {
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::ReadReceipt.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query(filter)?;
+    let mut read_receipts = records
+        .into_iter()
+        .map(|r| {
+            let Some(entry) = r.entry().as_option().clone() else {
+                return Err(wasm_error!("ReadReceipts record contained no entry."));
+            };
+            let entry = ReadReceipt::try_from(entry)?;
+            Ok(entry)
+        })
+        .collect::<ExternResult<Vec<ReadReceipt>>>()?;
+
+    let mut histories = query_event_histories()?;
+
+    for history in &mut histories {
+        read_receipts.append(&mut history.read_receipts);
+    }
+
+    Ok(read_receipts)
+}
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+
+use hdk::prelude::*;
+use private_event_sourcing_integrity::*;
+
+use crate::{
+    missing_dependencies::is_recipient_for, outgoing_messages::OutgoingMessages,
+    query_event_histories, query_my_linked_devices, utils::create_relaxed, validate_clock,
+    ClockTicker, ImportedEntries, PrivateEvent, ReceivedEntryOutcome,
+};
+
+/// Rejects an invalid event that we received, adding the rejection to the messages to be sent back
+/// to its author and to the agent that sent it to us, so that they stop sending it to us
+///
+/// If we had already rejected the event, our existing rejection is sent again instead of creating a new one
+///
+/// The reason is truncated to `MAX_REJECTION_REASON_BYTES`
+///
+/// Callers only reject the events that they could quarantine, so that a misbehaving agent can't make us
+/// commit an unbounded number of rejections either
+pub fn reject_private_event(
+    outgoing_messages: &mut OutgoingMessages,
+    clock_ticker: &mut ClockTicker,
+    event_hash: &EntryHashB64,
+    private_event_entry: &PrivateEventEntry,
+    provenance: &AgentPubKey,
+    reason: String,
+) -> ExternResult<()> {
+    let rejection = match query_my_rejection_for(event_hash)? {
+        Some(rejection) => rejection,
+        None => {
+            let signed_entry = clock_ticker.build_signed_entry(RejectionContent {
+                event_hash: event_hash.clone().into(),
+                reason: truncate_reason(reason),
+            })?;
+            let rejection = Rejection(signed_entry);
+            create_relaxed(EntryTypes::Rejection(rejection.clone()))?;
+            rejection
+        }
+    };
+
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+
+    for recipient in [&private_event_entry.0.author, provenance] {
+        if recipient.ne(&my_pub_key) {
+            info!(
+                "Sending rejection for event {} to agent {}.",
+                event_hash, recipient
+            );
+            outgoing_messages.add_rejection(recipient, &rejection);
+        }
+    }
+
+    Ok(())
+}
+
+fn truncate_reason(mut reason: String) -> String {
+    if reason.len() > MAX_REJECTION_REASON_BYTES {
+        let mut end = MAX_REJECTION_REASON_BYTES;
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        reason.truncate(end);
+    }
+    reason
+}
+
+pub fn query_my_rejection_for(event_hash: &EntryHashB64) -> ExternResult<Option<Rejection>> {
+    let rejections = query_rejection_entries(())?;
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+    Ok(rejections.into_iter().find(|r| {
+        r.0.author.eq(&my_pub_key)
+            && r.0
+                .payload
+                .content
+                .event_hash
+                .eq(&EntryHash::from(event_hash.clone()))
+    }))
+}
+
+pub fn receive_rejections<T: PrivateEvent>(
+    current_events: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    provenance: AgentPubKey,
+    rejections: Vec<Rejection>,
+    report: &mut ImportedEntries,
+) -> ExternResult<()> {
+    let current_rejections = query_rejection_entries(())?;
+    let my_linked_devices = query_my_linked_devices()?;
+
+    for rejection in rejections {
+        let entry_hash = EntryHashB64::from(hash_entry(&rejection)?);
+        let outcome = receive_rejection::<T>(
+            current_events,
+            &current_rejections,
+            &my_linked_devices,
+            &provenance,
+            rejection,
+        );
+        report.record(entry_hash, outcome);
+    }
+
+    Ok(())
+}
+
+/// Rejections are only accepted from the author or the recipients of the rejected event
+///
+/// The rejections of our own devices don't depend on the rejected event: they never committed it
+fn receive_rejection<T: PrivateEvent>(
+    current_events: &BTreeMap<EntryHashB64, PrivateEventEntry>,
+    current_rejections: &Vec<Rejection>,
+    my_linked_devices: &BTreeSet<AgentPubKey>,
+    provenance: &AgentPubKey,
+    rejection: Rejection,
+) -> ExternResult<ReceivedEntryOutcome> {
+    if current_rejections.contains(&rejection) {
+        // We already have this rejection committed
+        return Ok(ReceivedEntryOutcome::AlreadyKnown);
+    }
+
+    if !rejection.0.verify()? {
+        return Ok(ReceivedEntryOutcome::Invalid(String::from(
+            "Invalid rejection: invalid signature.",
+        )));
+    }
+
+    if rejection.0.payload.content.reason.len() > MAX_REJECTION_REASON_BYTES {
+        return Ok(ReceivedEntryOutcome::Invalid(format!(
+            "Invalid rejection: its reason is longer than {MAX_REJECTION_REASON_BYTES} bytes."
+        )));
+    }
+
+    if let Err(reason) = validate_clock(&rejection.0.payload)? {
+        return Ok(ReceivedEntryOutcome::Invalid(format!(
+            "Invalid rejection: {reason}"
+        )));
+    }
+
+    let event_hash = EntryHashB64::from(rejection.0.payload.content.event_hash.clone());
+    let my_pub_key = agent_info()?.agent_initial_pubkey;
+    let from_my_devices =
+        rejection.0.author.eq(&my_pub_key) || my_linked_devices.contains(&rejection.0.author);
+
+    let resolved = if from_my_devices {
+        true
+    } else if let Some(private_event_entry) = current_events.get(&event_hash) {
+        if !is_recipient_for::<T>(
+            &rejection.0.author,
+            &event_hash,
+            private_event_entry,
+            my_linked_devices,
+        )? {
+            return Ok(ReceivedEntryOutcome::Invalid(format!(
+                "Invalid rejection: its author is not a recipient of the event {event_hash}."
+            )));
+        }
+        true
+    } else {
+        false
+    };
+
+    if !resolved {
+        create_relaxed(EntryTypes::AwaitingDependencies(
+            AwaitingDependencies::Rejection { rejection },
+        ))?;
+        return Ok(ReceivedEntryOutcome::Deferred);
+    }
+
+    warn!(
+        "Received rejection for entry {} by agent {} from agent {}: {}.",
+        rejection.0.payload.content.event_hash,
+        rejection.0.author,
+        provenance,
+        rejection.0.payload.content.reason
+    );
+    create_relaxed(EntryTypes::Rejection(rejection))?;
+
+    Ok(ReceivedEntryOutcome::Accepted)
+}
+
+/// The events rejected by each agent, with their first rejection
+pub fn compute_rejections_by_agents(
+    rejections: &Vec<Rejection>,
+) -> BTreeMap<AgentPubKey, BTreeMap<EntryHash, Rejection>> {
+    let mut all_rejections: BTreeMap<AgentPubKey, BTreeMap<EntryHash, Rejection>> = BTreeMap::new();
+
+    for rejection in rejections {
+        all_rejections
+            .entry(rejection.0.author.clone())
+            .or_default()
+            .entry(rejection.0.payload.content.event_hash.clone())
+            .and_modify(|first_rejection| {
+                if rejection.0.payload.timestamp < first_rejection.0.payload.timestamp {
+                    *first_rejection = rejection.clone();
+                }
+            })
+            .or_insert(rejection.clone());
+    }
+
+    all_rejections
+}
+
+#[hdk_extern]
+pub fn query_rejection_entries() -> ExternResult<Vec<Rejection>> {
+    let filter = ChainQueryFilter::new()
+        .entry_type(UnitEntryTypes::Rejection.try_into()?)
+        .include_entries(true)
+        .action_type(ActionType::Create);
+    let records = query(filter)?;
+    let mut rejections = records
+        .into_iter()
+        .map(|r| {
+            let Some(entry) = r.entry().as_option().clone() else {
+                return Err(wasm_error!("Rejections record contained no entry."));
+            };
+            let entry = Rejection::try_from(entry)?;
+            Ok(entry)
+        })
+        .collect::<ExternResult<Vec<Rejection>>>()?;
+
+    let mut histories = query_event_histories()?;
+
+    for history in &mut histories {
+        rejections.append(&mut history.rejections);
+    }
+
+    Ok(rejections)
+}
@@ -0,0 +1,221 @@
+use hdk::prelude::*;
+
+use crate::SendAttempts;
+
+/// When to resend an event to the recipients that haven't acknowledged it yet
+///
+/// Build it with `ResendPolicy::new`, which validates it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "ResendPolicyFields")]
+pub struct ResendPolicy {
+    initial_delay_ms: i64,
+    backoff_factor: f64,
+    max_delay_ms: i64,
+    max_attempts: u32,
+    jitter: f64,
+}
+
+/// The unvalidated fields of a ResendPolicy, so that deserialized policies are validated as well
+#[derive(Deserialize)]
+struct ResendPolicyFields {
+    initial_delay_ms: i64,
+    backoff_factor: f64,
+    max_delay_ms: i64,
+    max_attempts: u32,
+    jitter: f64,
+}
+
+impl TryFrom<ResendPolicyFields> for ResendPolicy {
+    type Error = String;
+
+    fn try_from(fields: ResendPolicyFields) -> Result<Self, Self::Error> {
+        ResendPolicy::new(
+            fields.initial_delay_ms,
+            fields.backoff_factor,
+            fields.max_delay_ms,
+            fields.max_attempts,
+            fields.jitter,
+        )
+        .map_err(|err| format!("{err:?}"))
+    }
+}
+
+impl Default for ResendPolicy {
+    fn default() -> Self {
+        ResendPolicy {
+            initial_delay_ms: 1000 * 60 * 5,   // 5 minutes
+            backoff_factor: 2.0,               // 5 minutes, 10 minutes, 20 minutes...
+            max_delay_ms: 1000 * 60 * 60 * 24, // 1 day
+            max_attempts: 10,
+            jitter: 0.1,
+        }
+    }
+}
+
+impl ResendPolicy {
+    /// Builds a resend policy, failing if any of its parameters is out of range
+    ///
+    /// - `initial_delay_ms`: delay between the first send and the first resend
+    /// - `backoff_factor`: factor by which the delay is multiplied after every attempt, at least 1
+    /// - `max_delay_ms`: upper bound for the delay between two attempts, at least `initial_delay_ms`
+    /// - `max_attempts`: maximum number of times an event is sent to each recipient, including the first one
+    /// - `jitter`: fraction of the delay (between 0 and 1) that is pseudo-randomly added to it,
+    ///   so that the resends from different agents and for different events are spread out
+    pub fn new(
+        initial_delay_ms: i64,
+        backoff_factor: f64,
+        max_delay_ms: i64,
+        max_attempts: u32,
+        jitter: f64,
+    ) -> ExternResult<Self> {
+        if initial_delay_ms < 0 {
+            return Err(wasm_error!(
+                "Invalid resend policy: initial_delay_ms must not be negative, but was {initial_delay_ms}."
+            ));
+        }
+        if !backoff_factor.is_finite() || backoff_factor < 1.0 {
+            return Err(wasm_error!(
+                "Invalid resend policy: backoff_factor must be a finite number of at least 1, but was {backoff_factor}."
+            ));
+        }
+        if max_delay_ms < initial_delay_ms {
+            return Err(wasm_error!(
+                "Invalid resend policy: max_delay_ms must be at least initial_delay_ms, but was {max_delay_ms}."
+            ));
+        }
+        if !jitter.is_finite() || !(0.0..=1.0).contains(&jitter) {
+            return Err(wasm_error!(
+                "Invalid resend policy: jitter must be between 0 and 1, but was {jitter}."
+            ));
+        }
+
+        Ok(ResendPolicy {
+            initial_delay_ms,
+            backoff_factor,
+            max_delay_ms,
+            max_attempts,
+            jitter,
+        })
+    }
+
+    pub fn initial_delay_ms(&self) -> i64 {
+        self.initial_delay_ms
+    }
+
+    pub fn backoff_factor(&self) -> f64 {
+        self.backoff_factor
+    }
+
+    pub fn max_delay_ms(&self) -> i64 {
+        self.max_delay_ms
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Delay to wait after the given number of attempts before attempting to send the event again
+    ///
+    /// The jitter is derived from the event and the recipient, so that it doesn't change between scheduled runs
+    pub fn delay_ms(&self, attempts: u32, event_hash: &EntryHash, recipient: &AgentPubKey) -> i64 {
+        let exponent = attempts.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = (self.initial_delay_ms as f64) * self.backoff_factor.powi(exponent);
+        let delay = delay.min(self.max_delay_ms as f64);
+
+        let seed = event_hash
+            .get_raw_32()
+            .iter()
+            .chain(recipient.get_raw_32().iter())
+            .fold(attempts, |seed, byte| {
+                seed.wrapping_mul(31).wrapping_add(*byte as u32)
+            });
+        let jitter_fraction = (seed % 1000) as f64 / 1000.0;
+
+        (delay * (1.0 + self.jitter * jitter_fraction)) as i64
+    }
+
+    /// Whether all the attempts to send the event to the recipient have been exhausted
+    pub fn is_exhausted(&self, send_attempts: &SendAttempts) -> bool {
+        send_attempts.attempts >= self.max_attempts
+    }
+
+    /// Whether the event should be sent to the recipient now, given the previous attempts to send it
+    pub fn should_send(
+        &self,
+        send_attempts: Option<&SendAttempts>,
+        event_hash: &EntryHash,
+        recipient: &AgentPubKey,
+        now: Timestamp,
+    ) -> bool {
+        let Some(send_attempts) = send_attempts else {
+            // Never sent
+            return true;
+        };
+        if self.is_exhausted(send_attempts) {
+            return false;
+        }
+        let elapsed_ms = now.as_millis() - send_attempts.last_sent.as_millis();
+
+        elapsed_ms > self.delay_ms(send_attempts.attempts, event_hash, recipient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_hash() -> EntryHash {
+        EntryHash::from_raw_36(vec![1; 36])
+    }
+
+    fn recipient() -> AgentPubKey {
+        AgentPubKey::from_raw_36(vec![2; 36])
+    }
+
+    #[test]
+    fn delay_grows_exponentially_up_to_the_max_delay() {
+        let policy = ResendPolicy::new(1000, 2.0, 5000, 10, 0.0).unwrap();
+
+        assert_eq!(policy.delay_ms(1, &event_hash(), &recipient()), 1000);
+        assert_eq!(policy.delay_ms(2, &event_hash(), &recipient()), 2000);
+        assert_eq!(policy.delay_ms(3, &event_hash(), &recipient()), 4000);
+        assert_eq!(policy.delay_ms(4, &event_hash(), &recipient()), 5000);
+        assert_eq!(policy.delay_ms(u32::MAX, &event_hash(), &recipient()), 5000);
+    }
+
+    #[test]
+    fn jitter_is_bounded_and_stable() {
+        let policy = ResendPolicy::new(1000, 1.0, 1000, 10, 0.5).unwrap();
+
+        let delay = policy.delay_ms(1, &event_hash(), &recipient());
+        assert!((1000..=1500).contains(&delay));
+        assert_eq!(delay, policy.delay_ms(1, &event_hash(), &recipient()));
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        assert!(ResendPolicy::new(-1, 2.0, 1000, 10, 0.1).is_err());
+        assert!(ResendPolicy::new(1000, f64::NAN, 1000, 10, 0.1).is_err());
+        assert!(ResendPolicy::new(1000, 0.5, 1000, 10, 0.1).is_err());
+        assert!(ResendPolicy::new(1000, 2.0, 500, 10, 0.1).is_err());
+        assert!(ResendPolicy::new(1000, 2.0, 1000, 10, -0.1).is_err());
+        assert!(ResendPolicy::new(1000, 2.0, 1000, 10, f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn default_policy_is_valid() {
+        let default = ResendPolicy::default();
+        assert!(ResendPolicy::new(
+            default.initial_delay_ms(),
+            default.backoff_factor(),
+            default.max_delay_ms(),
+            default.max_attempts(),
+            default.jitter(),
+        )
+        .is_ok());
+    }
+}
@@ -2,33 +2,36 @@ use std::collections::BTreeMap;
 
 use hdk::prelude::*;
 use private_event_sourcing_integrity::{
-    Acknowledgement, EntryTypes, EventSentToRecipients, EventSentToRecipientsContent, Message,
-    PrivateEventEntry, SignedEntry,
+    Acknowledgement, EventSentToRecipients, EventSentToRecipientsContent, Message,
+    PrivateEventEntry, ReadReceipt,
 };
 
 use crate::{
-    compute_acknowledgements_by_agents, create_acknowledgements_for,
+    compute_acknowledgements_by_agents, compute_rejections_by_agents, create_acknowledgements_for,
+    decode_private_event,
     events_sent_to_recipients::{
         compute_events_sent_to_recipients, query_events_sent_to_recipients_entries,
     },
+    outgoing_messages::{commit_events_sent_to_recipients, OutgoingMessages},
     query_acknowledgement_entries, query_my_linked_devices, query_private_event_entries,
-    query_private_event_entry, send_async_message,
-    utils::create_relaxed,
+    query_private_event_entry, query_read_receipt_entries, query_rejection_entries, ClockTicker,
     PrivateEvent, PrivateEventSourcingRemoteSignal,
 };
 
-const INTERVAL_RESEND_MS: i64 = 1000 * 60 * 60 * 24 * 1000; // 1000 days
-
 pub fn resend_events_if_necessary<T: PrivateEvent>(
     entries: &BTreeMap<EntryHashB64, PrivateEventEntry>,
     events_sent_to_recipients_entries: &Vec<EventSentToRecipients>,
     acknowledgements_entries: &Vec<Acknowledgement>,
+    clock_ticker: &mut ClockTicker,
 ) -> ExternResult<()> {
     debug!("[send_events] Sending events to linked devices and recipients if necessary.");
 
+    let read_receipts_entries = query_read_receipt_entries(())?;
+
     let events_sent_to_recipients =
         compute_events_sent_to_recipients(events_sent_to_recipients_entries.clone())?;
     let acknowledgements = compute_acknowledgements_by_agents(acknowledgements_entries)?;
+    let rejections = compute_rejections_by_agents(&query_rejection_entries(())?);
 
     let my_linked_devices = query_my_linked_devices()?;
 
@@ -36,9 +39,11 @@ pub fn resend_events_if_necessary<T: PrivateEvent>(
 
     let my_pub_key = agent_info()?.agent_initial_pubkey;
 
+    let mut outgoing_messages = OutgoingMessages::default();
+    let mut new_events_sent_to_recipients: Vec<EventSentToRecipients> = vec![];
+
     for (event_hash, private_event_entry) in entries {
-        let private_event = T::try_from(private_event_entry.0.payload.content.event.clone())
-            .map_err(|_err| wasm_error!("Failed to deserialize private event"))?;
+        let private_event = decode_private_event::<T>(&private_event_entry.0.payload.content)?;
 
         // For each event, get the recipients
         let recipients_result = private_event.recipients(
@@ -62,23 +67,31 @@ pub fn resend_events_if_necessary<T: PrivateEvent>(
                     .get(recipient)
                     .cloned()
                     .unwrap_or_default()
-                    .contains(&EntryHash::from(event_hash.clone()))
+                    .contains_key(&EntryHash::from(event_hash.clone()))
+            })
+            // Filter out the recipients that rejected the event, they will never accept it
+            .filter(|recipient| {
+                !rejections.get(recipient).is_some_and(|rejected| {
+                    rejected.contains_key(&EntryHash::from(event_hash.clone()))
+                })
             })
             .collect();
 
-        // If the event was never sent or the last time it was sent was more than X, send it
+        // If the event was never sent or its resend policy says it's time to send it again, send it
+        let resend_policy = private_event.resend_policy();
+        let events_sent_for_this_entry = events_sent_to_recipients
+            .get(&EntryHash::from(event_hash.clone()))
+            .cloned()
+            .unwrap_or_default();
         let recipients_to_send: BTreeSet<AgentPubKey> = recipients_without_acknowledgement
             .into_iter()
             .filter(|recipient| {
-                let events_sent_for_recipient = events_sent_to_recipients
-                    .get(&EntryHash::from(event_hash.clone()))
-                    .cloned()
-                    .unwrap_or_default();
-
-                match events_sent_for_recipient.get(recipient) {
-                    Some(last_sent) => now.as_millis() - last_sent.as_millis() > INTERVAL_RESEND_MS,
-                    None => true,
-                }
+                resend_policy.should_send(
+                    events_sent_for_this_entry.get(recipient),
+                    &EntryHash::from(event_hash.clone()),
+                    recipient,
+                    now,
+                )
             })
             .collect();
 
@@ -92,22 +105,34 @@ pub fn resend_events_if_necessary<T: PrivateEvent>(
                 event_hash: event_hash.clone().into(),
                 recipients: recipients_to_send.clone(),
             };
-            let signed = SignedEntry::build(content)?;
+            let signed = clock_ticker.build_signed_entry(content)?;
             let event_sent_to_recipients = EventSentToRecipients(signed);
 
-            let acknowledgements_for_this_entry = acknowledgements_entries
+            let acknowledgements_for_this_entry: Vec<&Acknowledgement> = acknowledgements_entries
                 .iter()
                 .filter(|ack| {
                     ack.0
+                        .payload
+                        .content
+                        .acknowledges(&EntryHash::from(event_hash.clone()))
+                })
+                .collect();
+
+            // Read receipts travel with the events like acknowledgements do,
+            // so that the recipients that missed them get them with the next resend
+            let read_receipts_for_this_entry: Vec<&ReadReceipt> = read_receipts_entries
+                .iter()
+                .filter(|read_receipt| {
+                    read_receipt
+                        .0
                         .payload
                         .content
                         .private_event_hash
                         .eq(&EntryHash::from(event_hash.clone()))
                 })
-                .cloned()
                 .collect();
 
-            let mut events_sent_to_recipients_for_this_entry: Vec<EventSentToRecipients> =
+            let events_sent_to_recipients_for_this_entry: Vec<&EventSentToRecipients> =
                 events_sent_to_recipients_entries
                     .iter()
                     .filter(|event_sent_to_recipients| {
@@ -118,35 +143,35 @@ pub fn resend_events_if_necessary<T: PrivateEvent>(
                             .event_hash
                             .eq(&EntryHash::from(event_hash.clone()))
                     })
-                    .cloned()
+                    .chain(std::iter::once(&event_sent_to_recipients))
                     .collect();
 
-            events_sent_to_recipients_for_this_entry.push(event_sent_to_recipients.clone());
-
-            let message = Message {
-                private_events: vec![private_event_entry.clone()],
-                events_sent_to_recipients: events_sent_to_recipients_for_this_entry,
-                acknowledgements: acknowledgements_for_this_entry,
-            };
-
-            send_remote_signal(
-                SerializedBytes::try_from(PrivateEventSourcingRemoteSignal::SendMessage(
-                    message.clone(),
-                ))
-                .map_err(|err| wasm_error!(err))?,
-                recipients_to_send.clone().into_iter().collect(),
-            )?;
-
-            if let Ok(()) = send_async_message(
-                recipients_to_send.clone(),
-                EntryHashB64::from(event_hash.clone()).to_string(),
-                message,
-            ) {
-                create_relaxed(EntryTypes::EventSentToRecipients(event_sent_to_recipients))?;
+            for recipient in &recipients_to_send {
+                outgoing_messages.add_private_event(recipient, event_hash, private_event_entry);
+                for event_sent_to_recipients in &events_sent_to_recipients_for_this_entry {
+                    outgoing_messages
+                        .add_event_sent_to_recipients(recipient, event_sent_to_recipients);
+                }
+                for acknowledgement in &acknowledgements_for_this_entry {
+                    outgoing_messages.add_acknowledgement(recipient, acknowledgement);
+                }
+                for read_receipt in &read_receipts_for_this_entry {
+                    outgoing_messages.add_read_receipt(recipient, read_receipt);
+                }
             }
+
+            new_events_sent_to_recipients.push(event_sent_to_recipients);
         }
     }
 
+    // Send all the events for each recipient batched together
+    let sent_recipients = outgoing_messages.send()?;
+    commit_events_sent_to_recipients(
+        new_events_sent_to_recipients,
+        &sent_recipients,
+        clock_ticker,
+    )?;
+
     Ok(())
 }
 
@@ -157,14 +182,17 @@ pub fn send_new_events<T: PrivateEvent>(event_hashes: BTreeSet<EntryHash>) -> Ex
 
     let my_pub_key = agent_info()?.agent_initial_pubkey;
 
+    let mut clock_ticker = ClockTicker::new()?;
+    let mut outgoing_messages = OutgoingMessages::default();
+    let mut new_events_sent_to_recipients: Vec<EventSentToRecipients> = vec![];
+
     for event_hash in &event_hashes {
         let Some(private_event_entry) = query_private_event_entry(event_hash.clone())? else {
             error!("Could not find private event entry: {}.", event_hash);
             continue;
         };
 
-        let private_event = T::try_from(private_event_entry.0.payload.content.event.clone())
-            .map_err(|_err| wasm_error!("Failed to deserialize private event"))?;
+        let private_event = decode_private_event::<T>(&private_event_entry.0.payload.content)?;
 
         // Send first the depenendencies for the new event,
         // so that they don't end up in the AwaitingDependencies cue
@@ -182,6 +210,7 @@ pub fn send_new_events<T: PrivateEvent>(event_hashes: BTreeSet<EntryHash>) -> Ex
                 &entries,
                 &events_sent_to_recipients_entries,
                 &acknowledgements_entries,
+                &mut clock_ticker,
             )?;
         }
 
@@ -214,35 +243,34 @@ pub fn send_new_events<T: PrivateEvent>(event_hashes: BTreeSet<EntryHash>) -> Ex
                     event_hash: event_hash.clone().into(),
                     recipients: recipients.clone(),
                 };
-                let signed = SignedEntry::build(content)?;
+                let signed = clock_ticker.build_signed_entry(content)?;
                 let event_sent_to_recipients = EventSentToRecipients(signed);
 
-                let message = Message {
-                    private_events: vec![private_event_entry.clone()],
-                    events_sent_to_recipients: vec![event_sent_to_recipients.clone()],
-                    acknowledgements: vec![],
-                };
-
-                send_remote_signal(
-                    SerializedBytes::try_from(PrivateEventSourcingRemoteSignal::SendMessage(
-                        message.clone(),
-                    ))
-                    .map_err(|err| wasm_error!(err))?,
-                    recipients.clone().into_iter().collect(),
-                )?;
-
-                if let Ok(()) = send_async_message(
-                    recipients.clone(),
-                    EntryHashB64::from(event_hash.clone()).to_string(),
-                    message,
-                ) {
-                    create_relaxed(EntryTypes::EventSentToRecipients(event_sent_to_recipients))?;
+                let event_hash = EntryHashB64::from(event_hash.clone());
+                for recipient in &recipients {
+                    outgoing_messages.add_private_event(
+                        recipient,
+                        &event_hash,
+                        &private_event_entry,
+                    );
+                    outgoing_messages
+                        .add_event_sent_to_recipients(recipient, &event_sent_to_recipients);
                 }
+
+                new_events_sent_to_recipients.push(event_sent_to_recipients);
             }
         }
     }
 
-    create_acknowledgements_for::<T>(event_hashes)?;
+    // Send all the new events for each recipient batched together
+    let sent_recipients = outgoing_messages.send()?;
+    commit_events_sent_to_recipients(
+        new_events_sent_to_recipients,
+        &sent_recipients,
+        &mut clock_ticker,
+    )?;
+
+    create_acknowledgements_for::<T>(event_hashes, &mut clock_ticker)?;
 
     Ok(())
 }
@@ -255,11 +283,15 @@ pub fn synchronize_with_linked_device(linked_device: AgentPubKey) -> ExternResul
         .collect();
     let events_sent_to_recipients = query_events_sent_to_recipients_entries(())?;
     let acknowledgements = query_acknowledgement_entries(())?;
+    let read_receipts = query_read_receipt_entries(())?;
+    let rejections = query_rejection_entries(())?;
 
     let message = Message {
         private_events,
         events_sent_to_recipients,
         acknowledgements,
+        read_receipts,
+        rejections,
     };
 
     send_remote_signal(
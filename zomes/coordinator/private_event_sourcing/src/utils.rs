@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use hdk::prelude::*;
 use private_event_sourcing_integrity::*;
 
@@ -64,3 +66,20 @@ pub fn delete_relaxed(address: ActionHash) -> ExternResult<()> {
 
     Ok(())
 }
+
+/// The actions deleted by the delete actions in our source chain from the given action sequence on
+pub fn query_deleted_actions_since(first_action_seq: u32) -> ExternResult<BTreeSet<ActionHash>> {
+    let filter = ChainQueryFilter::new()
+        .action_type(ActionType::Delete)
+        .sequence_range(ChainQueryFilterRange::ActionSeqRange(
+            first_action_seq,
+            u32::MAX,
+        ));
+    Ok(query(filter)?
+        .into_iter()
+        .filter_map(|r| match r.action() {
+            Action::Delete(delete) => Some(delete.deletes_address.clone()),
+            _ => None,
+        })
+        .collect())
+}
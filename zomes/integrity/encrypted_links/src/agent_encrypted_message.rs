@@ -1,14 +1,49 @@
 use hdi::prelude::*;
 
+use crate::LinkTypes;
+
 #[hdk_entry_helper]
 #[derive(Clone)]
 pub struct EncryptedMessage(pub Vec<XSalsa20Poly1305EncryptedData>);
 
+const DEFAULT_MAX_ENCRYPTED_MESSAGE_SIZE_BYTES: usize = 1024 * 1024; // 1 MiB
+const DEFAULT_MAX_ENCRYPTED_MESSAGES_PER_MINUTE: u32 = 200;
+
+/// Maximum size in bytes of a serialized EncryptedMessage
+///
+/// Configurable at build time with the MAX_ENCRYPTED_MESSAGE_SIZE_BYTES environment variable
+pub fn max_encrypted_message_size() -> usize {
+    std::option_env!("MAX_ENCRYPTED_MESSAGE_SIZE_BYTES")
+        .and_then(|size| size.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_ENCRYPTED_MESSAGE_SIZE_BYTES)
+}
+
+/// Maximum number of AgentEncryptedMessage links that an agent can create for the same recipient in a minute,
+/// 0 meaning no limit
+///
+/// Configurable at build time with the MAX_ENCRYPTED_MESSAGES_PER_MINUTE environment variable
+pub fn max_encrypted_messages_per_minute() -> u32 {
+    std::option_env!("MAX_ENCRYPTED_MESSAGES_PER_MINUTE")
+        .and_then(|max| max.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_MAX_ENCRYPTED_MESSAGES_PER_MINUTE)
+}
+
+fn validate_encrypted_message_size(size: usize) -> ValidateCallbackResult {
+    if size > max_encrypted_message_size() {
+        return ValidateCallbackResult::Invalid(format!(
+            "EncryptedMessage of {size} bytes exceeds the maximum size of {} bytes",
+            max_encrypted_message_size()
+        ));
+    }
+    ValidateCallbackResult::Valid
+}
+
 pub fn validate_create_encrypted_message(
     _action: EntryCreationAction,
-    _encrypted_message: EncryptedMessage,
+    encrypted_message: EncryptedMessage,
 ) -> ExternResult<ValidateCallbackResult> {
-    Ok(ValidateCallbackResult::Valid)
+    let bytes = SerializedBytes::try_from(encrypted_message).map_err(|err| wasm_error!(err))?;
+    Ok(validate_encrypted_message_size(bytes.bytes().len()))
 }
 pub fn validate_update_encrypted_message(
     _action: Update,
@@ -25,10 +60,10 @@ pub fn validate_delete_encrypted_message(_action: Delete) -> ExternResult<Valida
 }
 
 pub fn validate_create_link_agent_encrypted_message(
-    _action: CreateLink,
+    action: CreateLink,
     base_address: AnyLinkableHash,
     target_address: AnyLinkableHash,
-    _tag: LinkTag,
+    tag: LinkTag,
 ) -> ExternResult<ValidateCallbackResult> {
     // Check the entry type for the given action hash
     let Some(_base_agent) = base_address.clone().into_agent_pub_key() else {
@@ -37,6 +72,11 @@ pub fn validate_create_link_agent_encrypted_message(
         )));
     };
 
+    let result = validate_rate_limit(&action)?;
+    let ValidateCallbackResult::Valid = result else {
+        return Ok(result);
+    };
+
     if base_address.ne(&target_address) {
         if let Some(encrypted_message_hash) = target_address.into_entry_hash() {
             let entry = must_get_entry(encrypted_message_hash.clone())?;
@@ -51,6 +91,70 @@ pub fn validate_create_link_agent_encrypted_message(
                     .to_string(),
             ));
         }
+    } else {
+        // The message is inlined in the tag
+        return Ok(validate_encrypted_message_size(tag.0.len()));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+/// Number of actions fetched at a time while walking back the author's chain to check the rate limit
+const RATE_LIMIT_ACTIVITY_PAGE_SIZE: u32 = 100;
+
+/// Rejects the link if its author already created too many AgentEncryptedMessage links for the same recipient
+/// in the minute before it
+///
+/// Sending a message to many recipients at once creates one link for each of them, so it's not limited
+fn validate_rate_limit(action: &CreateLink) -> ExternResult<ValidateCallbackResult> {
+    let max_per_minute = max_encrypted_messages_per_minute();
+    if max_per_minute == 0 {
+        return Ok(ValidateCallbackResult::Valid);
+    }
+
+    let window_start = action.timestamp.as_micros() - 60 * 1_000_000;
+    let mut links_in_window: u32 = 0;
+    let mut chain_top = Some(action.prev_action.clone());
+
+    // Walk back the author's chain until the start of the window
+    while let Some(top) = chain_top.take() {
+        let activity = must_get_agent_activity(
+            action.author.clone(),
+            ChainFilter::new(top).take(RATE_LIMIT_ACTIVITY_PAGE_SIZE),
+        )?;
+        let Some(oldest) = activity
+            .iter()
+            .min_by_key(|a| a.action.action().action_seq())
+        else {
+            break;
+        };
+        if oldest.action.action().timestamp().as_micros() >= window_start
+            && activity.len() as u32 == RATE_LIMIT_ACTIVITY_PAGE_SIZE
+        {
+            chain_top = oldest.action.action().prev_action().cloned();
+        }
+
+        for activity in &activity {
+            let Action::CreateLink(create_link) = activity.action.action() else {
+                continue;
+            };
+            if create_link.timestamp.as_micros() < window_start
+                || create_link.base_address.ne(&action.base_address)
+            {
+                continue;
+            }
+            if let Some(LinkTypes::AgentEncryptedMessage) =
+                LinkTypes::from_type(create_link.zome_index, create_link.link_type)?
+            {
+                links_in_window += 1;
+            }
+        }
+    }
+
+    if links_in_window >= max_per_minute {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "Agent {} exceeded the rate limit of {max_per_minute} encrypted messages per minute to agent {}",
+            action.author, action.base_address
+        )));
     }
     Ok(ValidateCallbackResult::Valid)
 }
@@ -0,0 +1,26 @@
+use hdi::prelude::*;
+
+/// An agent whose async messages we delete from our inbox without decrypting them
+#[hdk_entry_helper]
+#[derive(Clone)]
+pub struct BlockedAgent {
+    pub agent: AgentPubKey,
+}
+
+pub fn validate_create_blocked_agent(
+    _action: EntryCreationAction,
+    _blocked_agent: BlockedAgent,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_blocked_agent(
+    _action: Update,
+    _blocked_agent: BlockedAgent,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "BlockedAgents cannot be updated"
+    )))
+}
+pub fn validate_delete_blocked_agent(_action: Delete) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
@@ -0,0 +1,32 @@
+use hdi::prelude::*;
+
+/// An async message that could not be handed off to its target zome, kept so that it can be replayed later
+#[hdk_entry_helper]
+#[derive(Clone)]
+pub struct DeadLetter {
+    pub provenance: AgentPubKey,
+    /// None if the message could not be deserialized far enough to know its target zome
+    pub zome_name: Option<ZomeName>,
+    /// The decrypted bytes of the MessageWithZomeName
+    pub message: Vec<u8>,
+    pub error: String,
+    pub timestamp: Timestamp,
+}
+
+pub fn validate_create_dead_letter(
+    _action: EntryCreationAction,
+    _dead_letter: DeadLetter,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_dead_letter(
+    _action: Update,
+    _dead_letter: DeadLetter,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "DeadLetters cannot be updated"
+    )))
+}
+pub fn validate_delete_dead_letter(_action: Delete) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
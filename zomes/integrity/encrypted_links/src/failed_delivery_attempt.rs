@@ -0,0 +1,30 @@
+use hdi::prelude::*;
+
+/// Records a failed attempt to hand off a message in our inbox to its target zome
+#[hdk_entry_helper]
+#[derive(Clone)]
+pub struct FailedDeliveryAttempt {
+    /// The AgentEncryptedMessage link for the message
+    pub create_link_hash: ActionHash,
+    pub error: String,
+}
+
+pub fn validate_create_failed_delivery_attempt(
+    _action: EntryCreationAction,
+    _failed_delivery_attempt: FailedDeliveryAttempt,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_failed_delivery_attempt(
+    _action: Update,
+    _failed_delivery_attempt: FailedDeliveryAttempt,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "FailedDeliveryAttempts cannot be updated"
+    )))
+}
+pub fn validate_delete_failed_delivery_attempt(
+    _action: Delete,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
@@ -3,12 +3,37 @@ use hdi::prelude::*;
 mod agent_encrypted_message;
 pub use agent_encrypted_message::*;
 
+mod message_id_record;
+pub use message_id_record::*;
+
+mod failed_delivery_attempt;
+pub use failed_delivery_attempt::*;
+
+mod dead_letter;
+pub use dead_letter::*;
+
+mod blocked_agent;
+pub use blocked_agent::*;
+
+mod target_zome;
+pub use target_zome::*;
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(tag = "type")]
 #[hdk_entry_types]
 #[unit_enum(UnitEntryTypes)]
 pub enum EntryTypes {
     EncryptedMessage(EncryptedMessage),
+    #[entry_type(visibility = "private")]
+    MessageIdRecord(MessageIdRecord),
+    #[entry_type(visibility = "private")]
+    FailedDeliveryAttempt(FailedDeliveryAttempt),
+    #[entry_type(visibility = "private")]
+    DeadLetter(DeadLetter),
+    #[entry_type(visibility = "private")]
+    BlockedAgent(BlockedAgent),
+    #[entry_type(visibility = "private")]
+    TargetZome(TargetZome),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -74,6 +99,28 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         encrypted_message,
                     )
                 }
+                EntryTypes::MessageIdRecord(message_id_record) => {
+                    validate_create_message_id_record(
+                        EntryCreationAction::Create(action),
+                        message_id_record,
+                    )
+                }
+                EntryTypes::FailedDeliveryAttempt(failed_delivery_attempt) => {
+                    validate_create_failed_delivery_attempt(
+                        EntryCreationAction::Create(action),
+                        failed_delivery_attempt,
+                    )
+                }
+                EntryTypes::DeadLetter(dead_letter) => {
+                    validate_create_dead_letter(EntryCreationAction::Create(action), dead_letter)
+                }
+                EntryTypes::BlockedAgent(blocked_agent) => validate_create_blocked_agent(
+                    EntryCreationAction::Create(action),
+                    blocked_agent,
+                ),
+                EntryTypes::TargetZome(target_zome) => {
+                    validate_create_target_zome(EntryCreationAction::Create(action), target_zome)
+                }
             },
             OpEntry::UpdateEntry {
                 app_entry, action, ..
@@ -84,6 +131,28 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         encrypted_message,
                     )
                 }
+                EntryTypes::MessageIdRecord(message_id_record) => {
+                    validate_create_message_id_record(
+                        EntryCreationAction::Update(action),
+                        message_id_record,
+                    )
+                }
+                EntryTypes::FailedDeliveryAttempt(failed_delivery_attempt) => {
+                    validate_create_failed_delivery_attempt(
+                        EntryCreationAction::Update(action),
+                        failed_delivery_attempt,
+                    )
+                }
+                EntryTypes::DeadLetter(dead_letter) => {
+                    validate_create_dead_letter(EntryCreationAction::Update(action), dead_letter)
+                }
+                EntryTypes::BlockedAgent(blocked_agent) => validate_create_blocked_agent(
+                    EntryCreationAction::Update(action),
+                    blocked_agent,
+                ),
+                EntryTypes::TargetZome(target_zome) => {
+                    validate_create_target_zome(EntryCreationAction::Update(action), target_zome)
+                }
             },
             _ => Ok(ValidateCallbackResult::Valid),
         },
@@ -92,6 +161,21 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                 EntryTypes::EncryptedMessage(encrypted_message) => {
                     validate_update_encrypted_message(action, encrypted_message)
                 }
+                EntryTypes::MessageIdRecord(message_id_record) => {
+                    validate_update_message_id_record(action, message_id_record)
+                }
+                EntryTypes::FailedDeliveryAttempt(failed_delivery_attempt) => {
+                    validate_update_failed_delivery_attempt(action, failed_delivery_attempt)
+                }
+                EntryTypes::DeadLetter(dead_letter) => {
+                    validate_update_dead_letter(action, dead_letter)
+                }
+                EntryTypes::BlockedAgent(blocked_agent) => {
+                    validate_update_blocked_agent(action, blocked_agent)
+                }
+                EntryTypes::TargetZome(target_zome) => {
+                    validate_update_target_zome(action, target_zome)
+                }
             },
             _ => Ok(ValidateCallbackResult::Valid),
         },
@@ -139,6 +223,13 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
             };
             match original_app_entry {
                 EntryTypes::EncryptedMessage(_) => validate_delete_encrypted_message(action),
+                EntryTypes::MessageIdRecord(_) => validate_delete_message_id_record(action),
+                EntryTypes::FailedDeliveryAttempt(_) => {
+                    validate_delete_failed_delivery_attempt(action)
+                }
+                EntryTypes::DeadLetter(_) => validate_delete_dead_letter(action),
+                EntryTypes::BlockedAgent(_) => validate_delete_blocked_agent(action),
+                EntryTypes::TargetZome(_) => validate_delete_target_zome(action),
             }
         }
         FlatOp::RegisterCreateLink {
@@ -180,6 +271,28 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         encrypted_message,
                     )
                 }
+                EntryTypes::MessageIdRecord(message_id_record) => {
+                    validate_create_message_id_record(
+                        EntryCreationAction::Create(action),
+                        message_id_record,
+                    )
+                }
+                EntryTypes::FailedDeliveryAttempt(failed_delivery_attempt) => {
+                    validate_create_failed_delivery_attempt(
+                        EntryCreationAction::Create(action),
+                        failed_delivery_attempt,
+                    )
+                }
+                EntryTypes::DeadLetter(dead_letter) => {
+                    validate_create_dead_letter(EntryCreationAction::Create(action), dead_letter)
+                }
+                EntryTypes::BlockedAgent(blocked_agent) => validate_create_blocked_agent(
+                    EntryCreationAction::Create(action),
+                    blocked_agent,
+                ),
+                EntryTypes::TargetZome(target_zome) => {
+                    validate_create_target_zome(EntryCreationAction::Create(action), target_zome)
+                }
             },
             OpRecord::UpdateEntry {
                 app_entry, action, ..
@@ -194,6 +307,56 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                     };
                     validate_update_encrypted_message(action, encrypted_message)
                 }
+                EntryTypes::MessageIdRecord(message_id_record) => {
+                    let result = validate_create_message_id_record(
+                        EntryCreationAction::Update(action.clone()),
+                        message_id_record.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_message_id_record(action, message_id_record)
+                }
+                EntryTypes::FailedDeliveryAttempt(failed_delivery_attempt) => {
+                    let result = validate_create_failed_delivery_attempt(
+                        EntryCreationAction::Update(action.clone()),
+                        failed_delivery_attempt.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_failed_delivery_attempt(action, failed_delivery_attempt)
+                }
+                EntryTypes::DeadLetter(dead_letter) => {
+                    let result = validate_create_dead_letter(
+                        EntryCreationAction::Update(action.clone()),
+                        dead_letter.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_dead_letter(action, dead_letter)
+                }
+                EntryTypes::BlockedAgent(blocked_agent) => {
+                    let result = validate_create_blocked_agent(
+                        EntryCreationAction::Update(action.clone()),
+                        blocked_agent.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_blocked_agent(action, blocked_agent)
+                }
+                EntryTypes::TargetZome(target_zome) => {
+                    let result = validate_create_target_zome(
+                        EntryCreationAction::Update(action.clone()),
+                        target_zome.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_target_zome(action, target_zome)
+                }
             },
             OpRecord::DeleteEntry {
                 original_action_hash,
@@ -243,6 +406,13 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                 };
                 match original_app_entry {
                     EntryTypes::EncryptedMessage(_) => validate_delete_encrypted_message(action),
+                    EntryTypes::MessageIdRecord(_) => validate_delete_message_id_record(action),
+                    EntryTypes::FailedDeliveryAttempt(_) => {
+                        validate_delete_failed_delivery_attempt(action)
+                    }
+                    EntryTypes::DeadLetter(_) => validate_delete_dead_letter(action),
+                    EntryTypes::BlockedAgent(_) => validate_delete_blocked_agent(action),
+                    EntryTypes::TargetZome(_) => validate_delete_target_zome(action),
                 }
             }
             OpRecord::CreateLink {
@@ -0,0 +1,36 @@
+use hdi::prelude::*;
+
+/// Records the id of an async message that was sent or processed, so that repeated messages with the same id are dropped
+#[hdk_entry_helper]
+#[derive(Clone)]
+pub enum MessageIdRecord {
+    Sent {
+        message_id: String,
+        recipient: AgentPubKey,
+        /// The link to the message in the recipient's inbox, missing in records created by older versions
+        #[serde(default)]
+        create_link_hash: Option<ActionHash>,
+    },
+    Processed {
+        message_id: String,
+        provenance: AgentPubKey,
+    },
+}
+
+pub fn validate_create_message_id_record(
+    _action: EntryCreationAction,
+    _message_id_record: MessageIdRecord,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_message_id_record(
+    _action: Update,
+    _message_id_record: MessageIdRecord,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "MessageIdRecords cannot be updated"
+    )))
+}
+pub fn validate_delete_message_id_record(_action: Delete) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
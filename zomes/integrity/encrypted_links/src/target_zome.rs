@@ -0,0 +1,26 @@
+use hdi::prelude::*;
+
+/// A zome in our cell that incoming async messages are allowed to be handed off to
+#[hdk_entry_helper]
+#[derive(Clone)]
+pub struct TargetZome {
+    pub zome_name: ZomeName,
+}
+
+pub fn validate_create_target_zome(
+    _action: EntryCreationAction,
+    _target_zome: TargetZome,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_target_zome(
+    _action: Update,
+    _target_zome: TargetZome,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "TargetZomes cannot be updated"
+    )))
+}
+pub fn validate_delete_target_zome(_action: Delete) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
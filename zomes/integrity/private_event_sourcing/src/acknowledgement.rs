@@ -1,11 +1,13 @@
 use hdi::prelude::*;
 pub use private_event_sourcing_types::Acknowledgement;
 
+use crate::validate_signed_entry;
+
 pub fn validate_create_acknowledgement(
     _action: EntryCreationAction,
-    _acknowledgement: Acknowledgement,
+    acknowledgement: Acknowledgement,
 ) -> ExternResult<ValidateCallbackResult> {
-    Ok(ValidateCallbackResult::Valid)
+    validate_signed_entry(&acknowledgement.0, "Acknowledgement")
 }
 
 pub fn validate_update_acknowledgement(
@@ -1,7 +1,7 @@
 use hdi::prelude::*;
 use private_event_sourcing_types::EventSentToRecipients;
 
-use crate::{Acknowledgement, PrivateEventEntry};
+use crate::{validate_signed_entry, Acknowledgement, PrivateEventEntry, ReadReceipt, Rejection};
 
 #[hdk_entry_helper]
 #[derive(Clone)]
@@ -9,6 +9,10 @@ pub enum AwaitingDependencies {
     Event {
         event: PrivateEventEntry,
         unresolved_dependencies: UnresolvedDependencies,
+        /// The agent that sent us the event
+        /// None for imported events and for the ones received before this field existed
+        #[serde(default)]
+        provenance: Option<AgentPubKey>,
     },
     Acknowledgement {
         acknowledgement: Acknowledgement,
@@ -16,13 +20,51 @@ pub enum AwaitingDependencies {
     EventsSentToRecipients {
         event_sent_to_recipients: EventSentToRecipients,
     },
+    ReadReceipt {
+        read_receipt: ReadReceipt,
+    },
+    Rejection {
+        rejection: Rejection,
+    },
+}
+
+/// The action sequence from which on the entries awaiting dependencies that haven't been deleted were committed
+///
+/// Committed when the oldest of those entries are resolved or expire, so that they are queried without scanning the whole source chain
+#[hdk_entry_helper]
+#[derive(Clone)]
+pub struct AwaitingDependenciesCheckpoint {
+    pub oldest_awaiting_action_seq: u32,
+}
+
+/// Checks the signature of the entry that is awaiting its dependencies
+pub fn validate_awaiting_dependencies_signature(
+    awaiting_dependencies: &AwaitingDependencies,
+) -> ExternResult<ValidateCallbackResult> {
+    match awaiting_dependencies {
+        AwaitingDependencies::Event { event, .. } => {
+            validate_signed_entry(&event.0, "PrivateEvent")
+        }
+        AwaitingDependencies::Acknowledgement { acknowledgement } => {
+            validate_signed_entry(&acknowledgement.0, "Acknowledgement")
+        }
+        AwaitingDependencies::EventsSentToRecipients {
+            event_sent_to_recipients,
+        } => validate_signed_entry(&event_sent_to_recipients.0, "EventSentToRecipients"),
+        AwaitingDependencies::ReadReceipt { read_receipt } => {
+            validate_signed_entry(&read_receipt.0, "ReadReceipt")
+        }
+        AwaitingDependencies::Rejection { rejection } => {
+            validate_signed_entry(&rejection.0, "Rejection")
+        }
+    }
 }
 
 pub fn validate_create_awaiting_dependencies(
     _action: EntryCreationAction,
-    _event: AwaitingDependencies,
+    awaiting_dependencies: AwaitingDependencies,
 ) -> ExternResult<ValidateCallbackResult> {
-    Ok(ValidateCallbackResult::Valid)
+    validate_awaiting_dependencies_signature(&awaiting_dependencies)
 }
 
 pub fn validate_update_awaiting_dependencies(
@@ -45,3 +87,27 @@ pub fn validate_delete_awaiting_dependencies(
     }
     Ok(ValidateCallbackResult::Valid)
 }
+
+pub fn validate_create_awaiting_dependencies_checkpoint(
+    _action: EntryCreationAction,
+    _checkpoint: AwaitingDependenciesCheckpoint,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
+
+pub fn validate_update_awaiting_dependencies_checkpoint(
+    _action: Update,
+    _checkpoint: AwaitingDependenciesCheckpoint,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "AwaitingDependenciesCheckpoints cannot be updated",
+    )))
+}
+
+pub fn validate_delete_awaiting_dependencies_checkpoint(
+    _action: Delete,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "AwaitingDependenciesCheckpoints cannot be deleted",
+    )))
+}
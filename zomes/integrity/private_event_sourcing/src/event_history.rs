@@ -2,7 +2,10 @@ use std::collections::BTreeMap;
 
 use hdi::prelude::*;
 
-use crate::{Acknowledgement, AwaitingDependencies, EventSentToRecipients, PrivateEventEntry};
+use crate::{
+    validate_awaiting_dependencies_signature, validate_signed_entry, Acknowledgement,
+    AwaitingDependencies, EventSentToRecipients, PrivateEventEntry, ReadReceipt, Rejection,
+};
 
 #[hdk_entry_helper]
 #[derive(Clone)]
@@ -11,12 +14,82 @@ pub struct EventHistory {
     pub events: BTreeMap<EntryHashB64, PrivateEventEntry>,
     pub events_sent_to_recipients: Vec<EventSentToRecipients>,
     pub acknowledgements: Vec<Acknowledgement>,
+    #[serde(default)]
+    pub read_receipts: Vec<ReadReceipt>,
+    #[serde(default)]
+    pub rejections: Vec<Rejection>,
+    /// Present for histories that were imported in chunks from a paginated export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<EventHistoryChunk>,
+}
+
+/// Where an imported chunk of history comes from, used to resume interrupted imports
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EventHistoryChunk {
+    /// DNA hash of the cell that exported the history
+    pub source_dna_hash: DnaHash,
+    /// Cursor of the next page to import from the source, or None if this was the last one
+    pub next_cursor: Option<EventHistoryCursor>,
+}
+
+/// Position of an entry in an exported event history, used as the cursor to export the entries after it
+///
+/// Entries are exported in the order in which they were committed to the source chain, which doesn't change
+/// when new entries are committed during the export
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventHistoryCursor {
+    /// Sequence number of the action that committed the entry
+    pub action_seq: u32,
+    /// Position of the entry inside an imported EventHistory, 0 for the rest of the entries
+    pub index: u32,
 }
 
 pub fn validate_create_event_history(
     _action: EntryCreationAction,
-    _event: EventHistory,
+    event_history: EventHistory,
 ) -> ExternResult<ValidateCallbackResult> {
+    for (event_hash, private_event_entry) in &event_history.events {
+        if EntryHashB64::from(hash_entry(private_event_entry)?).ne(event_hash) {
+            return Ok(ValidateCallbackResult::Invalid(format!(
+                "Invalid EventHistory: the hash of the event {event_hash} does not match its contents."
+            )));
+        }
+        let result = validate_signed_entry(&private_event_entry.0, "PrivateEvent")?;
+        let ValidateCallbackResult::Valid = result else {
+            return Ok(result);
+        };
+    }
+    for event_sent_to_recipients in &event_history.events_sent_to_recipients {
+        let result = validate_signed_entry(&event_sent_to_recipients.0, "EventSentToRecipients")?;
+        let ValidateCallbackResult::Valid = result else {
+            return Ok(result);
+        };
+    }
+    for acknowledgement in &event_history.acknowledgements {
+        let result = validate_signed_entry(&acknowledgement.0, "Acknowledgement")?;
+        let ValidateCallbackResult::Valid = result else {
+            return Ok(result);
+        };
+    }
+    for read_receipt in &event_history.read_receipts {
+        let result = validate_signed_entry(&read_receipt.0, "ReadReceipt")?;
+        let ValidateCallbackResult::Valid = result else {
+            return Ok(result);
+        };
+    }
+    for rejection in &event_history.rejections {
+        let result = validate_signed_entry(&rejection.0, "Rejection")?;
+        let ValidateCallbackResult::Valid = result else {
+            return Ok(result);
+        };
+    }
+    for awaiting_dependencies in &event_history.awaiting_deps {
+        let result = validate_awaiting_dependencies_signature(awaiting_dependencies)?;
+        let ValidateCallbackResult::Valid = result else {
+            return Ok(result);
+        };
+    }
+
     Ok(ValidateCallbackResult::Valid)
 }
 
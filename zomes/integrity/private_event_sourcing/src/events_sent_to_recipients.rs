@@ -2,11 +2,13 @@ use hdi::prelude::*;
 
 pub use private_event_sourcing_types::EventSentToRecipients;
 
+use crate::validate_signed_entry;
+
 pub fn validate_create_events_sent_to_recipients(
     _action: EntryCreationAction,
-    _events_sent_to_recipients: EventSentToRecipients,
+    events_sent_to_recipients: EventSentToRecipients,
 ) -> ExternResult<ValidateCallbackResult> {
-    Ok(ValidateCallbackResult::Valid)
+    validate_signed_entry(&events_sent_to_recipients.0, "EventSentToRecipients")
 }
 pub fn validate_update_events_sent_to_recipients(
     _action: Update,
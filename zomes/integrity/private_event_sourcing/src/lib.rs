@@ -17,6 +17,21 @@ pub use event_history::*;
 mod acknowledgement;
 pub use acknowledgement::*;
 
+mod projection_snapshot;
+pub use projection_snapshot::*;
+
+mod read_receipt;
+pub use read_receipt::*;
+
+mod rejection;
+pub use rejection::*;
+
+mod quarantined_event;
+pub use quarantined_event::*;
+
+mod signed_entry;
+pub use signed_entry::*;
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(tag = "type")]
 #[hdk_entry_types]
@@ -32,6 +47,16 @@ pub enum EntryTypes {
     Acknowledgement(Acknowledgement),
     #[entry_type(visibility = "private")]
     EventSentToRecipients(EventSentToRecipients),
+    #[entry_type(visibility = "private")]
+    ProjectionSnapshot(ProjectionSnapshot),
+    #[entry_type(visibility = "private")]
+    ReadReceipt(ReadReceipt),
+    #[entry_type(visibility = "private")]
+    Rejection(Rejection),
+    #[entry_type(visibility = "private")]
+    QuarantinedEvent(QuarantinedEvent),
+    #[entry_type(visibility = "private")]
+    AwaitingDependenciesCheckpoint(AwaitingDependenciesCheckpoint),
 }
 
 /// Validation you perform during the genesis process. Nobody else on the network performs it, only you.
@@ -109,6 +134,30 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                     EntryCreationAction::Create(action),
                     event_history,
                 ),
+                EntryTypes::ProjectionSnapshot(projection_snapshot) => {
+                    validate_create_projection_snapshot(
+                        EntryCreationAction::Create(action),
+                        projection_snapshot,
+                    )
+                }
+                EntryTypes::ReadReceipt(read_receipt) => {
+                    validate_create_read_receipt(EntryCreationAction::Create(action), read_receipt)
+                }
+                EntryTypes::Rejection(rejection) => {
+                    validate_create_rejection(EntryCreationAction::Create(action), rejection)
+                }
+                EntryTypes::QuarantinedEvent(quarantined_event) => {
+                    validate_create_quarantined_event(
+                        EntryCreationAction::Create(action),
+                        quarantined_event,
+                    )
+                }
+                EntryTypes::AwaitingDependenciesCheckpoint(checkpoint) => {
+                    validate_create_awaiting_dependencies_checkpoint(
+                        EntryCreationAction::Create(action),
+                        checkpoint,
+                    )
+                }
             },
             OpEntry::UpdateEntry {
                 app_entry, action, ..
@@ -137,6 +186,30 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                     EntryCreationAction::Update(action),
                     event_history,
                 ),
+                EntryTypes::ProjectionSnapshot(projection_snapshot) => {
+                    validate_create_projection_snapshot(
+                        EntryCreationAction::Update(action),
+                        projection_snapshot,
+                    )
+                }
+                EntryTypes::ReadReceipt(read_receipt) => {
+                    validate_create_read_receipt(EntryCreationAction::Update(action), read_receipt)
+                }
+                EntryTypes::Rejection(rejection) => {
+                    validate_create_rejection(EntryCreationAction::Update(action), rejection)
+                }
+                EntryTypes::QuarantinedEvent(quarantined_event) => {
+                    validate_create_quarantined_event(
+                        EntryCreationAction::Update(action),
+                        quarantined_event,
+                    )
+                }
+                EntryTypes::AwaitingDependenciesCheckpoint(checkpoint) => {
+                    validate_create_awaiting_dependencies_checkpoint(
+                        EntryCreationAction::Update(action),
+                        checkpoint,
+                    )
+                }
             },
             _ => Ok(ValidateCallbackResult::Valid),
         },
@@ -155,6 +228,19 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                     validate_update_events_sent_to_recipients(action, events_sent_to_recipients)
                 }
                 EntryTypes::EventHistory(_event_history) => validate_update_event_history(action),
+                EntryTypes::ProjectionSnapshot(projection_snapshot) => {
+                    validate_update_projection_snapshot(action, projection_snapshot)
+                }
+                EntryTypes::ReadReceipt(read_receipt) => {
+                    validate_update_read_receipt(action, read_receipt)
+                }
+                EntryTypes::Rejection(rejection) => validate_update_rejection(action, rejection),
+                EntryTypes::QuarantinedEvent(quarantined_event) => {
+                    validate_update_quarantined_event(action, quarantined_event)
+                }
+                EntryTypes::AwaitingDependenciesCheckpoint(checkpoint) => {
+                    validate_update_awaiting_dependencies_checkpoint(action, checkpoint)
+                }
             },
             _ => Ok(ValidateCallbackResult::Valid),
         },
@@ -210,6 +296,13 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                 }
                 EntryTypes::Acknowledgement(_) => validate_delete_acknowledgement(action),
                 EntryTypes::EventHistory(_) => validate_delete_event_history(action),
+                EntryTypes::ProjectionSnapshot(_) => validate_delete_projection_snapshot(action),
+                EntryTypes::ReadReceipt(_) => validate_delete_read_receipt(action),
+                EntryTypes::Rejection(_) => validate_delete_rejection(action),
+                EntryTypes::QuarantinedEvent(_) => validate_delete_quarantined_event(action),
+                EntryTypes::AwaitingDependenciesCheckpoint(_) => {
+                    validate_delete_awaiting_dependencies_checkpoint(action)
+                }
             }
         }
         FlatOp::RegisterCreateLink {
@@ -257,6 +350,30 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                     EntryCreationAction::Create(action),
                     event_history,
                 ),
+                EntryTypes::ProjectionSnapshot(projection_snapshot) => {
+                    validate_create_projection_snapshot(
+                        EntryCreationAction::Create(action),
+                        projection_snapshot,
+                    )
+                }
+                EntryTypes::ReadReceipt(read_receipt) => {
+                    validate_create_read_receipt(EntryCreationAction::Create(action), read_receipt)
+                }
+                EntryTypes::Rejection(rejection) => {
+                    validate_create_rejection(EntryCreationAction::Create(action), rejection)
+                }
+                EntryTypes::QuarantinedEvent(quarantined_event) => {
+                    validate_create_quarantined_event(
+                        EntryCreationAction::Create(action),
+                        quarantined_event,
+                    )
+                }
+                EntryTypes::AwaitingDependenciesCheckpoint(checkpoint) => {
+                    validate_create_awaiting_dependencies_checkpoint(
+                        EntryCreationAction::Create(action),
+                        checkpoint,
+                    )
+                }
             },
             OpRecord::UpdateEntry {
                 app_entry, action, ..
@@ -311,6 +428,56 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                     };
                     validate_update_event_history(action)
                 }
+                EntryTypes::ProjectionSnapshot(projection_snapshot) => {
+                    let result = validate_create_projection_snapshot(
+                        EntryCreationAction::Update(action.clone()),
+                        projection_snapshot.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_projection_snapshot(action, projection_snapshot)
+                }
+                EntryTypes::ReadReceipt(read_receipt) => {
+                    let result = validate_create_read_receipt(
+                        EntryCreationAction::Update(action.clone()),
+                        read_receipt.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_read_receipt(action, read_receipt)
+                }
+                EntryTypes::Rejection(rejection) => {
+                    let result = validate_create_rejection(
+                        EntryCreationAction::Update(action.clone()),
+                        rejection.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_rejection(action, rejection)
+                }
+                EntryTypes::QuarantinedEvent(quarantined_event) => {
+                    let result = validate_create_quarantined_event(
+                        EntryCreationAction::Update(action.clone()),
+                        quarantined_event.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_quarantined_event(action, quarantined_event)
+                }
+                EntryTypes::AwaitingDependenciesCheckpoint(checkpoint) => {
+                    let result = validate_create_awaiting_dependencies_checkpoint(
+                        EntryCreationAction::Update(action.clone()),
+                        checkpoint.clone(),
+                    )?;
+                    let ValidateCallbackResult::Valid = result else {
+                        return Ok(result);
+                    };
+                    validate_update_awaiting_dependencies_checkpoint(action, checkpoint)
+                }
             },
             OpRecord::DeleteEntry {
                 original_action_hash,
@@ -368,6 +535,15 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         validate_delete_events_sent_to_recipients(action)
                     }
                     EntryTypes::EventHistory(_) => validate_delete_event_history(action),
+                    EntryTypes::ProjectionSnapshot(_) => {
+                        validate_delete_projection_snapshot(action)
+                    }
+                    EntryTypes::ReadReceipt(_) => validate_delete_read_receipt(action),
+                    EntryTypes::Rejection(_) => validate_delete_rejection(action),
+                    EntryTypes::QuarantinedEvent(_) => validate_delete_quarantined_event(action),
+                    EntryTypes::AwaitingDependenciesCheckpoint(_) => {
+                        validate_delete_awaiting_dependencies_checkpoint(action)
+                    }
                 }
             }
             OpRecord::CreateLink {
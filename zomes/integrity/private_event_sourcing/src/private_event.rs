@@ -1,11 +1,13 @@
 use hdi::prelude::*;
 pub use private_event_sourcing_types::*;
 
+use crate::validate_signed_entry;
+
 pub fn validate_create_private_event(
     _action: EntryCreationAction,
-    _event: PrivateEventEntry,
+    event: PrivateEventEntry,
 ) -> ExternResult<ValidateCallbackResult> {
-    Ok(ValidateCallbackResult::Valid)
+    validate_signed_entry(&event.0, "PrivateEvent")
 }
 
 pub fn validate_update_private_event(
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+
+use hdi::prelude::*;
+
+use crate::EventOrderKey;
+
+/// Persisted state of a projection after applying all the events up to `last_applied_event`
+///
+/// The events committed after `last_action_seq` are applied on top of it, using `heads_lamport_clocks`
+/// to compute their position in the total order
+#[hdk_entry_helper]
+#[derive(Clone)]
+pub struct ProjectionSnapshot {
+    pub projection_name: String,
+    pub last_applied_event: EventOrderKey,
+    pub heads_lamport_clocks: BTreeMap<EntryHashB64, u32>,
+    pub last_action_seq: u32,
+    pub last_action_hash: ActionHash,
+    pub state: SerializedBytes,
+}
+
+pub fn validate_create_projection_snapshot(
+    _action: EntryCreationAction,
+    _projection_snapshot: ProjectionSnapshot,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
+
+pub fn validate_update_projection_snapshot(
+    _action: Update,
+    _projection_snapshot: ProjectionSnapshot,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "ProjectionSnapshots cannot be updated"
+    )))
+}
+
+/// Snapshots are deleted once a newer snapshot supersedes them
+pub fn validate_delete_projection_snapshot(action: Delete) -> ExternResult<ValidateCallbackResult> {
+    let create = must_get_action(action.deletes_address)?;
+    if action.author.ne(create.hashed.content.author()) {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "ProjectionSnapshots can only be deleted by their authors"
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
@@ -0,0 +1,40 @@
+use hdi::prelude::*;
+
+use crate::PrivateEventEntry;
+
+/// A received private event that was discarded because it was invalid,
+/// kept to debug interop issues between app versions and to detect misbehaving agents
+#[hdk_entry_helper]
+#[derive(Clone)]
+pub struct QuarantinedEvent {
+    pub event: PrivateEventEntry,
+    /// The agent that sent us the event, who may not be its author
+    pub provenance: AgentPubKey,
+    pub reason: String,
+    pub timestamp: Timestamp,
+    /// Number of invalid events that the provenance sent us after this one and that were not quarantined,
+    /// because it had reached the maximum number of quarantined events
+    #[serde(default)]
+    pub overflow: u32,
+}
+
+pub fn validate_create_quarantined_event(
+    _action: EntryCreationAction,
+    _quarantined_event: QuarantinedEvent,
+) -> ExternResult<ValidateCallbackResult> {
+    // The quarantined event is invalid by definition, so its contents are not validated
+    Ok(ValidateCallbackResult::Valid)
+}
+
+pub fn validate_update_quarantined_event(
+    _action: Update,
+    _quarantined_event: QuarantinedEvent,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "QuarantinedEvents cannot be updated"
+    )))
+}
+
+pub fn validate_delete_quarantined_event(_action: Delete) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Valid)
+}
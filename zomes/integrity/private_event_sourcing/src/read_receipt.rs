@@ -0,0 +1,26 @@
+use hdi::prelude::*;
+pub use private_event_sourcing_types::ReadReceipt;
+
+use crate::validate_signed_entry;
+
+pub fn validate_create_read_receipt(
+    _action: EntryCreationAction,
+    read_receipt: ReadReceipt,
+) -> ExternResult<ValidateCallbackResult> {
+    validate_signed_entry(&read_receipt.0, "ReadReceipt")
+}
+
+pub fn validate_update_read_receipt(
+    _action: Update,
+    _read_receipt: ReadReceipt,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "ReadReceipts cannot be updated"
+    )))
+}
+
+pub fn validate_delete_read_receipt(_action: Delete) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "ReadReceipts cannot be deleted"
+    )))
+}
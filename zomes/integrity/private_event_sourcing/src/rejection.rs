@@ -0,0 +1,34 @@
+use hdi::prelude::*;
+pub use private_event_sourcing_types::Rejection;
+
+use crate::validate_signed_entry;
+
+/// Maximum length in bytes of the reason of a rejection
+pub const MAX_REJECTION_REASON_BYTES: usize = 1024;
+
+pub fn validate_create_rejection(
+    _action: EntryCreationAction,
+    rejection: Rejection,
+) -> ExternResult<ValidateCallbackResult> {
+    if rejection.0.payload.content.reason.len() > MAX_REJECTION_REASON_BYTES {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "Invalid Rejection: its reason is longer than {MAX_REJECTION_REASON_BYTES} bytes."
+        )));
+    }
+    validate_signed_entry(&rejection.0, "Rejection")
+}
+
+pub fn validate_update_rejection(
+    _action: Update,
+    _rejection: Rejection,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "Rejections cannot be updated"
+    )))
+}
+
+pub fn validate_delete_rejection(_action: Delete) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "Rejections cannot be deleted"
+    )))
+}
@@ -0,0 +1,21 @@
+use hdi::prelude::*;
+use serde::de::DeserializeOwned;
+
+use crate::SignedEntry;
+
+/// Checks that the signature of the given signed entry matches its author and its payload
+pub fn validate_signed_entry<T>(
+    signed_entry: &SignedEntry<T>,
+    entry_type_name: &str,
+) -> ExternResult<ValidateCallbackResult>
+where
+    T: Clone + std::fmt::Debug + Serialize + DeserializeOwned,
+{
+    if !signed_entry.verify()? {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "Invalid {entry_type_name}: invalid signature."
+        )));
+    }
+
+    Ok(ValidateCallbackResult::Valid)
+}